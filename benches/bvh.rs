@@ -0,0 +1,179 @@
+//! Hitting scenes of many primitives through bounding volume hierarchies and instances,
+//! run with `cargo bench --bench bvh`.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rrt::bvh::Bvh;
+use rrt::objects::mesh::TriangleMesh;
+use rrt::tlas::Tlas;
+use rrt::transform::{Instance, Transform};
+use rrt::types::PositionVec;
+use rrt::{Hittable, Ray, Sphere};
+use std::f64::consts::PI;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Small spheres scattered in a cube of size 20 around the origin.
+fn spheres(count: usize) -> Vec<Sphere> {
+    let mut rng = StdRng::seed_from_u64(1);
+    (0..count)
+        .map(|_| Sphere {
+            center: PositionVec::new(
+                rng.gen_range(-10.0..10.0),
+                rng.gen_range(-10.0..10.0),
+                rng.gen_range(-10.0..10.0),
+            ),
+            radius: rng.gen_range(0.02..0.2),
+        })
+        .collect()
+}
+
+/// A unit sphere tessellated into `2 * rings * rings` triangles.
+fn tessellated_sphere(rings: u32) -> TriangleMesh {
+    let mut mesh = TriangleMesh::default();
+    for i in 0..=rings {
+        let theta = PI * i as f64 / rings as f64;
+        for j in 0..rings {
+            let phi = 2.0 * PI * j as f64 / rings as f64;
+            mesh.positions_mut().push(PositionVec::new(
+                theta.sin() * phi.cos(),
+                theta.cos(),
+                theta.sin() * phi.sin(),
+            ));
+        }
+    }
+    for i in 0..rings {
+        for j in 0..rings {
+            let v = |i: u32, j: u32| i * rings + j % rings;
+            mesh.indices_mut()
+                .push([v(i, j), v(i + 1, j), v(i + 1, j + 1)]);
+            mesh.indices_mut()
+                .push([v(i, j), v(i + 1, j + 1), v(i, j + 1)]);
+        }
+    }
+    mesh
+}
+
+/// Rays from outside the scene towards random points inside it.
+fn rays(count: usize, size: f64) -> Vec<Ray> {
+    let mut rng = StdRng::seed_from_u64(2);
+    (0..count)
+        .map(|_| {
+            let origin = PositionVec::new(0.0, 0.0, 3.0 * size);
+            let target = PositionVec::new(
+                rng.gen_range(-size..size),
+                rng.gen_range(-size..size),
+                rng.gen_range(-size..size),
+            );
+            Ray {
+                origin,
+                direction: target - origin,
+            }
+        })
+        .collect()
+}
+
+fn trace(object: &dyn Hittable, rays: &[Ray]) -> usize {
+    rays.iter()
+        .filter(|ray| object.try_hit(ray, 0.0, f64::INFINITY).is_some())
+        .count()
+}
+
+fn linear_scan(objects: &[Sphere], rays: &[Ray]) -> usize {
+    rays.iter()
+        .filter(|ray| {
+            let mut t_max = f64::INFINITY;
+            for object in objects {
+                let hit = object.try_hit(ray, 0.0, t_max);
+                if let Some(hit) = hit {
+                    t_max = hit.t;
+                }
+            }
+            t_max.is_finite()
+        })
+        .count()
+}
+
+fn bench_spheres(c: &mut Criterion) {
+    let mut group = c.benchmark_group("spheres");
+    group.measurement_time(Duration::from_secs(3));
+    let rays = rays(1000, 10.0);
+    for count in [10_000, 100_000] {
+        group.bench_with_input(BenchmarkId::new("build", count), &count, |b, &count| {
+            let objects = spheres(count);
+            b.iter_batched(
+                || objects.iter().collect::<Vec<_>>(),
+                Bvh::new,
+                criterion::BatchSize::LargeInput,
+            );
+        });
+        let bvh = Bvh::new(spheres(count));
+        group.bench_with_input(BenchmarkId::new("1000 rays", count), &bvh, |b, bvh| {
+            b.iter(|| trace(black_box(bvh), &rays));
+        });
+    }
+    // the linear scan for comparison, on fewer rays
+    let objects = spheres(10_000);
+    group.sample_size(10);
+    group.bench_function("10 rays linear scan/10000", |b| {
+        b.iter(|| linear_scan(black_box(&objects), &rays[..10]));
+    });
+    group.finish();
+}
+
+fn bench_mesh(c: &mut Criterion) {
+    let mut group = c.benchmark_group("mesh");
+    group.measurement_time(Duration::from_secs(3));
+    let rays = rays(1000, 1.0);
+    // 20000 and 180000 triangles
+    for rings in [100, 300] {
+        let mesh = tessellated_sphere(rings);
+        // build the hierarchy before measuring
+        trace(&mesh, &rays[..1]);
+        let count = mesh.triangle_count();
+        group.bench_with_input(BenchmarkId::new("1000 rays", count), &mesh, |b, mesh| {
+            b.iter(|| trace(black_box(mesh), &rays));
+        });
+    }
+    group.finish();
+}
+
+fn bench_instances(c: &mut Criterion) {
+    let mut group = c.benchmark_group("instances");
+    group.measurement_time(Duration::from_secs(3));
+    let rays = rays(1000, 10.0);
+    // a grid of 1000 copies of one mesh of 20000 triangles
+    let blas = Arc::new(tessellated_sphere(100));
+    let grid = || {
+        (0..1000).map(|i| {
+            let offset = PositionVec::new(
+                (i % 10) as f64 * 2.0 - 9.0,
+                (i / 10 % 10) as f64 * 2.0 - 9.0,
+                (i / 100) as f64 * 2.0 - 9.0,
+            );
+            Instance::new(blas.clone(), Transform::translation(&offset))
+        })
+    };
+    group.bench_function("build/1000", |b| {
+        b.iter(|| Tlas::new(grid().collect()));
+    });
+    let mut tlas = Tlas::new(grid().collect());
+    group.bench_function("1000 rays/1000", |b| {
+        b.iter(|| trace(black_box(&tlas), &rays));
+    });
+    group.bench_function("move and rebuild/1000", |b| {
+        b.iter(|| {
+            tlas.update(|instances| {
+                let up = Transform::translation(&PositionVec::new(0.0, 0.1, 0.0));
+                for instance in instances.iter_mut() {
+                    instance.transform = up * instance.transform;
+                }
+            })
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_spheres, bench_mesh, bench_instances);
+criterion_main!(benches);
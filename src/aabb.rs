@@ -0,0 +1,136 @@
+use crate::ray::Ray;
+use crate::types::{NumPosition, PositionVec, Time};
+
+/// Axis-aligned bounding box, empty if `min` is larger than `max` on any axis.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Aabb {
+    /// corner with the smallest coordinates
+    pub min: PositionVec,
+    /// corner with the largest coordinates
+    pub max: PositionVec,
+}
+
+impl Aabb {
+    /// A box containing nothing, the identity of [`Aabb::union`].
+    pub fn empty() -> Self {
+        Aabb {
+            min: PositionVec::repeat(NumPosition::INFINITY),
+            max: PositionVec::repeat(NumPosition::NEG_INFINITY),
+        }
+    }
+
+    /// The smallest box containing all `points`.
+    pub fn from_points<'a>(points: impl IntoIterator<Item = &'a PositionVec>) -> Self {
+        points.into_iter().fold(Aabb::empty(), |b, p| b.grow(p))
+    }
+
+    /// Box around `center`, extending `half` along each axis in both directions.
+    pub fn around(center: &PositionVec, half: &PositionVec) -> Self {
+        Aabb {
+            min: center - half,
+            max: center + half,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        (0..3).any(|k| self.min[k] > self.max[k])
+    }
+
+    /// The smallest box containing this box and point `p`.
+    pub fn grow(&self, p: &PositionVec) -> Self {
+        Aabb {
+            min: self.min.inf(p),
+            max: self.max.sup(p),
+        }
+    }
+
+    /// The smallest box containing both boxes.
+    pub fn union(&self, other: &Aabb) -> Self {
+        Aabb {
+            min: self.min.inf(&other.min),
+            max: self.max.sup(&other.max),
+        }
+    }
+
+    /// The eight corners, with bits 0, 1 and 2 of the index selecting the larger x, y and z.
+    pub fn corners(&self) -> [PositionVec; 8] {
+        std::array::from_fn(|k| {
+            PositionVec::from_fn(|axis, _| {
+                if k & (1 << axis) == 0 {
+                    self.min[axis]
+                } else {
+                    self.max[axis]
+                }
+            })
+        })
+    }
+
+    pub fn center(&self) -> PositionVec {
+        0.5 * (self.min + self.max)
+    }
+
+    /// Total area of the faces, zero for an empty box.
+    pub fn surface_area(&self) -> NumPosition {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let d = self.max - self.min;
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    /// Whether the ray passes through the box in time range `t1` <= t <= `t2`,
+    /// given the reciprocal of the ray direction.
+    pub fn hit(&self, ray: &Ray, inverse_direction: &PositionVec, t1: Time, t2: Time) -> bool {
+        let (mut t_near, mut t_far) = (t1, t2);
+        for axis in 0..3 {
+            let ta = (self.min[axis] - ray.origin[axis]) * inverse_direction[axis];
+            let tb = (self.max[axis] - ray.origin[axis]) * inverse_direction[axis];
+            if ta.is_nan() || tb.is_nan() {
+                // parallel to the slab and on its boundary
+                continue;
+            }
+            t_near = t_near.max(ta.min(tb));
+            t_far = t_far.min(ta.max(tb));
+        }
+        t_near <= t_far
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aabb::Aabb;
+    use crate::ray::Ray;
+    use crate::types::PositionVec;
+
+    #[test]
+    fn test_aabb() {
+        let unit = Aabb::from_points(&[PositionVec::zeros(), PositionVec::new(1.0, 2.0, 3.0)]);
+        assert_eq!(unit.center(), PositionVec::new(0.5, 1.0, 1.5));
+        assert_eq!(unit.surface_area(), 22.0);
+        assert_eq!(unit.corners()[5], PositionVec::new(1.0, 0.0, 3.0));
+        assert_eq!(Aabb::from_points(&unit.corners()), unit);
+        assert!(Aabb::empty().is_empty());
+        assert_eq!(Aabb::empty().union(&unit), unit);
+        assert_eq!(Aabb::empty().surface_area(), 0.0);
+
+        let hit = |origin: PositionVec, direction: PositionVec, t2: f64| {
+            let inverse = direction.map(|c| 1.0 / c);
+            unit.hit(&Ray { origin, direction }, &inverse, 0.0, t2)
+        };
+        let x = PositionVec::new(1.0, 0.0, 0.0);
+        assert!(hit(PositionVec::new(-1.0, 1.0, 1.0), x, 10.0));
+        assert!(!hit(PositionVec::new(-1.0, 1.0, 1.0), x, 0.5));
+        assert!(!hit(PositionVec::new(2.0, 1.0, 1.0), x, 10.0));
+        assert!(!hit(PositionVec::new(-1.0, 3.0, 1.0), x, 10.0));
+        // along a face
+        assert!(hit(PositionVec::new(-1.0, 0.0, 1.0), x, 10.0));
+        // flat boxes are hit too
+        let flat = Aabb::from_points(&[PositionVec::zeros(), PositionVec::new(1.0, 0.0, 1.0)]);
+        let down = PositionVec::new(0.0, -1.0, 0.0);
+        let ray = Ray {
+            origin: PositionVec::new(0.5, 1.0, 0.5),
+            direction: down,
+        };
+        assert!(flat.hit(&ray, &down.map(|c| 1.0 / c), 0.0, 2.0));
+    }
+}
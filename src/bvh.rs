@@ -0,0 +1,437 @@
+//! Bounding volume hierarchies, finding the objects a ray hits without testing all of them.
+
+use crate::aabb::Aabb;
+use crate::ray::Ray;
+use crate::scene::{Bounded, HitEvent, Hittable};
+use crate::types::{NumPosition, PositionVec, Time};
+
+/// number of buckets along an axis evaluated for splitting a node
+const BINS: usize = 12;
+/// largest number of primitives in a leaf
+const MAX_LEAF_SIZE: usize = 4;
+/// cost of visiting a node, relative to the cost of intersecting a primitive
+const TRAVERSAL_COST: NumPosition = 1.0;
+
+#[derive(Clone, Debug)]
+struct Node {
+    bounds: Aabb,
+    /// for leaves the first primitive in `order`,
+    /// for interior nodes the second child, the first one follows the node
+    offset: u32,
+    /// number of primitives of a leaf, zero for interior nodes
+    count: u32,
+    /// axis the primitives of an interior node are split along
+    axis: u8,
+}
+
+/// A hierarchy of bounding boxes over primitives referred to by index,
+/// built by the surface area heuristic. Primitives without bounds are left out.
+#[derive(Clone, Debug, Default)]
+pub struct BvhTree {
+    /// nodes in depth-first order, the root first
+    nodes: Vec<Node>,
+    /// primitive indices, leaves refer to ranges of it
+    order: Vec<u32>,
+}
+
+impl BvhTree {
+    pub fn new(bounds: &[Option<Aabb>]) -> Self {
+        let mut order: Vec<u32> = (0..bounds.len() as u32)
+            .filter(|&i| bounds[i as usize].is_some_and(|b| !b.is_empty()))
+            .collect();
+        let bounds: Vec<Aabb> = bounds.iter().map(|b| b.unwrap_or(Aabb::empty())).collect();
+        let centers: Vec<PositionVec> = bounds.iter().map(Aabb::center).collect();
+        let mut tree = BvhTree {
+            nodes: Vec::with_capacity(2 * order.len()),
+            order: Vec::new(),
+        };
+        if !order.is_empty() {
+            tree.build(&bounds, &centers, &mut order, 0);
+        }
+        tree.order = order;
+        tree
+    }
+
+    /// Bounds of all primitives, `None` if there are none.
+    pub fn bounds(&self) -> Option<Aabb> {
+        self.nodes.first().map(|root| root.bounds)
+    }
+
+    /// Build the subtree over `order`, which starts at `first` in the whole order,
+    /// returning the index of its root node.
+    fn build(
+        &mut self,
+        bounds: &[Aabb],
+        centers: &[PositionVec],
+        order: &mut [u32],
+        first: usize,
+    ) -> usize {
+        let index = self.nodes.len();
+        let node_bounds = order
+            .iter()
+            .fold(Aabb::empty(), |b, &i| b.union(&bounds[i as usize]));
+        let leaf = Node {
+            bounds: node_bounds,
+            offset: first as u32,
+            count: order.len() as u32,
+            axis: 0,
+        };
+        if order.len() == 1 {
+            self.nodes.push(leaf);
+            return index;
+        }
+        let center_bounds = Aabb::from_points(order.iter().map(|&i| &centers[i as usize]));
+        let (axis, split, cost) = best_split(bounds, centers, order, &node_bounds, &center_bounds);
+        if cost >= order.len() as NumPosition && order.len() <= MAX_LEAF_SIZE {
+            self.nodes.push(leaf);
+            return index;
+        }
+        let mid = if cost.is_finite() {
+            partition(order, |i| {
+                bin_of(&center_bounds, axis, &centers[*i as usize]) <= split
+            })
+        } else {
+            0
+        };
+        // split in the middle if the centers can not be told apart
+        let mid = if mid == 0 || mid == order.len() {
+            order.len() / 2
+        } else {
+            mid
+        };
+        self.nodes.push(Node {
+            offset: 0,
+            count: 0,
+            axis: axis as u8,
+            ..leaf
+        });
+        let (left, right) = order.split_at_mut(mid);
+        self.build(bounds, centers, left, first);
+        let second = self.build(bounds, centers, right, first + mid);
+        self.nodes[index].offset = second as u32;
+        index
+    }
+
+    /// Visit the primitives whose bounds the ray passes through in time range
+    /// `t1` <= t < `t2`, nearer subtrees first. `hit` is called with a primitive and
+    /// the end of the time range still to search, and returns the time and the result if
+    /// it hits the primitive in that range. Returns the result of the nearest hit.
+    pub fn traverse<R>(
+        &self,
+        ray: &Ray,
+        t1: Time,
+        t2: Time,
+        mut hit: impl FnMut(usize, Time) -> Option<(Time, R)>,
+    ) -> Option<R> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let inverse_direction = ray.direction.map(|c| 1.0 / c);
+        let mut t_max = t2;
+        let mut nearest = None;
+        let mut stack = Vec::with_capacity(32);
+        stack.push(0);
+        while let Some(index) = stack.pop() {
+            let node: &Node = &self.nodes[index];
+            if !node.bounds.hit(ray, &inverse_direction, t1, t_max) {
+                continue;
+            }
+            if node.count > 0 {
+                let start = node.offset as usize;
+                for &i in &self.order[start..start + node.count as usize] {
+                    if let Some((t, result)) = hit(i as usize, t_max) {
+                        t_max = t;
+                        nearest = Some(result);
+                    }
+                }
+            } else if ray.direction[node.axis as usize] < 0.0 {
+                // visit the child with larger coordinates first
+                stack.extend([index + 1, node.offset as usize]);
+            } else {
+                stack.extend([node.offset as usize, index + 1]);
+            }
+        }
+        nearest
+    }
+}
+
+/// Bucket of a center along `axis`, evenly dividing the bounds of the centers.
+fn bin_of(center_bounds: &Aabb, axis: usize, center: &PositionVec) -> usize {
+    let extent = center_bounds.max[axis] - center_bounds.min[axis];
+    let relative = (center[axis] - center_bounds.min[axis]) / extent;
+    ((relative * BINS as NumPosition) as usize).min(BINS - 1)
+}
+
+/// Find the axis and the last bucket of the first half with the smallest cost of splitting,
+/// relative to the cost of intersecting a primitive. Splitting costs infinity if the
+/// centers coincide on all axes, or if the node has no area like a line of points.
+fn best_split(
+    bounds: &[Aabb],
+    centers: &[PositionVec],
+    order: &[u32],
+    node_bounds: &Aabb,
+    center_bounds: &Aabb,
+) -> (usize, usize, NumPosition) {
+    let node_area = node_bounds.surface_area();
+    let mut best = (0, 0, NumPosition::INFINITY);
+    for axis in 0..3 {
+        if center_bounds.max[axis] <= center_bounds.min[axis] {
+            continue;
+        }
+        let mut bins = [(Aabb::empty(), 0usize); BINS];
+        for &i in order {
+            let bin = &mut bins[bin_of(center_bounds, axis, &centers[i as usize])];
+            bin.0 = bin.0.union(&bounds[i as usize]);
+            bin.1 += 1;
+        }
+        // sweep from the right to collect the costs of the second halves
+        let mut right = [0.0; BINS];
+        let (mut area, mut count) = (Aabb::empty(), 0);
+        for split in (1..BINS).rev() {
+            area = area.union(&bins[split].0);
+            count += bins[split].1;
+            right[split - 1] = area.surface_area() * count as NumPosition;
+        }
+        let (mut area, mut count) = (Aabb::empty(), 0);
+        for split in 0..BINS - 1 {
+            area = area.union(&bins[split].0);
+            count += bins[split].1;
+            let cost = TRAVERSAL_COST
+                + (area.surface_area() * count as NumPosition + right[split]) / node_area;
+            if cost < best.2 {
+                best = (axis, split, cost);
+            }
+        }
+    }
+    best
+}
+
+/// Reorder `items` so the ones satisfying `predicate` come first, returning their count.
+fn partition<T>(items: &mut [T], predicate: impl Fn(&T) -> bool) -> usize {
+    let mut mid = 0;
+    for i in 0..items.len() {
+        if predicate(&items[i]) {
+            items.swap(i, mid);
+            mid += 1;
+        }
+    }
+    mid
+}
+
+/// A collection of objects hit like a single object, by searching a [`BvhTree`] over the
+/// objects with bounds and testing the unbounded ones one by one.
+pub struct Bvh<H> {
+    objects: Vec<H>,
+    tree: BvhTree,
+    /// objects without bounds, like planes
+    unbounded: Vec<usize>,
+}
+
+impl<H> Bvh<H> {
+    pub fn new(objects: Vec<H>) -> Self
+    where
+        H: Bounded,
+    {
+        let mut bvh = Bvh {
+            objects,
+            tree: BvhTree::default(),
+            unbounded: Vec::new(),
+        };
+        bvh.rebuild();
+        bvh
+    }
+
+    fn rebuild(&mut self)
+    where
+        H: Bounded,
+    {
+        let bounds: Vec<Option<Aabb>> = self.objects.iter().map(|o| o.bounding_box()).collect();
+        self.unbounded = (0..bounds.len()).filter(|&i| bounds[i].is_none()).collect();
+        self.tree = BvhTree::new(&bounds);
+    }
+
+    /// Change the objects, like moving instances of a [`Tlas`](crate::tlas::Tlas),
+    /// and rebuild the hierarchy over them.
+    pub fn update(&mut self, f: impl FnOnce(&mut Vec<H>))
+    where
+        H: Bounded,
+    {
+        f(&mut self.objects);
+        self.rebuild();
+    }
+
+    pub fn objects(&self) -> &[H] {
+        &self.objects
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+}
+
+impl<H: Hittable> Hittable for Bvh<H> {
+    fn try_hit(&self, ray: &Ray, t1: Time, t2: Time) -> Option<HitEvent> {
+        let mut nearest = self.tree.traverse(ray, t1, t2, |i, t_max| {
+            let hit = self.objects[i].try_hit(ray, t1, t_max)?;
+            Some((hit.t, hit))
+        });
+        for &i in &self.unbounded {
+            let t_max = nearest.as_ref().map_or(t2, |hit: &HitEvent| hit.t);
+            if let Some(hit) = self.objects[i].try_hit(ray, t1, t_max) {
+                nearest = Some(hit);
+            }
+        }
+        nearest
+    }
+}
+
+impl<H> Bounded for Bvh<H> {
+    fn bounding_box(&self) -> Option<Aabb> {
+        if self.unbounded.is_empty() {
+            self.tree.bounds()
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bvh::Bvh;
+    use crate::objects::aabox::AxisAlignedBox;
+    use crate::objects::plane::{Plane, Quad};
+    use crate::objects::sphere::Sphere;
+    use crate::objects::triangle::Triangle;
+    use crate::ray::Ray;
+    use crate::scene::{Bounded, HitEvent, Hittable};
+    use crate::types::PositionVec;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn random_point(rng: &mut StdRng, size: f64) -> PositionVec {
+        PositionVec::new(
+            rng.gen_range(-size..size),
+            rng.gen_range(-size..size),
+            rng.gen_range(-size..size),
+        )
+    }
+
+    /// Spheres, triangles, quads and boxes of various sizes, with duplicates.
+    fn random_objects(rng: &mut StdRng, count: usize) -> Vec<Box<dyn Hittable>> {
+        let mut objects: Vec<Box<dyn Hittable>> = Vec::new();
+        for i in 0..count {
+            let center = random_point(rng, 10.0);
+            let size = rng.gen_range(0.01..2.0);
+            objects.push(match i % 5 {
+                0 | 1 => Box::new(Sphere {
+                    center,
+                    radius: size,
+                }),
+                2 => Box::new(Triangle::new(
+                    center,
+                    center + random_point(rng, size),
+                    center + random_point(rng, size),
+                )),
+                3 => Box::new(Quad {
+                    corner: center,
+                    u: random_point(rng, size),
+                    v: random_point(rng, size),
+                }),
+                _ => Box::new(AxisAlignedBox {
+                    min: center,
+                    max: center + PositionVec::repeat(size),
+                }),
+            });
+        }
+        objects.push(Box::new(Sphere {
+            center: PositionVec::zeros(),
+            radius: 1.0,
+        }));
+        objects.push(Box::new(Sphere {
+            center: PositionVec::zeros(),
+            radius: 1.0,
+        }));
+        objects
+    }
+
+    fn linear_scan(objects: &[Box<dyn Hittable>], ray: &Ray, t1: f64, t2: f64) -> Option<HitEvent> {
+        let mut nearest: Option<HitEvent> = None;
+        for object in objects {
+            let t_max = nearest.as_ref().map_or(t2, |hit| hit.t);
+            if let Some(hit) = object.try_hit(ray, t1, t_max) {
+                nearest = Some(hit);
+            }
+        }
+        nearest
+    }
+
+    #[test]
+    fn test_same_hits_as_linear_scan() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for count in [0, 1, 5, 1000] {
+            let mut objects = random_objects(&mut rng, count);
+            if count == 5 {
+                objects.push(Box::new(Plane {
+                    point: PositionVec::new(0.0, -8.0, 0.0),
+                    normal: PositionVec::new(0.0, 1.0, 0.0),
+                }));
+            }
+            let expected: Vec<_> = (0..2000)
+                .map(|i| {
+                    let ray = Ray {
+                        origin: random_point(&mut rng, 15.0),
+                        direction: if i % 10 == 0 {
+                            // axis-aligned rays have infinite reciprocal components
+                            PositionVec::new(0.0, 0.0, -1.0)
+                        } else {
+                            random_point(&mut rng, 1.0)
+                        },
+                    };
+                    let t1 = if i % 3 == 0 {
+                        rng.gen_range(0.0..5.0)
+                    } else {
+                        0.0
+                    };
+                    let t2 = if i % 4 == 0 {
+                        rng.gen_range(t1..20.0)
+                    } else {
+                        f64::INFINITY
+                    };
+                    let hit = linear_scan(&objects, &ray, t1, t2);
+                    (ray, t1, t2, hit.map(|h| (h.t, h.hit_pos, h.surface_nv)))
+                })
+                .collect();
+            let bvh = Bvh::new(objects);
+            assert_eq!(bvh.len(), count + 2 + (count == 5) as usize);
+            let mut hits = 0;
+            for (ray, t1, t2, expected) in expected {
+                let hit = bvh.try_hit(&ray, t1, t2);
+                assert_eq!(hit.map(|h| (h.t, h.hit_pos, h.surface_nv)), expected);
+                hits += expected.is_some() as usize;
+            }
+            if count >= 5 {
+                assert!(hits > 100, "only {hits} rays hit");
+            }
+        }
+    }
+
+    #[test]
+    fn test_bounds() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let bvh = Bvh::new(random_objects(&mut rng, 100));
+        let bounds = bvh.bounding_box().expect("bounded");
+        for object in bvh.objects() {
+            let b = object.bounding_box().expect("bounded");
+            assert_eq!(bounds.union(&b), bounds);
+        }
+        let planes = Bvh::new(vec![Plane {
+            point: PositionVec::zeros(),
+            normal: PositionVec::new(0.0, 1.0, 0.0),
+        }]);
+        assert!(planes.bounding_box().is_none());
+    }
+}
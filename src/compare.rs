@@ -0,0 +1,278 @@
+//! Error metrics between two images of the same size, for regression testing of
+//! noisy Monte Carlo renders where byte-exact comparison does not work.
+
+use crate::ppm::{Error, Image};
+use crate::types::{NumColorRatio, Pixel, PixelF64};
+
+/// Standard deviation of the Gaussian window used by SSIM.
+const SSIM_SIGMA: f64 = 1.5;
+/// Radius of the (11x11) Gaussian window used by SSIM.
+const SSIM_RADIUS: usize = 5;
+/// SSIM stabilizing constants for a dynamic range of 1.0
+const SSIM_C1: f64 = 0.01 * 0.01;
+const SSIM_C2: f64 = 0.03 * 0.03;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Metrics {
+    /// mean squared error over all channels
+    pub mse: f64,
+    /// peak signal-to-noise ratio in dB with peak value 1.0, infinite for identical images
+    pub psnr: f64,
+    /// mean structural similarity index over all channels, 1.0 for identical images
+    pub ssim: f64,
+    /// largest absolute difference of any channel of any pixel
+    pub max_error: f64,
+}
+
+/// Limits a comparison must satisfy, unset limits are not checked.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Tolerance {
+    pub max_mse: Option<f64>,
+    pub min_psnr: Option<f64>,
+    pub min_ssim: Option<f64>,
+    pub max_error: Option<f64>,
+}
+
+impl Metrics {
+    pub fn within(&self, tolerance: &Tolerance) -> bool {
+        tolerance.max_mse.is_none_or(|v| self.mse <= v)
+            && tolerance.min_psnr.is_none_or(|v| self.psnr >= v)
+            && tolerance.min_ssim.is_none_or(|v| self.ssim >= v)
+            && tolerance.max_error.is_none_or(|v| self.max_error <= v)
+    }
+}
+
+/// Get channels of both images as planar buffers.
+fn channels<A: Pixel, B: Pixel>(a: &Image<A>, b: &Image<B>) -> Result<[[Vec<f64>; 3]; 2], Error> {
+    if a.get_width() != b.get_width() || a.get_height() != b.get_height() {
+        return Err(Error::SizeMismatch);
+    }
+    fn planes<T: Pixel>(img: &Image<T>) -> [Vec<f64>; 3] {
+        let getters: [fn(&T) -> NumColorRatio; 3] = [T::red, T::green, T::blue];
+        getters.map(|f| img.iter().map(|(_, _, p)| f(p)).collect())
+    }
+    Ok([planes(a), planes(b)])
+}
+
+/// Mean squared error and largest absolute difference of planar channels.
+fn errors(pa: &[Vec<f64>; 3], pb: &[Vec<f64>; 3]) -> (f64, f64) {
+    let mut sum = 0.0;
+    let mut max_error: f64 = 0.0;
+    for (ca, cb) in pa.iter().zip(pb.iter()) {
+        for (x, y) in ca.iter().zip(cb.iter()) {
+            let d = (x - y).abs();
+            sum += d * d;
+            max_error = max_error.max(d);
+        }
+    }
+    (sum / (3 * pa[0].len()) as f64, max_error)
+}
+
+/// Mean SSIM of planar channels of images of size `width` x `height`.
+fn mean_ssim(pa: &[Vec<f64>; 3], pb: &[Vec<f64>; 3], width: usize, height: usize) -> f64 {
+    pa.iter()
+        .zip(pb.iter())
+        .map(|(ca, cb)| ssim_channel(ca, cb, width, height))
+        .sum::<f64>()
+        / 3.0
+}
+
+/// Compute all metrics between `a` and `b`.
+pub fn compare<A: Pixel, B: Pixel>(a: &Image<A>, b: &Image<B>) -> Result<Metrics, Error> {
+    let [pa, pb] = channels(a, b)?;
+    let (mse, max_error) = errors(&pa, &pb);
+    let (width, height) = (a.get_width() as usize, a.get_height() as usize);
+    Ok(Metrics {
+        mse,
+        psnr: psnr_from_mse(mse),
+        ssim: mean_ssim(&pa, &pb, width, height),
+        max_error,
+    })
+}
+
+pub fn mse<A: Pixel, B: Pixel>(a: &Image<A>, b: &Image<B>) -> Result<f64, Error> {
+    let [pa, pb] = channels(a, b)?;
+    Ok(errors(&pa, &pb).0)
+}
+
+pub fn psnr<A: Pixel, B: Pixel>(a: &Image<A>, b: &Image<B>) -> Result<f64, Error> {
+    Ok(psnr_from_mse(mse(a, b)?))
+}
+
+pub fn ssim<A: Pixel, B: Pixel>(a: &Image<A>, b: &Image<B>) -> Result<f64, Error> {
+    let [pa, pb] = channels(a, b)?;
+    let (width, height) = (a.get_width() as usize, a.get_height() as usize);
+    Ok(mean_ssim(&pa, &pb, width, height))
+}
+
+fn psnr_from_mse(mse: f64) -> f64 {
+    if mse == 0.0 {
+        f64::INFINITY
+    } else {
+        -10.0 * mse.log10()
+    }
+}
+
+/// Blur a plane with a separable Gaussian window. Weights are renormalized near the borders
+/// so that images smaller than the window are handled too.
+fn gaussian_blur(data: &[f64], width: usize, height: usize, kernel: &[f64]) -> Vec<f64> {
+    let r = kernel.len() / 2;
+    let pass = |src: &[f64], horizontal: bool| -> Vec<f64> {
+        let mut out = vec![0.0; src.len()];
+        for y in 0..height {
+            for x in 0..width {
+                let (pos, len) = if horizontal { (x, width) } else { (y, height) };
+                let (mut sum, mut weight) = (0.0, 0.0);
+                for (k, w) in kernel.iter().enumerate() {
+                    let p = pos + k;
+                    if p < r || p - r >= len {
+                        continue;
+                    }
+                    let i = if horizontal {
+                        y * width + p - r
+                    } else {
+                        (p - r) * width + x
+                    };
+                    sum += w * src[i];
+                    weight += w;
+                }
+                out[y * width + x] = sum / weight;
+            }
+        }
+        out
+    };
+    pass(&pass(data, true), false)
+}
+
+/// Mean SSIM of a single channel, as proposed by Wang et al. 2004.
+fn ssim_channel(a: &[f64], b: &[f64], width: usize, height: usize) -> f64 {
+    let kernel: Vec<f64> = (0..=2 * SSIM_RADIUS)
+        .map(|i| {
+            let d = i as f64 - SSIM_RADIUS as f64;
+            (-d * d / (2.0 * SSIM_SIGMA * SSIM_SIGMA)).exp()
+        })
+        .collect();
+    let blur = |v: Vec<f64>| gaussian_blur(&v, width, height, &kernel);
+    let mu_a = blur(a.to_vec());
+    let mu_b = blur(b.to_vec());
+    let aa = blur(a.iter().map(|v| v * v).collect());
+    let bb = blur(b.iter().map(|v| v * v).collect());
+    let ab = blur(a.iter().zip(b).map(|(x, y)| x * y).collect());
+    let mut sum = 0.0;
+    for i in 0..a.len() {
+        let (ma, mb) = (mu_a[i], mu_b[i]);
+        let var_a = aa[i] - ma * ma;
+        let var_b = bb[i] - mb * mb;
+        let cov = ab[i] - ma * mb;
+        sum += ((2.0 * ma * mb + SSIM_C1) * (2.0 * cov + SSIM_C2))
+            / ((ma * ma + mb * mb + SSIM_C1) * (var_a + var_b + SSIM_C2));
+    }
+    sum / a.len() as f64
+}
+
+/// Map `v` in range [0, 1] to a color of a heat map going from black over blue, cyan,
+/// green and yellow to red.
+pub fn false_color(v: f64) -> PixelF64 {
+    const STOPS: [(f64, f64, f64); 6] = [
+        (0.0, 0.0, 0.0),
+        (0.0, 0.0, 1.0),
+        (0.0, 1.0, 1.0),
+        (0.0, 1.0, 0.0),
+        (1.0, 1.0, 0.0),
+        (1.0, 0.0, 0.0),
+    ];
+    let v = if v.is_nan() { 1.0 } else { v.clamp(0.0, 1.0) };
+    let pos = v * (STOPS.len() - 1) as f64;
+    let i = (pos.floor() as usize).min(STOPS.len() - 2);
+    let t = pos - i as f64;
+    let (a, b) = (STOPS[i], STOPS[i + 1]);
+    PixelF64::new(
+        a.0 + (b.0 - a.0) * t,
+        a.1 + (b.1 - a.1) * t,
+        a.2 + (b.2 - a.2) * t,
+    )
+}
+
+/// Visualize the per-pixel maximum channel error between `a` and `b` as a false-color image.
+/// Errors greater than or equal to `scale` are shown in full red.
+pub fn diff_image<A: Pixel, B: Pixel>(
+    a: &Image<A>,
+    b: &Image<B>,
+    scale: f64,
+) -> Result<Image<PixelF64>, Error> {
+    if a.get_width() != b.get_width() || a.get_height() != b.get_height() {
+        return Err(Error::SizeMismatch);
+    }
+    let mut out = Image::new(a.get_width(), a.get_height());
+    for ((x, y, pa), (_, _, pb)) in a.iter().zip(b.iter()) {
+        let e = (pa.red() - pb.red())
+            .abs()
+            .max((pa.green() - pb.green()).abs())
+            .max((pa.blue() - pb.blue()).abs());
+        out.set_pixel(x, y, false_color(e / scale));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compare::{compare, diff_image, mse, psnr, ssim, Tolerance};
+    use crate::ppm::{Error, Image};
+    use crate::testing;
+    use crate::types::{Pixel, PixelF64, PixelU8};
+
+    #[test]
+    fn test_identical_images() {
+        let img: Image<PixelU8> = Image::load(&testing::path("1.100x100.ppm")).expect("load");
+        let metrics = compare(&img, &img).expect("compare");
+        assert_eq!(metrics.mse, 0.0);
+        assert_eq!(metrics.psnr, f64::INFINITY);
+        assert!((metrics.ssim - 1.0).abs() < 1e-12);
+        assert_eq!(metrics.max_error, 0.0);
+    }
+
+    #[test]
+    fn test_noisy_image_within_tolerance() {
+        let expected: Image<PixelU8> = Image::load(&testing::path("1.300x200.ppm")).expect("load");
+        let mut noisy: Image<PixelF64> = Image::new(300, 200);
+        for ((_, _, p), (x, y, _)) in noisy.iter_mut().zip(expected.iter()) {
+            // deterministic pseudo-random noise of amplitude 0.01
+            let n = (((x * 7919 + y * 104729) % 200) as f64 / 100.0 - 1.0) * 0.01;
+            let e = expected.get_pixel(x, y);
+            *p = PixelF64::new(e.red() + n, e.green() - n, e.blue() + n);
+        }
+        let metrics = compare(&expected, &noisy).expect("compare");
+        assert!(metrics.max_error <= 0.01 + 1e-9);
+        assert!(metrics.psnr > 40.0);
+        // single metrics agree with the full comparison
+        assert_eq!(mse(&expected, &noisy).unwrap(), metrics.mse);
+        assert_eq!(psnr(&expected, &noisy).unwrap(), metrics.psnr);
+        assert_eq!(ssim(&expected, &noisy).unwrap(), metrics.ssim);
+        assert!(metrics.within(&Tolerance {
+            min_psnr: Some(40.0),
+            min_ssim: Some(0.9),
+            ..Default::default()
+        }));
+        assert!(!metrics.within(&Tolerance {
+            max_error: Some(0.001),
+            ..Default::default()
+        }));
+    }
+
+    #[test]
+    fn test_diff_image() {
+        let a: Image<PixelF64> = Image::new(2, 1);
+        let mut b: Image<PixelF64> = Image::new(2, 1);
+        b.set_pixel(1, 0, PixelF64::new(0.0, 0.5, 0.0));
+        let diff = diff_image(&a, &b, 0.5).expect("diff");
+        let (same, different) = (diff.get_pixel(0, 0), diff.get_pixel(1, 0));
+        assert_eq!((same.red(), same.green(), same.blue()), (0.0, 0.0, 0.0));
+        assert_eq!(
+            (different.red(), different.green(), different.blue()),
+            (1.0, 0.0, 0.0)
+        );
+        let c: Image<PixelF64> = Image::new(1, 2);
+        assert!(matches!(compare(&a, &c), Err(Error::SizeMismatch)));
+        assert!(matches!(psnr(&a, &c), Err(Error::SizeMismatch)));
+    }
+}
@@ -0,0 +1,768 @@
+//! OpenEXR writer for single-part scanline images with 32-bit float channels.
+//! Several images of the same size can be stored in one file as named layers,
+//! following the `layer.channel` naming convention understood by compositing software.
+//!
+//! The reader loads the default layer of single-part scanline files with uncompressed,
+//! RLE, ZIPS or ZIP compressed blocks, the compression methods of most renderers.
+
+use crate::ppm::Error::{
+    InvalidArgument, InvalidData, InvalidHeader, SizeMismatch, TruncatedData, UnsupportedFormat,
+};
+use crate::ppm::{create_file, Error, Image, ImageSize};
+use crate::types::{NumColorRatio, Pixel};
+use crate::zlib;
+use std::io::Write;
+use std::path::Path;
+use std::{fs, io};
+
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+/// version 2, single-part scanline file with short attribute names
+const VERSION: [u8; 4] = [2, 0, 0, 0];
+/// version flags of tiled, deep and multi-part files
+const VERSION_FLAGS_UNSUPPORTED: u32 = 0x200 | 0x800 | 0x1000;
+const PIXEL_TYPE_UINT: i32 = 0;
+const PIXEL_TYPE_HALF: i32 = 1;
+const PIXEL_TYPE_FLOAT: i32 = 2;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum ExrCompression {
+    None,
+    /// run-length encoding, one scanline per block
+    Rle,
+    /// zlib deflate, 16 scanlines per block
+    #[default]
+    Zip,
+}
+
+impl ExrCompression {
+    fn id(&self) -> u8 {
+        match self {
+            ExrCompression::None => 0,
+            ExrCompression::Rle => 1,
+            ExrCompression::Zip => 3,
+        }
+    }
+
+    fn lines_per_block(&self) -> u32 {
+        match self {
+            ExrCompression::None | ExrCompression::Rle => 1,
+            ExrCompression::Zip => 16,
+        }
+    }
+}
+
+/// Which channels of the pixels are written for a layer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ExrChannels {
+    /// `R`, `G` and `B` channels
+    Rgb,
+    /// a single `Y` channel taken from the red component, for data like depth
+    Y,
+}
+
+pub struct ExrLayer<'a, T: Pixel> {
+    /// layer name, an empty name means the default (beauty) layer
+    pub name: &'a str,
+    pub image: &'a Image<T>,
+    pub channels: ExrChannels,
+}
+
+type SampleGetter<T> = fn(&T) -> NumColorRatio;
+
+/// A channel to be written, with its sample getter.
+struct Channel<'a, T: Pixel> {
+    name: String,
+    image: &'a Image<T>,
+    sample: SampleGetter<T>,
+}
+
+fn attribute<W: Write>(w: &mut W, name: &str, kind: &str, value: &[u8]) -> io::Result<()> {
+    w.write_all(name.as_bytes())?;
+    w.write_all(&[0])?;
+    w.write_all(kind.as_bytes())?;
+    w.write_all(&[0])?;
+    w.write_all(&(value.len() as i32).to_le_bytes())?;
+    w.write_all(value)
+}
+
+/// Byte interleaving and delta prediction applied before RLE and ZIP compression,
+/// which puts the similar high bytes of neighbouring samples together.
+fn predict(data: &[u8]) -> Vec<u8> {
+    let half = data.len().div_ceil(2);
+    let mut out = vec![0u8; data.len()];
+    for (i, &v) in data.iter().enumerate() {
+        out[if i % 2 == 0 { i / 2 } else { half + i / 2 }] = v;
+    }
+    let mut prev = out.first().copied().unwrap_or(0);
+    for v in out.iter_mut().skip(1) {
+        let cur = *v;
+        *v = cur.wrapping_sub(prev).wrapping_add(128);
+        prev = cur;
+    }
+    out
+}
+
+/// OpenEXR flavour of run-length encoding: a non-negative count byte `n` is followed by
+/// one byte repeated `n + 1` times, a negative count `-n` is followed by `n` literal bytes.
+fn rle(data: &[u8]) -> Vec<u8> {
+    const MAX_RUN: usize = 127;
+    const MIN_RUN: usize = 3;
+    let run_at = |i: usize| {
+        data[i..]
+            .iter()
+            .take(MAX_RUN + 1)
+            .take_while(|&&v| v == data[i])
+            .count()
+    };
+    let mut out = Vec::with_capacity(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        let run = run_at(i);
+        if run >= MIN_RUN {
+            out.push((run - 1) as u8);
+            out.push(data[i]);
+            i += run;
+            continue;
+        }
+        let start = i;
+        while i < data.len() && i - start < MAX_RUN && run_at(i) < MIN_RUN {
+            i += 1;
+        }
+        out.push((-((i - start) as i8)) as u8);
+        out.extend_from_slice(&data[start..i]);
+    }
+    out
+}
+
+/// Inverse of [`predict`].
+fn unpredict(data: &[u8]) -> Vec<u8> {
+    let mut t = data.to_vec();
+    for i in 1..t.len() {
+        t[i] = t[i - 1].wrapping_add(t[i]).wrapping_sub(128);
+    }
+    let half = t.len().div_ceil(2);
+    (0..t.len())
+        .map(|i| t[if i % 2 == 0 { i / 2 } else { half + i / 2 }])
+        .collect()
+}
+
+/// Inverse of [`rle`].
+fn unrle(data: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let n = data[i] as i8;
+        if n < 0 {
+            let n = n.unsigned_abs() as usize;
+            out.extend_from_slice(data.get(i + 1..i + 1 + n).ok_or(TruncatedData)?);
+            i += 1 + n;
+        } else {
+            let v = *data.get(i + 1).ok_or(TruncatedData)?;
+            out.extend(std::iter::repeat_n(v, n as usize + 1));
+            i += 2;
+        }
+    }
+    Ok(out)
+}
+
+/// Convert a 16-bit IEEE 754 half precision float.
+fn half_to_f32(h: u16) -> f32 {
+    let sign = if h & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = (h >> 10) & 0x1f;
+    let mantissa = (h & 0x3ff) as f32;
+    sign * match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        31 if mantissa == 0.0 => f32::INFINITY,
+        31 => f32::NAN,
+        e => (1.0 + mantissa / 1024.0) * 2f32.powi(e as i32 - 15),
+    }
+}
+
+fn read_cstr<'a>(data: &'a [u8], pos: &mut usize) -> Result<&'a [u8], Error> {
+    let rest = data.get(*pos..).ok_or(TruncatedData)?;
+    let len = rest.iter().position(|&c| c == 0).ok_or(TruncatedData)?;
+    *pos += len + 1;
+    Ok(&rest[..len])
+}
+
+fn read_i32(data: &[u8], pos: usize) -> Result<i32, Error> {
+    let b = data.get(pos..pos + 4).ok_or(TruncatedData)?;
+    Ok(i32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// A channel of a file being read.
+struct ChannelInfo {
+    name: Vec<u8>,
+    pixel_type: i32,
+}
+
+impl ChannelInfo {
+    fn sample_size(&self) -> usize {
+        if self.pixel_type == PIXEL_TYPE_HALF {
+            2
+        } else {
+            4
+        }
+    }
+
+    fn sample(&self, b: &[u8]) -> NumColorRatio {
+        match self.pixel_type {
+            PIXEL_TYPE_UINT => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as NumColorRatio,
+            PIXEL_TYPE_HALF => half_to_f32(u16::from_le_bytes([b[0], b[1]])) as NumColorRatio,
+            _ => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as NumColorRatio,
+        }
+    }
+}
+
+fn parse_channels(chlist: &[u8]) -> Result<Vec<ChannelInfo>, Error> {
+    let mut channels = Vec::new();
+    let mut pos = 0;
+    while *chlist.get(pos).ok_or(TruncatedData)? != 0 {
+        let name = read_cstr(chlist, &mut pos)?.to_vec();
+        let pixel_type = read_i32(chlist, pos)?;
+        if !(PIXEL_TYPE_UINT..=PIXEL_TYPE_FLOAT).contains(&pixel_type) {
+            return Err(InvalidHeader("unknown channel pixel type"));
+        }
+        // pLinear and reserved bytes, then x and y sampling rates
+        if read_i32(chlist, pos + 8)? != 1 || read_i32(chlist, pos + 12)? != 1 {
+            return Err(UnsupportedFormat("subsampled OpenEXR channels".to_string()));
+        }
+        pos += 16;
+        channels.push(ChannelInfo { name, pixel_type });
+    }
+    Ok(channels)
+}
+
+impl<T: Pixel> Image<T> {
+    /// Load the default layer of an OpenEXR file.
+    pub fn load_exr(path: &Path) -> Result<Self, Error> {
+        let data = fs::read(path)?;
+        Self::decode_exr(&data)
+    }
+
+    /// Decode the default layer of an OpenEXR image from its file content, from its `R`,
+    /// `G` and `B` channels or a single `Y` channel for grayscale images.
+    pub fn decode_exr(data: &[u8]) -> Result<Self, Error> {
+        if !data.starts_with(&MAGIC) {
+            return Err(InvalidHeader("missing OpenEXR magic number"));
+        }
+        let version = read_i32(data, 4)? as u32;
+        if version & 0xff != 2 {
+            return Err(InvalidHeader("unsupported OpenEXR version"));
+        }
+        if version & VERSION_FLAGS_UNSUPPORTED != 0 {
+            return Err(UnsupportedFormat(
+                "tiled, deep or multi-part OpenEXR".to_string(),
+            ));
+        }
+        let mut pos = 8;
+        let (mut channels, mut compression, mut window) = (None, None, None);
+        while *data.get(pos).ok_or(TruncatedData)? != 0 {
+            let name = read_cstr(data, &mut pos)?;
+            read_cstr(data, &mut pos)?;
+            let size = usize::try_from(read_i32(data, pos)?)
+                .map_err(|_| InvalidHeader("negative attribute size"))?;
+            let value = data.get(pos + 4..pos + 4 + size).ok_or(TruncatedData)?;
+            pos += 4 + size;
+            match name {
+                b"channels" => channels = Some(parse_channels(value)?),
+                b"compression" => compression = value.first().copied(),
+                b"dataWindow" => {
+                    let mut v = [0; 4];
+                    for (i, c) in v.iter_mut().enumerate() {
+                        *c = read_i32(value, 4 * i)?;
+                    }
+                    window = Some(v);
+                }
+                _ => {}
+            }
+        }
+        pos += 1;
+        let channels = channels.ok_or(InvalidHeader("missing channels attribute"))?;
+        let [x_min, y_min, x_max, y_max] = window.ok_or(InvalidHeader("missing dataWindow"))?;
+        let compression = compression.ok_or(InvalidHeader("missing compression attribute"))?;
+        let lines = match compression {
+            0..=2 => 1,
+            3 => 16,
+            id => return Err(UnsupportedFormat(format!("OpenEXR compression {id}"))),
+        };
+        let size = |min: i32, max: i32| {
+            ImageSize::try_from(max as i64 - min as i64 + 1)
+                .ok()
+                .filter(|&s| s > 0)
+                .ok_or(InvalidHeader("invalid data window"))
+        };
+        let (width, height) = (size(x_min, x_max)?, size(y_min, y_max)?);
+        // red, green and blue are read from the channel at these indices
+        let find = |name: &[u8]| channels.iter().position(|c| c.name == name);
+        let mut rgb = [0; 3];
+        for (index, name) in rgb.iter_mut().zip([b"R", b"G", b"B"]) {
+            *index = find(name)
+                .or_else(|| find(b"Y"))
+                .ok_or(InvalidHeader("no R, G, B or Y channels"))?;
+        }
+
+        let w = width as usize;
+        let line_size: usize = channels.iter().map(|c| c.sample_size() * w).sum();
+        let blocks = height.div_ceil(lines) as usize;
+        // reject impossible sizes before allocating the pixel buffer, deflate does not
+        // compress better than 1032:1
+        let remaining = (data.len() - pos) as u64;
+        let max_ratio = if compression == 0 { 1 } else { 1032 };
+        if remaining / 8 < blocks as u64 || remaining * max_ratio < line_size as u64 * height as u64
+        {
+            return Err(TruncatedData);
+        }
+        let mut img = Image::new(width, height);
+        for b in 0..blocks {
+            let offset = data[pos + 8 * b..pos + 8 * b + 8]
+                .try_into()
+                .map(u64::from_le_bytes)
+                .expect("offset is 8 bytes");
+            let offset = usize::try_from(offset).map_err(|_| TruncatedData)?;
+            let y0 = (read_i32(data, offset)? as i64 - y_min as i64)
+                .try_into()
+                .ok()
+                .filter(|&y: &ImageSize| y < height && y % lines == 0)
+                .ok_or(InvalidData("block outside of data window"))?;
+            let block_size = usize::try_from(read_i32(data, offset + 4)?)
+                .map_err(|_| InvalidData("negative block size"))?;
+            let block = data
+                .get(offset + 8..offset + 8 + block_size)
+                .ok_or(TruncatedData)?;
+            let n_lines = lines.min(height - y0) as usize;
+            let expected_size = n_lines * line_size;
+            // blocks that do not shrink are stored uncompressed
+            let raw = if compression == 0 || block_size == expected_size {
+                block.to_vec()
+            } else if compression == 1 {
+                unpredict(&unrle(block)?)
+            } else {
+                let inflated =
+                    zlib::decompress(block).map_err(|_| InvalidData("corrupted image data"))?;
+                unpredict(&inflated)
+            };
+            if raw.len() != expected_size {
+                return Err(InvalidData("block size mismatch"));
+            }
+            for (i, line) in raw.chunks_exact(line_size).enumerate() {
+                // samples of all channels, one channel after the other
+                let mut samples = Vec::with_capacity(channels.len() * w);
+                let mut rest = line;
+                for c in &channels {
+                    let (bytes, tail) = rest.split_at(c.sample_size() * w);
+                    samples.extend(bytes.chunks_exact(c.sample_size()).map(|b| c.sample(b)));
+                    rest = tail;
+                }
+                let y = y0 + i as ImageSize;
+                for x in 0..w {
+                    let [r, g, b] = rgb.map(|c| samples[c * w + x]);
+                    img.set_pixel(x as ImageSize, y, T::from_rgb_normalized(r, g, b));
+                }
+            }
+        }
+        Ok(img)
+    }
+}
+
+fn compress_block(raw: Vec<u8>, compression: ExrCompression) -> Vec<u8> {
+    let compressed = match compression {
+        ExrCompression::None => return raw,
+        ExrCompression::Rle => rle(&predict(&raw)),
+        ExrCompression::Zip => zlib::compress(&predict(&raw)),
+    };
+    // blocks that do not shrink are stored uncompressed
+    if compressed.len() < raw.len() {
+        compressed
+    } else {
+        raw
+    }
+}
+
+/// Write `layers` into an OpenEXR file. All layers must have the same size.
+pub fn save_exr<T: Pixel>(
+    path: &Path,
+    layers: &[ExrLayer<T>],
+    compression: ExrCompression,
+) -> Result<(), Error> {
+    let mut writer = create_file(path)?;
+    encode_exr(&mut writer, layers, compression)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Write `layers` in OpenEXR format to `w`. All layers must have the same size.
+pub fn encode_exr<T: Pixel, W: Write>(
+    w: &mut W,
+    layers: &[ExrLayer<T>],
+    compression: ExrCompression,
+) -> Result<(), Error> {
+    let first = layers
+        .first()
+        .ok_or(InvalidArgument("at least one layer is required"))?;
+    let (width, height) = (first.image.get_width(), first.image.get_height());
+    let mut channels: Vec<Channel<T>> = Vec::new();
+    for layer in layers {
+        if layer.image.get_width() != width || layer.image.get_height() != height {
+            return Err(SizeMismatch);
+        }
+        let prefix = if layer.name.is_empty() {
+            String::new()
+        } else {
+            format!("{}.", layer.name)
+        };
+        let getters: &[(&str, SampleGetter<T>)] = match layer.channels {
+            ExrChannels::Rgb => &[("R", T::red), ("G", T::green), ("B", T::blue)],
+            ExrChannels::Y => &[("Y", T::red)],
+        };
+        for &(name, sample) in getters {
+            channels.push(Channel {
+                name: format!("{prefix}{name}"),
+                image: layer.image,
+                sample,
+            });
+        }
+    }
+    // channels must be stored in alphabetical order
+    channels.sort_by(|a, b| a.name.cmp(&b.name));
+    if channels.windows(2).any(|c| c[0].name == c[1].name) {
+        return Err(InvalidArgument("duplicate channel name"));
+    }
+
+    let mut header = Vec::new();
+    header.write_all(&MAGIC)?;
+    header.write_all(&VERSION)?;
+    let mut chlist = Vec::new();
+    for c in &channels {
+        chlist.extend_from_slice(c.name.as_bytes());
+        chlist.push(0);
+        chlist.extend_from_slice(&PIXEL_TYPE_FLOAT.to_le_bytes());
+        // pLinear and reserved bytes, then x and y sampling rates
+        chlist.extend_from_slice(&[0, 0, 0, 0]);
+        chlist.extend_from_slice(&1i32.to_le_bytes());
+        chlist.extend_from_slice(&1i32.to_le_bytes());
+    }
+    chlist.push(0);
+    attribute(&mut header, "channels", "chlist", &chlist)?;
+    attribute(
+        &mut header,
+        "compression",
+        "compression",
+        &[compression.id()],
+    )?;
+    let mut window = Vec::with_capacity(16);
+    for v in [0, 0, width as i32 - 1, height as i32 - 1] {
+        window.extend_from_slice(&v.to_le_bytes());
+    }
+    attribute(&mut header, "dataWindow", "box2i", &window)?;
+    attribute(&mut header, "displayWindow", "box2i", &window)?;
+    // increasing y
+    attribute(&mut header, "lineOrder", "lineOrder", &[0])?;
+    attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1.0f32.to_le_bytes(),
+    )?;
+    attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8])?;
+    attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1.0f32.to_le_bytes(),
+    )?;
+    header.write_all(&[0])?;
+
+    let lines = compression.lines_per_block();
+    let mut blocks = Vec::new();
+    for y0 in (0..height).step_by(lines as usize) {
+        let mut raw = Vec::new();
+        for y in y0..(y0 + lines).min(height) {
+            for c in &channels {
+                for x in 0..width {
+                    let v = (c.sample)(&c.image.get_pixel(x, y)) as f32;
+                    raw.extend_from_slice(&v.to_le_bytes());
+                }
+            }
+        }
+        blocks.push((y0, compress_block(raw, compression)));
+    }
+    w.write_all(&header)?;
+    // the offset table points to the absolute file position of each block
+    let mut offset = (header.len() + 8 * blocks.len()) as u64;
+    for (_, data) in &blocks {
+        w.write_all(&offset.to_le_bytes())?;
+        offset += 8 + data.len() as u64;
+    }
+    for (y, data) in &blocks {
+        w.write_all(&(*y as i32).to_le_bytes())?;
+        w.write_all(&(data.len() as i32).to_le_bytes())?;
+        w.write_all(data)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::exr::{encode_exr, ExrChannels, ExrCompression, ExrLayer};
+    use crate::ppm::{Error, Image};
+    use crate::types::{Pixel, PixelF64};
+    use crate::zlib;
+    use std::collections::HashMap;
+
+    fn read_cstr(data: &[u8], pos: &mut usize) -> String {
+        let len = data[*pos..].iter().position(|&c| c == 0).unwrap();
+        let s = String::from_utf8(data[*pos..*pos + len].to_vec()).unwrap();
+        *pos += len + 1;
+        s
+    }
+
+    fn i32_at(data: &[u8], pos: usize) -> i32 {
+        i32::from_le_bytes(data[pos..pos + 4].try_into().unwrap())
+    }
+
+    /// Decode an EXR file written by `encode_exr`, returning samples of each channel.
+    fn decode(data: &[u8]) -> HashMap<String, Vec<f32>> {
+        assert_eq!(&data[..8], &[0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0]);
+        let mut pos = 8;
+        let mut attributes = HashMap::new();
+        while data[pos] != 0 {
+            let name = read_cstr(data, &mut pos);
+            let _kind = read_cstr(data, &mut pos);
+            let size = i32_at(data, pos) as usize;
+            attributes.insert(name, data[pos + 4..pos + 4 + size].to_vec());
+            pos += 4 + size;
+        }
+        pos += 1;
+        let chlist = &attributes["channels"];
+        let mut names = Vec::new();
+        let mut p = 0;
+        while chlist[p] != 0 {
+            names.push(read_cstr(chlist, &mut p));
+            assert_eq!(i32_at(chlist, p), 2, "expected float channels");
+            p += 16;
+        }
+        let window = &attributes["dataWindow"];
+        let width = i32_at(window, 8) as usize + 1;
+        let height = i32_at(window, 12) as usize + 1;
+        let compression = attributes["compression"][0];
+        let lines = if compression == 3 { 16 } else { 1 };
+        let blocks = height.div_ceil(lines);
+        let mut channels: HashMap<String, Vec<f32>> = HashMap::new();
+        for b in 0..blocks {
+            let offset = u64::from_le_bytes(data[pos + 8 * b..pos + 8 * b + 8].try_into().unwrap());
+            let offset = offset as usize;
+            let y0 = i32_at(data, offset) as usize;
+            assert_eq!(y0, b * lines);
+            let size = i32_at(data, offset + 4) as usize;
+            let chunk = &data[offset + 8..offset + 8 + size];
+            let n_lines = lines.min(height - y0);
+            let expected_size = n_lines * width * names.len() * 4;
+            let raw = if size == expected_size {
+                chunk.to_vec()
+            } else if compression == 1 {
+                super::unpredict(&super::unrle(chunk).expect("unrle block"))
+            } else {
+                super::unpredict(&zlib::decompress(chunk).expect("decompress block"))
+            };
+            assert_eq!(raw.len(), expected_size);
+            let mut samples = raw
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes(b.try_into().unwrap()));
+            for _ in 0..n_lines {
+                for name in &names {
+                    let channel = channels.entry(name.clone()).or_default();
+                    channel.extend(samples.by_ref().take(width));
+                }
+            }
+        }
+        channels
+    }
+
+    #[test]
+    fn test_exr_layers() {
+        let (width, height) = (37, 21);
+        let mut beauty: Image<PixelF64> = Image::new(width, height);
+        let mut depth: Image<PixelF64> = Image::new(width, height);
+        let mut normal: Image<PixelF64> = Image::new(width, height);
+        for (x, y, pix) in beauty.iter_mut() {
+            *pix = PixelF64::new(x as f64 * 3.0, y as f64 / 7.0, 0.25);
+        }
+        for (x, y, pix) in depth.iter_mut() {
+            *pix = PixelF64::new((x + y) as f64, 0.0, 0.0);
+        }
+        for (x, _, pix) in normal.iter_mut() {
+            *pix = PixelF64::new(0.0, 1.0, -(x as f64));
+        }
+        let layers = [
+            ExrLayer {
+                name: "",
+                image: &beauty,
+                channels: ExrChannels::Rgb,
+            },
+            ExrLayer {
+                name: "depth",
+                image: &depth,
+                channels: ExrChannels::Y,
+            },
+            ExrLayer {
+                name: "normal",
+                image: &normal,
+                channels: ExrChannels::Rgb,
+            },
+        ];
+        for compression in [
+            ExrCompression::None,
+            ExrCompression::Rle,
+            ExrCompression::Zip,
+        ] {
+            let mut data = Vec::new();
+            encode_exr(&mut data, &layers, compression).expect("encode exr");
+            let channels = decode(&data);
+            let mut names: Vec<&String> = channels.keys().collect();
+            names.sort();
+            assert_eq!(
+                names,
+                ["B", "G", "R", "depth.Y", "normal.B", "normal.G", "normal.R"]
+            );
+            for (i, (_, _, pix)) in beauty.iter().enumerate() {
+                assert_eq!(channels["R"][i], pix.red() as f32);
+                assert_eq!(channels["G"][i], pix.green() as f32);
+                assert_eq!(channels["B"][i], pix.blue() as f32);
+            }
+            for (i, (_, _, pix)) in depth.iter().enumerate() {
+                assert_eq!(channels["depth.Y"][i], pix.red() as f32);
+            }
+            for (i, (_, _, pix)) in normal.iter().enumerate() {
+                assert_eq!(channels["normal.B"][i], pix.blue() as f32);
+            }
+        }
+    }
+
+    #[test]
+    fn test_exr_invalid_layers() {
+        let a: Image<PixelF64> = Image::new(2, 2);
+        let b: Image<PixelF64> = Image::new(2, 3);
+        let layer = |name, image| ExrLayer {
+            name,
+            image,
+            channels: ExrChannels::Rgb,
+        };
+        let mut data = Vec::new();
+        assert!(matches!(
+            encode_exr(
+                &mut data,
+                &[layer("", &a), layer("x", &b)],
+                ExrCompression::Zip
+            ),
+            Err(Error::SizeMismatch)
+        ));
+        assert!(matches!(
+            encode_exr(
+                &mut data,
+                &[layer("x", &a), layer("x", &a)],
+                ExrCompression::Zip
+            ),
+            Err(Error::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn test_decode_exr() {
+        let (width, height) = (19, 35);
+        let mut img: Image<PixelF64> = Image::new(width, height);
+        for (x, y, pix) in img.iter_mut() {
+            *pix = PixelF64::new(x as f64 * 3.0, y as f64 / 8.0, -0.25);
+        }
+        let mut depth: Image<PixelF64> = Image::new(width, height);
+        for (x, y, pix) in depth.iter_mut() {
+            *pix = PixelF64::new((x * y) as f64, 0.0, 0.0);
+        }
+        for compression in [
+            ExrCompression::None,
+            ExrCompression::Rle,
+            ExrCompression::Zip,
+        ] {
+            let layers = [
+                ExrLayer {
+                    name: "",
+                    image: &img,
+                    channels: ExrChannels::Rgb,
+                },
+                ExrLayer {
+                    name: "depth",
+                    image: &depth,
+                    channels: ExrChannels::Y,
+                },
+            ];
+            let mut data = Vec::new();
+            encode_exr(&mut data, &layers, compression).expect("encode exr");
+            let decoded: Image<PixelF64> = Image::decode_exr(&data).expect("decode exr");
+            assert_eq!(decoded.get_width(), width);
+            assert_eq!(decoded.get_height(), height);
+            for (x, y, pix) in img.iter() {
+                assert_eq!(decoded.get_pixel(x, y), *pix);
+            }
+
+            // a single Y channel is read as gray
+            let mut data = Vec::new();
+            encode_exr(
+                &mut data,
+                &[ExrLayer {
+                    name: "",
+                    ..layers[1]
+                }],
+                compression,
+            )
+            .expect("encode exr");
+            let gray: Image<PixelF64> = Image::decode_exr(&data).expect("decode exr");
+            let v = (5 * 7) as f64;
+            assert_eq!(gray.get_pixel(5, 7), PixelF64::new(v, v, v));
+
+            assert!(matches!(
+                Image::<PixelF64>::decode_exr(&data[..data.len() - 1]),
+                Err(Error::TruncatedData)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_decode_exr_half() {
+        assert_eq!(super::half_to_f32(0x3c00), 1.0);
+        assert_eq!(super::half_to_f32(0xc000), -2.0);
+        assert_eq!(super::half_to_f32(0x0001), 2f32.powi(-24));
+        assert_eq!(super::half_to_f32(0x7c00), f32::INFINITY);
+        assert!(super::half_to_f32(0x7e00).is_nan());
+
+        // 2x1 image with half R, G, B channels, uncompressed
+        let mut data = vec![0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0];
+        let mut chlist = Vec::new();
+        for name in [b"B", b"G", b"R"] {
+            chlist.extend_from_slice(name);
+            chlist.push(0);
+            chlist.extend_from_slice(&1i32.to_le_bytes());
+            chlist.extend_from_slice(&[0, 0, 0, 0]);
+            chlist.extend_from_slice(&1i32.to_le_bytes());
+            chlist.extend_from_slice(&1i32.to_le_bytes());
+        }
+        chlist.push(0);
+        super::attribute(&mut data, "channels", "chlist", &chlist).unwrap();
+        super::attribute(&mut data, "compression", "compression", &[0]).unwrap();
+        let window: Vec<u8> = [3i32, 5, 4, 5]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        super::attribute(&mut data, "dataWindow", "box2i", &window).unwrap();
+        data.push(0);
+        data.extend_from_slice(&(data.len() as u64 + 8).to_le_bytes());
+        data.extend_from_slice(&5i32.to_le_bytes());
+        data.extend_from_slice(&12i32.to_le_bytes());
+        // B, G and R of both pixels
+        for v in [0x0000u16, 0x3800, 0x3c00, 0x4000, 0x3c00, 0xc000] {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+        let img: Image<PixelF64> = Image::decode_exr(&data).expect("decode exr");
+        assert_eq!(img.get_pixel(0, 0), PixelF64::new(1.0, 1.0, 0.0));
+        assert_eq!(img.get_pixel(1, 0), PixelF64::new(-2.0, 2.0, 0.5));
+    }
+}
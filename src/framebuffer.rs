@@ -0,0 +1,125 @@
+use crate::ppm::{Error, Image, ImageSize};
+use crate::types::{NumColorRatio, Pixel, PixelF64};
+use nalgebra::Vector3;
+
+/// Accumulates samples of every pixel in double precision, independent of the pixel type
+/// of sampled and resolved images, so that averaging many samples does not lose precision
+/// or overflow narrow pixel types.
+pub struct AccumulationBuffer {
+    width: ImageSize,
+    height: ImageSize,
+    /// sum of all samples of each pixel
+    sums: Vec<Vector3<NumColorRatio>>,
+    /// number of samples of each pixel
+    counts: Vec<u32>,
+}
+
+impl AccumulationBuffer {
+    pub fn new(width: ImageSize, height: ImageSize) -> Self {
+        let n = width as usize * height as usize;
+        AccumulationBuffer {
+            width,
+            height,
+            sums: vec![Vector3::zeros(); n],
+            counts: vec![0; n],
+        }
+    }
+
+    pub fn get_width(&self) -> ImageSize {
+        self.width
+    }
+
+    pub fn get_height(&self) -> ImageSize {
+        self.height
+    }
+
+    /// Total number of samples of all pixels.
+    pub fn total_samples(&self) -> u64 {
+        self.counts.iter().map(|&c| c as u64).sum()
+    }
+
+    pub fn add_sample<T: Pixel>(&mut self, x: ImageSize, y: ImageSize, sample: &T) {
+        let i = x as usize + y as usize * self.width as usize;
+        self.sums[i] += Vector3::new(sample.red(), sample.green(), sample.blue());
+        self.counts[i] += 1;
+    }
+
+    /// Add every pixel of `image` as one sample of the corresponding pixel.
+    pub fn add_image<T: Pixel>(&mut self, image: &Image<T>) -> Result<(), Error> {
+        if image.get_width() != self.width || image.get_height() != self.height {
+            return Err(Error::SizeMismatch);
+        }
+        for (x, y, pixel) in image.iter() {
+            self.add_sample(x, y, pixel);
+        }
+        Ok(())
+    }
+
+    /// Merge samples accumulated in another buffer of the same size into this one.
+    pub fn merge(&mut self, other: &AccumulationBuffer) -> Result<(), Error> {
+        if other.width != self.width || other.height != self.height {
+            return Err(Error::SizeMismatch);
+        }
+        for (a, b) in self.sums.iter_mut().zip(other.sums.iter()) {
+            *a += b;
+        }
+        for (a, b) in self.counts.iter_mut().zip(other.counts.iter()) {
+            *a += b;
+        }
+        Ok(())
+    }
+
+    /// Average of samples of the given pixel, black if the pixel has no sample.
+    pub fn get_mean(&self, x: ImageSize, y: ImageSize) -> PixelF64 {
+        let i = x as usize + y as usize * self.width as usize;
+        if self.counts[i] == 0 {
+            return PixelF64::black();
+        }
+        let mean = self.sums[i] / self.counts[i] as NumColorRatio;
+        PixelF64::new(mean.x, mean.y, mean.z)
+    }
+
+    /// Convert averaged samples to an image of any pixel type.
+    pub fn resolve<T: Pixel>(&self) -> Image<T> {
+        let mut image = Image::new(self.width, self.height);
+        for (x, y, pixel) in image.iter_mut() {
+            *pixel = T::from(&self.get_mean(x, y));
+        }
+        image
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::framebuffer::AccumulationBuffer;
+    use crate::ppm::Image;
+    use crate::types::{Pixel, PixelU8};
+
+    #[test]
+    fn test_accumulate_8bit_samples() {
+        let mut a = AccumulationBuffer::new(2, 1);
+        let mut b = AccumulationBuffer::new(2, 1);
+        let mut image: Image<PixelU8> = Image::new(2, 1);
+        image.set_pixel(0, 0, PixelU8::from_rgb8(200, 255, 1));
+        image.set_pixel(1, 0, PixelU8::from_rgb8(100, 0, 0));
+        for i in 0..1000 {
+            let buffer = if i % 3 == 0 { &mut a } else { &mut b };
+            buffer.add_image(&image).expect("add image");
+        }
+        a.merge(&b).expect("merge");
+        assert_eq!(a.total_samples(), 2000);
+        let resolved: Image<PixelU8> = a.resolve();
+        let p = resolved.get_pixel(0, 0);
+        assert_eq!((p.red8(), p.green8(), p.blue8()), (200, 255, 1));
+        assert_eq!(resolved.get_pixel(1, 0).red8(), 100);
+    }
+
+    #[test]
+    fn test_mean_of_different_samples() {
+        let mut buffer = AccumulationBuffer::new(1, 1);
+        buffer.add_sample(0, 0, &PixelU8::from_rgb8(255, 0, 0));
+        buffer.add_sample(0, 0, &PixelU8::from_rgb8(0, 0, 0));
+        assert_eq!(buffer.get_mean(0, 0).red(), 0.5);
+        assert_eq!(AccumulationBuffer::new(1, 1).get_mean(0, 0).red(), 0.0);
+    }
+}
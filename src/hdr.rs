@@ -0,0 +1,327 @@
+//! Radiance RGBE (.hdr) format, storing a shared 8-bit exponent for the three color channels.
+
+use crate::ppm::Error::{InvalidData, InvalidHeader, TruncatedData};
+use crate::ppm::{create_file, Error, Image, ImageSize};
+use crate::types::{NumColorRatio, Pixel};
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+const SIGNATURE: &[u8] = b"#?RADIANCE";
+
+/// Scanlines with width out of this range can not be run-length encoded.
+const RLE_MIN_WIDTH: usize = 8;
+const RLE_MAX_WIDTH: usize = 0x7fff;
+
+/// Convert a linear color to RGBE representation.
+pub fn to_rgbe(r: NumColorRatio, g: NumColorRatio, b: NumColorRatio) -> [u8; 4] {
+    let (r, g, b) = (r.max(0.0), g.max(0.0), b.max(0.0));
+    let v = r.max(g).max(b);
+    if v < 1e-32 || !v.is_finite() {
+        return [0; 4];
+    }
+    // v = m * 2^e, 0.5 <= m < 1
+    let e = v.log2().floor() as i32 + 1;
+    let scale = 256.0 / 2f64.powi(e);
+    // guard against rounding in log2 pushing the mantissa to 256
+    let (e, scale) = if v * scale >= 256.0 {
+        (e + 1, scale / 2.0)
+    } else {
+        (e, scale)
+    };
+    [
+        (r * scale) as u8,
+        (g * scale) as u8,
+        (b * scale) as u8,
+        (e + 128).clamp(0, 255) as u8,
+    ]
+}
+
+/// Convert a RGBE value back to linear color, using the center of the quantization interval.
+pub fn from_rgbe(rgbe: [u8; 4]) -> (NumColorRatio, NumColorRatio, NumColorRatio) {
+    if rgbe[3] == 0 {
+        return (0.0, 0.0, 0.0);
+    }
+    let f = 2f64.powi(rgbe[3] as i32 - (128 + 8));
+    (
+        (rgbe[0] as f64 + 0.5) * f,
+        (rgbe[1] as f64 + 0.5) * f,
+        (rgbe[2] as f64 + 0.5) * f,
+    )
+}
+
+/// Append one channel of a scanline to `out` with Radiance's adaptive run-length encoding.
+fn encode_rle_channel(data: &[u8], out: &mut Vec<u8>) {
+    const MIN_RUN: usize = 4;
+    let mut i = 0;
+    while i < data.len() {
+        // find the next run that is long enough to be worth encoding
+        let mut run_start = i;
+        let mut run_len = 0;
+        while run_start < data.len() {
+            run_len = 1;
+            while run_len < 127
+                && run_start + run_len < data.len()
+                && data[run_start + run_len] == data[run_start]
+            {
+                run_len += 1;
+            }
+            if run_len >= MIN_RUN {
+                break;
+            }
+            run_start += run_len;
+        }
+        // dump literal bytes before the run
+        while i < run_start {
+            let n = (run_start - i).min(128);
+            out.push(n as u8);
+            out.extend_from_slice(&data[i..i + n]);
+            i += n;
+        }
+        if run_len >= MIN_RUN && run_start < data.len() {
+            out.push(128 + run_len as u8);
+            out.push(data[run_start]);
+            i = run_start + run_len;
+        }
+    }
+}
+
+fn decode_rle_scanline(
+    data: &[u8],
+    pos: &mut usize,
+    width: usize,
+    scanline: &mut [[u8; 4]],
+) -> Result<(), Error> {
+    let mut next = || -> Result<u8, Error> {
+        let c = *data.get(*pos).ok_or(TruncatedData)?;
+        *pos += 1;
+        Ok(c)
+    };
+    for channel in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let n = next()? as usize;
+            if n > 128 {
+                let n = n - 128;
+                if x + n > width {
+                    return Err(InvalidData("run-length overflows scanline"));
+                }
+                let v = next()?;
+                for pix in &mut scanline[x..x + n] {
+                    pix[channel] = v;
+                }
+                x += n;
+            } else {
+                if n == 0 || x + n > width {
+                    return Err(InvalidData("invalid literal count in scanline"));
+                }
+                for pix in &mut scanline[x..x + n] {
+                    pix[channel] = next()?;
+                }
+                x += n;
+            }
+        }
+    }
+    Ok(())
+}
+
+impl<T: Pixel> Image<T> {
+    /// Save the image as a run-length encoded Radiance HDR file.
+    pub fn save_hdr(&self, path: &Path) -> Result<(), Error> {
+        let mut writer = create_file(path)?;
+        self.encode_hdr(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Write the image in Radiance HDR format to `w`.
+    pub fn encode_hdr<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let width = self.get_width() as usize;
+        w.write_all(SIGNATURE)?;
+        write!(
+            w,
+            "\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+            self.get_height(),
+            width
+        )?;
+        let rle = (RLE_MIN_WIDTH..=RLE_MAX_WIDTH).contains(&width);
+        let mut scanline = Vec::with_capacity(width);
+        let mut channel = Vec::with_capacity(width);
+        let mut out = Vec::with_capacity(width * 4 + 4);
+        for y in 0..self.get_height() {
+            scanline.clear();
+            for x in 0..self.get_width() {
+                let pix = self.get_pixel(x, y);
+                scanline.push(to_rgbe(pix.red(), pix.green(), pix.blue()));
+            }
+            out.clear();
+            if rle {
+                out.extend_from_slice(&[2, 2, (width >> 8) as u8, width as u8]);
+                for c in 0..4 {
+                    channel.clear();
+                    channel.extend(scanline.iter().map(|rgbe| rgbe[c]));
+                    encode_rle_channel(&channel, &mut out);
+                }
+            } else {
+                out.extend(scanline.iter().flatten());
+            }
+            w.write_all(&out)?;
+        }
+        Ok(())
+    }
+
+    /// Load a Radiance HDR file in RGBE format.
+    pub fn load_hdr(path: &Path) -> Result<Self, Error> {
+        let data = fs::read(path)?;
+        Self::decode_hdr(&data)
+    }
+
+    /// Decode a Radiance HDR image from its file content.
+    /// Only the standard `-Y height +X width` orientation is supported.
+    pub fn decode_hdr(data: &[u8]) -> Result<Self, Error> {
+        let mut pos = 0;
+        let mut next_line = || -> Result<&[u8], Error> {
+            let start = pos;
+            let len = data[start..]
+                .iter()
+                .position(|&c| c == b'\n')
+                .ok_or(TruncatedData)?;
+            pos = start + len + 1;
+            Ok(&data[start..start + len])
+        };
+        if !next_line()?.starts_with(b"#?") {
+            return Err(InvalidHeader("missing Radiance signature"));
+        }
+        // header lines are terminated by an empty line
+        loop {
+            let line = next_line()?;
+            if line.is_empty() {
+                break;
+            }
+            if let Some(format) = line.strip_prefix(b"FORMAT=") {
+                if format != b"32-bit_rle_rgbe" {
+                    return Err(InvalidHeader("unsupported pixel format"));
+                }
+            }
+        }
+        let resolution = std::str::from_utf8(next_line()?)
+            .map_err(|_| InvalidHeader("resolution line is not ASCII"))?;
+        let (width, height) = match resolution.split_whitespace().collect::<Vec<_>>()[..] {
+            ["-Y", h, "+X", w] => (
+                w.parse::<ImageSize>()
+                    .map_err(|_| InvalidHeader("invalid image width"))?,
+                h.parse::<ImageSize>()
+                    .map_err(|_| InvalidHeader("invalid image height"))?,
+            ),
+            _ => return Err(InvalidHeader("unsupported resolution line")),
+        };
+        if width == 0 || height == 0 {
+            return Err(InvalidHeader("image size must be positive"));
+        }
+        let w = width as usize;
+        // reject impossible sizes before allocating the pixel buffer: a run-length encoded
+        // scanline needs its header and two bytes for every 127 samples of each component
+        let min_line_size = if (RLE_MIN_WIDTH..=RLE_MAX_WIDTH).contains(&w) {
+            4 + 8 * w.div_ceil(127)
+        } else {
+            4 * w
+        };
+        let min_size = min_line_size
+            .checked_mul(height as usize)
+            .ok_or(InvalidHeader("image size is too large"))?;
+        if data.len() - pos < min_size {
+            return Err(TruncatedData);
+        }
+        let mut img = Image::new(width, height);
+        let mut scanline = vec![[0u8; 4]; w];
+        for y in 0..height {
+            let head = data.get(pos..pos + 4).ok_or(TruncatedData)?;
+            let is_rle = (RLE_MIN_WIDTH..=RLE_MAX_WIDTH).contains(&w)
+                && head[0] == 2
+                && head[1] == 2
+                && head[2] & 0x80 == 0;
+            if is_rle {
+                if ((head[2] as usize) << 8 | head[3] as usize) != w {
+                    return Err(InvalidData("scanline width mismatch"));
+                }
+                pos += 4;
+                decode_rle_scanline(data, &mut pos, w, &mut scanline)?;
+            } else {
+                let raw = data.get(pos..pos + 4 * w).ok_or(TruncatedData)?;
+                for (pix, rgbe) in scanline.iter_mut().zip(raw.chunks_exact(4)) {
+                    pix.copy_from_slice(rgbe);
+                }
+                pos += 4 * w;
+            }
+            for (x, rgbe) in scanline.iter().enumerate() {
+                let (r, g, b) = from_rgbe(*rgbe);
+                img.set_pixel(x as ImageSize, y, T::from_rgb_normalized(r, g, b));
+            }
+        }
+        Ok(img)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hdr;
+    use crate::ppm::{Error, Image};
+    use crate::types::{Pixel, PixelF64};
+
+    #[test]
+    fn test_rgbe() {
+        for (r, g, b) in [(1.0, 0.5, 0.25), (1000.0, 0.0, 3.0), (1e-5, 2e-5, 0.0)] {
+            let (r2, g2, b2) = hdr::from_rgbe(hdr::to_rgbe(r, g, b));
+            // one unit of mantissa of the largest channel
+            let tolerance = r.max(g).max(b) / 128.0;
+            assert!((r - r2).abs() <= tolerance);
+            assert!((g - g2).abs() <= tolerance);
+            assert!((b - b2).abs() <= tolerance);
+        }
+        assert_eq!(hdr::to_rgbe(1.0, 0.0, 0.0), [128, 0, 0, 129]);
+        assert_eq!(hdr::to_rgbe(0.0, 0.0, 0.0), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_hdr_roundtrip() {
+        // 5 pixels wide images are stored flat, 40 pixels wide ones are run-length encoded
+        for width in [5, 40] {
+            let mut img: Image<PixelF64> = Image::new(width, 3);
+            for (x, y, pix) in img.iter_mut() {
+                let v = if x < 20 { 4.0 } else { x as f64 * 0.25 };
+                *pix = PixelF64::new(v, y as f64, 0.5);
+            }
+            let mut data = Vec::new();
+            img.encode_hdr(&mut data).expect("encode hdr");
+            let loaded: Image<PixelF64> = Image::decode_hdr(&data).expect("decode hdr");
+            assert_eq!(loaded.get_width(), width);
+            for ((_, _, a), (_, _, b)) in img.iter().zip(loaded.iter()) {
+                let tolerance = a.red().max(a.green()).max(a.blue()) / 128.0;
+                assert!((a.red() - b.red()).abs() <= tolerance);
+                assert!((a.green() - b.green()).abs() <= tolerance);
+                assert!((a.blue() - b.blue()).abs() <= tolerance);
+            }
+        }
+    }
+
+    #[test]
+    fn test_hdr_invalid_size() {
+        let decode = |resolution: &str| {
+            let header = format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n{resolution}\n");
+            let mut data = header.into_bytes();
+            data.extend_from_slice(&[0; 64]);
+            Image::<PixelF64>::decode_hdr(&data).map(|_| ())
+        };
+        assert!(decode("-Y 4 +X 4").is_ok());
+        assert!(matches!(decode("-Y 5 +X 4"), Err(Error::TruncatedData)));
+        assert!(matches!(
+            decode("-Y 100000 +X 100000"),
+            Err(Error::TruncatedData)
+        ));
+        assert!(matches!(
+            decode("-Y 4000000000 +X 4000000000"),
+            Err(Error::InvalidHeader(_))
+        ));
+    }
+}
@@ -0,0 +1,1101 @@
+//! glTF 2.0 scenes, in both JSON (`.gltf`) and binary (`.glb`) files.
+//!
+//! Meshes are transformed into world space while loading, so every primitive
+//! becomes a [`TriangleMesh`] of its own.
+//!
+//! Textures are not supported, materials only use their constant factors.
+
+use crate::import::Error;
+use crate::light::Light;
+use crate::material::{Diffuse, Emissive, Material, Metal};
+use crate::objects::mesh::TriangleMesh;
+use crate::objects::shaded::Shaded;
+use crate::scene::{Camera, Hittable};
+use crate::types::{NumColorRatio, NumPosition, PixelF64, PositionVec};
+use nalgebra::{Matrix3, Matrix4, Point3, Quaternion, UnitQuaternion, Vector3};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::warn;
+
+/// Raw structure of glTF JSON documents, only the parts used by the importer.
+mod json {
+    use serde::de::IgnoredAny;
+    use serde::Deserialize;
+    use std::collections::HashMap;
+
+    #[derive(Deserialize, Default)]
+    #[serde(default, rename_all = "camelCase")]
+    pub struct Document {
+        pub asset: Asset,
+        pub scene: Option<usize>,
+        pub scenes: Vec<Scene>,
+        pub nodes: Vec<Node>,
+        pub meshes: Vec<Mesh>,
+        pub accessors: Vec<Accessor>,
+        pub buffer_views: Vec<BufferView>,
+        pub buffers: Vec<Buffer>,
+        pub materials: Vec<Material>,
+        pub cameras: Vec<Camera>,
+        pub extensions: DocumentExtensions,
+    }
+
+    #[derive(Deserialize, Default)]
+    #[serde(default)]
+    pub struct Asset {
+        pub version: String,
+    }
+
+    #[derive(Deserialize, Default)]
+    #[serde(default)]
+    pub struct Scene {
+        pub nodes: Vec<usize>,
+    }
+
+    #[derive(Deserialize, Default)]
+    #[serde(default)]
+    pub struct Node {
+        pub name: Option<String>,
+        pub children: Vec<usize>,
+        pub mesh: Option<usize>,
+        pub camera: Option<usize>,
+        pub matrix: Option<[f64; 16]>,
+        pub translation: Option<[f64; 3]>,
+        /// quaternion in (x, y, z, w) order
+        pub rotation: Option<[f64; 4]>,
+        pub scale: Option<[f64; 3]>,
+        pub extensions: NodeExtensions,
+    }
+
+    #[derive(Deserialize, Default)]
+    #[serde(default)]
+    pub struct NodeExtensions {
+        #[serde(rename = "KHR_lights_punctual")]
+        pub lights_punctual: Option<NodeLight>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct NodeLight {
+        pub light: usize,
+    }
+
+    #[derive(Deserialize, Default)]
+    #[serde(default)]
+    pub struct DocumentExtensions {
+        #[serde(rename = "KHR_lights_punctual")]
+        pub lights_punctual: Option<Lights>,
+    }
+
+    #[derive(Deserialize, Default)]
+    #[serde(default)]
+    pub struct Lights {
+        pub lights: Vec<Light>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Light {
+        pub name: Option<String>,
+        #[serde(rename = "type")]
+        pub kind: String,
+        pub color: Option<[f64; 3]>,
+        pub intensity: Option<f64>,
+        pub range: Option<f64>,
+        pub spot: Option<Spot>,
+    }
+
+    #[derive(Deserialize, Default)]
+    #[serde(default, rename_all = "camelCase")]
+    pub struct Spot {
+        pub inner_cone_angle: Option<f64>,
+        pub outer_cone_angle: Option<f64>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Mesh {
+        pub name: Option<String>,
+        pub primitives: Vec<Primitive>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Primitive {
+        pub attributes: HashMap<String, usize>,
+        pub indices: Option<usize>,
+        pub material: Option<usize>,
+        pub mode: Option<u32>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Accessor {
+        pub buffer_view: Option<usize>,
+        #[serde(default)]
+        pub byte_offset: usize,
+        pub component_type: u32,
+        #[serde(default)]
+        pub normalized: bool,
+        pub count: usize,
+        #[serde(rename = "type")]
+        pub kind: String,
+        pub sparse: Option<serde_json::Value>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct BufferView {
+        pub buffer: usize,
+        #[serde(default)]
+        pub byte_offset: usize,
+        pub byte_length: usize,
+        pub byte_stride: Option<usize>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Buffer {
+        pub uri: Option<String>,
+        pub byte_length: usize,
+    }
+
+    #[derive(Deserialize, Default)]
+    #[serde(default, rename_all = "camelCase")]
+    pub struct Material {
+        pub name: Option<String>,
+        pub pbr_metallic_roughness: Option<Pbr>,
+        pub emissive_factor: Option<[f64; 3]>,
+        pub emissive_texture: Option<IgnoredAny>,
+        pub normal_texture: Option<IgnoredAny>,
+        pub occlusion_texture: Option<IgnoredAny>,
+        pub double_sided: bool,
+    }
+
+    #[derive(Deserialize, Default)]
+    #[serde(default, rename_all = "camelCase")]
+    pub struct Pbr {
+        pub base_color_factor: Option<[f64; 4]>,
+        pub base_color_texture: Option<IgnoredAny>,
+        pub metallic_factor: Option<f64>,
+        pub roughness_factor: Option<f64>,
+        pub metallic_roughness_texture: Option<IgnoredAny>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Camera {
+        pub name: Option<String>,
+        #[serde(rename = "type")]
+        pub kind: String,
+        pub perspective: Option<Perspective>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub struct Perspective {
+        pub yfov: f64,
+        pub aspect_ratio: Option<f64>,
+    }
+}
+
+/// Metallic-roughness material.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GltfMaterial {
+    pub name: String,
+    /// linear RGBA
+    pub base_color: [NumColorRatio; 4],
+    pub metallic: NumColorRatio,
+    pub roughness: NumColorRatio,
+    pub emissive: [NumColorRatio; 3],
+    pub double_sided: bool,
+}
+
+impl Default for GltfMaterial {
+    fn default() -> Self {
+        GltfMaterial {
+            name: String::new(),
+            base_color: [1.0; 4],
+            metallic: 1.0,
+            roughness: 1.0,
+            emissive: [0.0; 3],
+            double_sided: false,
+        }
+    }
+}
+
+impl GltfMaterial {
+    /// Material of surfaces using this one. Emitting surfaces become lights, and mostly
+    /// metallic ones metal blurred by their roughness. Transparency is not used.
+    pub fn material(&self) -> Arc<dyn Material> {
+        let [r, g, b, _] = self.base_color;
+        let base_color = PixelF64::new(r, g, b);
+        if self.emissive.iter().any(|&e| e > 0.0) {
+            let [r, g, b] = self.emissive;
+            Arc::new(Emissive {
+                radiance: PixelF64::new(r, g, b),
+            })
+        } else if self.metallic >= 0.5 {
+            Arc::new(Metal {
+                albedo: base_color,
+                fuzz: self.roughness.clamp(0.0, 1.0),
+            })
+        } else {
+            Arc::new(Diffuse { albedo: base_color })
+        }
+    }
+}
+
+/// One primitive of a mesh in world space.
+#[derive(Clone, Debug)]
+pub struct GltfMesh {
+    /// name of the mesh, or of the node if the mesh has no name
+    pub name: String,
+    /// index into [`GltfScene::materials`]
+    pub material: Option<usize>,
+    pub mesh: TriangleMesh,
+}
+
+/// Perspective camera placed in world space.
+#[derive(Clone, Debug)]
+pub struct GltfCamera {
+    pub name: String,
+    pub position: PositionVec,
+    /// rotation from camera space to world space, cameras look at -z like [`Camera`]
+    pub orientation: Matrix3<NumPosition>,
+    /// vertical field of view in radians
+    pub yfov: NumPosition,
+    /// width / height, the image size decides if not set
+    pub aspect_ratio: Option<NumPosition>,
+}
+
+impl GltfCamera {
+    /// Create a camera rendering an image of given size, keeping the vertical field of view.
+    pub fn camera(&self, width: u32, height: u32) -> Camera {
+        let pixel_size = 2.0 * (self.yfov / 2.0).tan() / height as NumPosition;
+        let mut camera = Camera::new(width, height, pixel_size * width as NumPosition, 1.0);
+        camera.pos = self.position;
+        camera.orientation = self.orientation;
+        camera
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GltfLightKind {
+    Directional,
+    Point,
+    Spot {
+        inner_cone_angle: NumPosition,
+        outer_cone_angle: NumPosition,
+    },
+}
+
+/// Punctual light (`KHR_lights_punctual`) placed in world space.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GltfLight {
+    pub name: String,
+    pub kind: GltfLightKind,
+    /// linear RGB
+    pub color: [NumColorRatio; 3],
+    /// candela for point and spot lights, lux for directional lights
+    pub intensity: NumColorRatio,
+    pub range: Option<NumPosition>,
+    pub position: PositionVec,
+    /// direction the light points to
+    pub direction: PositionVec,
+}
+
+impl GltfLight {
+    /// The light for rendering. Intensities are taken as radiometric values without
+    /// photometric conversion, and the range is ignored as lights fall off with the
+    /// square of the distance anyway.
+    pub fn light(&self) -> Light {
+        let [r, g, b] = self.color;
+        let mut color = PixelF64::new(r, g, b);
+        color *= self.intensity;
+        match self.kind {
+            GltfLightKind::Directional => Light::Distant {
+                direction: self.direction,
+                radiance: color,
+            },
+            GltfLightKind::Point => Light::Point {
+                position: self.position,
+                intensity: color,
+            },
+            GltfLightKind::Spot {
+                inner_cone_angle,
+                outer_cone_angle,
+            } => Light::Spot {
+                position: self.position,
+                direction: self.direction,
+                cos_outer: outer_cone_angle.cos(),
+                cos_inner: inner_cone_angle.cos(),
+                intensity: color,
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct GltfScene {
+    pub meshes: Vec<GltfMesh>,
+    pub materials: Vec<GltfMaterial>,
+    pub cameras: Vec<GltfCamera>,
+    pub lights: Vec<GltfLight>,
+}
+
+fn invalid(message: impl Into<String>) -> Error {
+    Error::InvalidData(message.into())
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn decode_base64(s: &str) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::with_capacity(s.len() / 4 * 3);
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    for c in s.bytes() {
+        if c == b'=' || c.is_ascii_whitespace() {
+            continue;
+        }
+        let v = BASE64_ALPHABET
+            .iter()
+            .position(|&a| a == c)
+            .ok_or_else(|| invalid("invalid base64 data"))?;
+        acc = (acc << 6) | v as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+        }
+    }
+    Ok(out)
+}
+
+fn decode_percent(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| {
+            std::str::from_utf8(h)
+                .ok()
+                .and_then(|h| u8::from_str_radix(h, 16).ok())
+        });
+        match (bytes[i], hex) {
+            (b'%', Some(v)) => {
+                out.push(v);
+                i += 3;
+            }
+            (b, _) => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Where a URI points to.
+enum Uri {
+    Data(Vec<u8>),
+    File(PathBuf),
+}
+
+fn resolve_uri(uri: &str, dir: &Path) -> Result<Uri, Error> {
+    match uri.strip_prefix("data:") {
+        Some(rest) => {
+            let (_, data) = rest
+                .split_once(";base64,")
+                .ok_or_else(|| Error::Unsupported("data URI without base64 encoding".into()))?;
+            Ok(Uri::Data(decode_base64(data)?))
+        }
+        None => Ok(Uri::File(dir.join(decode_percent(uri)))),
+    }
+}
+
+/// Split a binary glTF file into the JSON document and the binary chunk.
+fn split_glb(data: &[u8]) -> Result<(&[u8], Option<&[u8]>), Error> {
+    let u32_at = |pos: usize| {
+        data.get(pos..pos + 4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize)
+            .ok_or_else(|| invalid("truncated GLB file"))
+    };
+    if u32_at(4)? != 2 {
+        return Err(Error::Unsupported(format!("GLB version {}", u32_at(4)?)));
+    }
+    let length = u32_at(8)?.min(data.len());
+    let mut pos = 12;
+    let mut json = None;
+    let mut bin = None;
+    while pos + 8 <= length {
+        let chunk_length = u32_at(pos)?;
+        let chunk_type = &data[pos + 4..pos + 8];
+        let chunk = data
+            .get(pos + 8..pos + 8 + chunk_length)
+            .ok_or_else(|| invalid("truncated GLB chunk"))?;
+        match chunk_type {
+            b"JSON" => json = json.or(Some(chunk)),
+            b"BIN\0" => bin = bin.or(Some(chunk)),
+            // unknown chunks must be ignored
+            _ => {}
+        }
+        pos += 8 + chunk_length;
+    }
+    Ok((
+        json.ok_or_else(|| invalid("GLB file without JSON chunk"))?,
+        bin,
+    ))
+}
+
+fn component_count(kind: &str) -> Result<usize, Error> {
+    Ok(match kind {
+        "SCALAR" => 1,
+        "VEC2" => 2,
+        "VEC3" => 3,
+        "VEC4" => 4,
+        "MAT2" => 4,
+        "MAT3" => 9,
+        "MAT4" => 16,
+        _ => return Err(invalid(format!("unknown accessor type `{kind}`"))),
+    })
+}
+
+/// Read a little-endian component, converting normalized integers to range [0, 1] or [-1, 1].
+fn read_component(bytes: &[u8], component_type: u32, normalized: bool) -> f64 {
+    let (v, max) = match component_type {
+        5120 => (bytes[0] as i8 as f64, i8::MAX as f64),
+        5121 => (bytes[0] as f64, u8::MAX as f64),
+        5122 => (
+            i16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            i16::MAX as f64,
+        ),
+        5123 => (
+            u16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            u16::MAX as f64,
+        ),
+        5125 => (
+            u32::from_le_bytes(bytes[..4].try_into().unwrap()) as f64,
+            u32::MAX as f64,
+        ),
+        _ => (
+            f32::from_le_bytes(bytes[..4].try_into().unwrap()) as f64,
+            1.0,
+        ),
+    };
+    if normalized {
+        (v / max).max(-1.0)
+    } else {
+        v
+    }
+}
+
+struct Loader<'a> {
+    doc: &'a json::Document,
+    buffers: Vec<Vec<u8>>,
+}
+
+impl Loader<'_> {
+    fn buffer_view(&self, index: usize) -> Result<(&[u8], Option<usize>), Error> {
+        let view = self
+            .doc
+            .buffer_views
+            .get(index)
+            .ok_or_else(|| invalid(format!("buffer view {index} does not exist")))?;
+        let buffer = self
+            .buffers
+            .get(view.buffer)
+            .ok_or_else(|| invalid(format!("buffer {} does not exist", view.buffer)))?;
+        let data = view
+            .byte_offset
+            .checked_add(view.byte_length)
+            .and_then(|end| buffer.get(view.byte_offset..end))
+            .ok_or_else(|| invalid(format!("buffer view {index} is out of its buffer")))?;
+        Ok((data, view.byte_stride))
+    }
+
+    fn accessor_info(&self, index: usize) -> Result<&json::Accessor, Error> {
+        self.doc
+            .accessors
+            .get(index)
+            .ok_or_else(|| invalid(format!("accessor {index} does not exist")))
+    }
+
+    /// Read all elements of an accessor, returning components of each element.
+    /// Accessors without buffer view are filled with zeros and may have at most `limit` elements.
+    fn accessor(&self, index: usize, limit: usize) -> Result<Vec<Vec<f64>>, Error> {
+        let accessor = self.accessor_info(index)?;
+        if accessor.sparse.is_some() {
+            return Err(Error::Unsupported("sparse accessors".into()));
+        }
+        let n = component_count(&accessor.kind)?;
+        let size = match accessor.component_type {
+            5120 | 5121 => 1,
+            5122 | 5123 => 2,
+            5125 | 5126 => 4,
+            t => return Err(invalid(format!("unknown component type {t}"))),
+        };
+        let Some(view) = accessor.buffer_view else {
+            if accessor.count > limit {
+                return Err(invalid(format!(
+                    "accessor {index} without buffer view has too many elements"
+                )));
+            }
+            return Ok(vec![vec![0.0; n]; accessor.count]);
+        };
+        let (data, stride) = self.buffer_view(view)?;
+        let element = n * size;
+        let stride = stride.unwrap_or(element);
+        if stride < element {
+            return Err(invalid(format!(
+                "byte stride of buffer view {view} is smaller than the elements of accessor {index}"
+            )));
+        }
+        // the last element must end within the buffer view, before anything is allocated
+        let end = match accessor.count {
+            0 => Some(0),
+            count => (count - 1)
+                .checked_mul(stride)
+                .and_then(|start| start.checked_add(accessor.byte_offset))
+                .and_then(|start| start.checked_add(element)),
+        };
+        if end.is_none_or(|end| end > data.len()) {
+            return Err(invalid(format!(
+                "accessor {index} is out of its buffer view"
+            )));
+        }
+        Ok((0..accessor.count)
+            .map(|i| {
+                let start = accessor.byte_offset + i * stride;
+                data[start..start + element]
+                    .chunks_exact(size)
+                    .map(|c| read_component(c, accessor.component_type, accessor.normalized))
+                    .collect()
+            })
+            .collect())
+    }
+
+    /// Read a vertex attribute of `kind` with float components, or normalized integer
+    /// components if `normalized` is allowed.
+    fn attribute(
+        &self,
+        primitive: &json::Primitive,
+        name: &str,
+        kind: &str,
+        normalized: bool,
+        limit: usize,
+    ) -> Result<Vec<Vec<f64>>, Error> {
+        let Some(&index) = primitive.attributes.get(name) else {
+            return Ok(Vec::new());
+        };
+        let accessor = self.accessor_info(index)?;
+        let float = accessor.component_type == 5126
+            || (normalized
+                && accessor.normalized
+                && matches!(accessor.component_type, 5121 | 5123));
+        if accessor.kind != kind || !float {
+            return Err(invalid(format!(
+                "{name} accessor {index} is not a {kind} of floats"
+            )));
+        }
+        self.accessor(index, limit)
+    }
+
+    fn material(&self, material: &json::Material) -> GltfMaterial {
+        let default = GltfMaterial::default();
+        let pbr = material.pbr_metallic_roughness.as_ref();
+        let name = material.name.clone().unwrap_or_default();
+        let textured = pbr.is_some_and(|p| {
+            p.base_color_texture.is_some() || p.metallic_roughness_texture.is_some()
+        }) || material.emissive_texture.is_some()
+            || material.normal_texture.is_some()
+            || material.occlusion_texture.is_some();
+        if textured {
+            warn!("Textures of material `{name}` are ignored, textures are not supported");
+        }
+        GltfMaterial {
+            name,
+            base_color: pbr
+                .and_then(|p| p.base_color_factor)
+                .unwrap_or(default.base_color),
+            metallic: pbr
+                .and_then(|p| p.metallic_factor)
+                .unwrap_or(default.metallic),
+            roughness: pbr
+                .and_then(|p| p.roughness_factor)
+                .unwrap_or(default.roughness),
+            emissive: material.emissive_factor.unwrap_or(default.emissive),
+            double_sided: material.double_sided,
+        }
+    }
+
+    fn primitive(
+        &self,
+        primitive: &json::Primitive,
+        transform: &Matrix4<f64>,
+    ) -> Result<Option<TriangleMesh>, Error> {
+        let mode = primitive.mode.unwrap_or(4);
+        if mode < 4 {
+            warn!("Points and lines in glTF files are ignored");
+            return Ok(None);
+        }
+        let position = primitive
+            .attributes
+            .get("POSITION")
+            .ok_or_else(|| invalid("primitive without POSITION attribute"))?;
+        if self.accessor_info(*position)?.buffer_view.is_none() {
+            warn!("Primitives without position data are ignored");
+            return Ok(None);
+        }
+        let positions: Vec<PositionVec> = self
+            .attribute(primitive, "POSITION", "VEC3", false, 0)?
+            .iter()
+            .map(|p| {
+                transform
+                    .transform_point(&Point3::new(p[0], p[1], p[2]))
+                    .coords
+            })
+            .collect();
+        let linear = transform.fixed_view::<3, 3>(0, 0).into_owned();
+        let normal_matrix = linear
+            .try_inverse()
+            .ok_or_else(|| invalid("singular node transform"))?
+            .transpose();
+        let normals: Vec<PositionVec> = self
+            .attribute(primitive, "NORMAL", "VEC3", false, positions.len())?
+            .iter()
+            .map(|n| (normal_matrix * Vector3::new(n[0], n[1], n[2])).normalize())
+            .collect();
+        let uvs: Vec<[NumPosition; 2]> = self
+            .attribute(primitive, "TEXCOORD_0", "VEC2", true, positions.len())?
+            .iter()
+            .map(|t| [t[0], t[1]])
+            .collect();
+        for (len, name) in [(normals.len(), "NORMAL"), (uvs.len(), "TEXCOORD_0")] {
+            if len != 0 && len != positions.len() {
+                return Err(invalid(format!("{name} does not match POSITION")));
+            }
+        }
+        let vertex_indices: Vec<u32> = match primitive.indices {
+            None => (0..positions.len() as u32).collect(),
+            Some(i) => self
+                .accessor(i, positions.len())?
+                .iter()
+                .map(|v| v[0] as u32)
+                .collect(),
+        };
+        if let Some(&i) = vertex_indices
+            .iter()
+            .find(|&&i| i as usize >= positions.len())
+        {
+            return Err(invalid(format!("vertex index {i} out of range")));
+        }
+        let v = &vertex_indices;
+        let mut indices: Vec<[u32; 3]> = match mode {
+            4 => v.chunks_exact(3).map(|t| [t[0], t[1], t[2]]).collect(),
+            // triangle strip
+            5 => (0..v.len().saturating_sub(2))
+                .map(|i| {
+                    if i % 2 == 0 {
+                        [v[i], v[i + 1], v[i + 2]]
+                    } else {
+                        [v[i + 1], v[i], v[i + 2]]
+                    }
+                })
+                .collect(),
+            // triangle fan
+            6 => (1..v.len().saturating_sub(1))
+                .map(|i| [v[0], v[i], v[i + 1]])
+                .collect(),
+            _ => return Err(invalid(format!("unknown primitive mode {mode}"))),
+        };
+        if linear.determinant() < 0.0 {
+            // mirroring turns counter-clockwise triangles into clockwise ones
+            for t in &mut indices {
+                t.swap(1, 2);
+            }
+        }
+        let mut mesh = TriangleMesh::new(positions, indices);
+        *mesh.normals_mut() = normals;
+        *mesh.uvs_mut() = uvs;
+        Ok(Some(mesh))
+    }
+
+    fn node(
+        &self,
+        scene: &mut GltfScene,
+        index: usize,
+        parent: &Matrix4<f64>,
+        depth: usize,
+    ) -> Result<(), Error> {
+        if depth > self.doc.nodes.len() {
+            return Err(invalid("node hierarchy contains a cycle"));
+        }
+        let node = self
+            .doc
+            .nodes
+            .get(index)
+            .ok_or_else(|| invalid(format!("node {index} does not exist")))?;
+        let local = match node.matrix {
+            Some(m) => Matrix4::from_column_slice(&m),
+            None => {
+                let t = node.translation.unwrap_or([0.0; 3]);
+                let [x, y, z, w] = node.rotation.unwrap_or([0.0, 0.0, 0.0, 1.0]);
+                let s = node.scale.unwrap_or([1.0; 3]);
+                Matrix4::new_translation(&Vector3::from(t))
+                    * UnitQuaternion::from_quaternion(Quaternion::new(w, x, y, z)).to_homogeneous()
+                    * Matrix4::new_nonuniform_scaling(&Vector3::from(s))
+            }
+        };
+        let world = parent * local;
+        let node_name = node.name.clone().unwrap_or_default();
+        let position = world.transform_point(&Point3::origin()).coords;
+        // rotation without scaling
+        let orientation =
+            Matrix3::from_columns(&[0, 1, 2].map(|i| world.fixed_view::<3, 1>(0, i).normalize()));
+
+        if let Some(mesh_index) = node.mesh {
+            let mesh = self
+                .doc
+                .meshes
+                .get(mesh_index)
+                .ok_or_else(|| invalid(format!("mesh {mesh_index} does not exist")))?;
+            for primitive in &mesh.primitives {
+                if let Some(triangles) = self.primitive(primitive, &world)? {
+                    scene.meshes.push(GltfMesh {
+                        name: mesh.name.clone().unwrap_or_else(|| node_name.clone()),
+                        material: primitive.material,
+                        mesh: triangles,
+                    });
+                }
+            }
+        }
+        if let Some(camera_index) = node.camera {
+            let camera = self
+                .doc
+                .cameras
+                .get(camera_index)
+                .ok_or_else(|| invalid(format!("camera {camera_index} does not exist")))?;
+            match (&camera.perspective, camera.kind.as_str()) {
+                (Some(p), "perspective") => scene.cameras.push(GltfCamera {
+                    name: camera.name.clone().unwrap_or_else(|| node_name.clone()),
+                    position,
+                    orientation,
+                    yfov: p.yfov,
+                    aspect_ratio: p.aspect_ratio,
+                }),
+                _ => warn!(
+                    "Camera {camera_index} is ignored, only perspective cameras are supported"
+                ),
+            }
+        }
+        if let Some(light) = &node.extensions.lights_punctual {
+            let lights = self.doc.extensions.lights_punctual.as_ref();
+            let l = lights
+                .and_then(|l| l.lights.get(light.light))
+                .ok_or_else(|| invalid(format!("light {} does not exist", light.light)))?;
+            let spot = l.spot.as_ref();
+            let kind = match l.kind.as_str() {
+                "directional" => GltfLightKind::Directional,
+                "point" => GltfLightKind::Point,
+                "spot" => GltfLightKind::Spot {
+                    inner_cone_angle: spot.and_then(|s| s.inner_cone_angle).unwrap_or(0.0),
+                    outer_cone_angle: spot
+                        .and_then(|s| s.outer_cone_angle)
+                        .unwrap_or(std::f64::consts::FRAC_PI_4),
+                },
+                kind => return Err(invalid(format!("unknown light type `{kind}`"))),
+            };
+            scene.lights.push(GltfLight {
+                name: l.name.clone().unwrap_or_else(|| node_name.clone()),
+                kind,
+                color: l.color.unwrap_or([1.0; 3]),
+                intensity: l.intensity.unwrap_or(1.0),
+                range: l.range,
+                position,
+                direction: -orientation.column(2).into_owned(),
+            });
+        }
+        for &child in &node.children {
+            self.node(scene, child, &world, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl GltfScene {
+    /// Load a `.gltf` or `.glb` file.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        Self::parse(&fs::read(path)?, path.parent().unwrap_or(Path::new("")))
+    }
+
+    /// Parse the content of a `.gltf` or `.glb` file.
+    /// External buffers are resolved relative to `dir`.
+    pub fn parse(data: &[u8], dir: &Path) -> Result<Self, Error> {
+        let (json, bin) = if data.starts_with(b"glTF") {
+            split_glb(data)?
+        } else {
+            (data, None)
+        };
+        let doc: json::Document = serde_json::from_slice(json).map_err(|e| Error::SyntaxError {
+            line: e.line(),
+            message: e.to_string(),
+        })?;
+        if !doc.asset.version.starts_with("2.") {
+            return Err(Error::Unsupported(format!(
+                "glTF version `{}`",
+                doc.asset.version
+            )));
+        }
+        let buffers = doc
+            .buffers
+            .iter()
+            .enumerate()
+            .map(|(i, buffer)| {
+                let data = match (&buffer.uri, bin) {
+                    (Some(uri), _) => match resolve_uri(uri, dir)? {
+                        Uri::Data(data) => data,
+                        Uri::File(path) => fs::read(path)?,
+                    },
+                    // the first buffer without URI refers to the binary chunk of GLB files
+                    (None, Some(bin)) if i == 0 => bin.to_vec(),
+                    (None, _) => return Err(invalid(format!("buffer {i} has no data"))),
+                };
+                if data.len() < buffer.byte_length {
+                    return Err(invalid(format!("buffer {i} is truncated")));
+                }
+                Ok(data)
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let loader = Loader { doc: &doc, buffers };
+        let mut scene = GltfScene {
+            materials: doc.materials.iter().map(|m| loader.material(m)).collect(),
+            ..GltfScene::default()
+        };
+        let roots: Vec<usize> =
+            match doc
+                .scene
+                .or(if doc.scenes.is_empty() { None } else { Some(0) })
+            {
+                Some(i) => doc
+                    .scenes
+                    .get(i)
+                    .ok_or_else(|| invalid(format!("scene {i} does not exist")))?
+                    .nodes
+                    .clone(),
+                // without scenes, render every node that is not a child of another node
+                None => (0..doc.nodes.len())
+                    .filter(|i| !doc.nodes.iter().any(|n| n.children.contains(i)))
+                    .collect(),
+            };
+        for root in roots {
+            loader.node(&mut scene, root, &Matrix4::identity(), 0)?;
+        }
+        Ok(scene)
+    }
+
+    /// The lights for rendering, see [`GltfLight::light`].
+    pub fn lights(&self) -> Vec<Light> {
+        self.lights.iter().map(GltfLight::light).collect()
+    }
+
+    /// Take the meshes with their materials. Meshes without material are made of
+    /// the default glTF material.
+    pub fn into_meshes(self) -> Vec<(TriangleMesh, Arc<dyn Material>)> {
+        let default = GltfMaterial::default();
+        let materials = self.materials;
+        self.meshes
+            .into_iter()
+            .map(|mesh| {
+                let material = mesh
+                    .material
+                    .and_then(|i| materials.get(i))
+                    .unwrap_or(&default);
+                (mesh.mesh, material.material())
+            })
+            .collect()
+    }
+
+    /// Create an object for each mesh, see [`GltfScene::into_meshes`].
+    pub fn into_objects(self) -> Vec<Box<dyn Hittable>> {
+        self.into_meshes()
+            .into_iter()
+            .map(|(object, material)| Box::new(Shaded { object, material }) as Box<dyn Hittable>)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::import::gltf::{decode_base64, decode_percent, GltfLightKind, GltfScene};
+    use crate::import::Error;
+    use crate::light::Light;
+    use crate::ray::Ray;
+    use crate::types::{PixelF64, PositionVec};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::path::Path;
+    use tracing_test::traced_test;
+
+    /// A triangle scaled by 2 and moved to z = -2 by nested nodes,
+    /// a camera at z = 1 and a light pointing down.
+    const GLTF: &str = r#"{
+        "asset": {"version": "2.0"},
+        "scene": 0,
+        "scenes": [{"nodes": [0, 2, 3]}],
+        "nodes": [
+            {"name": "parent", "translation": [0, 0, -2], "children": [1]},
+            {"name": "tri", "mesh": 0, "scale": [2, 2, 2]},
+            {"name": "cam", "camera": 0, "translation": [0, 0, 1]},
+            {"name": "sun", "rotation": [-0.7071068, 0, 0, 0.7071068],
+             "extensions": {"KHR_lights_punctual": {"light": 0}}}
+        ],
+        "meshes": [{"primitives": [{"attributes": {"POSITION": 0}, "indices": 1, "material": 0}]}],
+        "materials": [{"name": "green", "pbrMetallicRoughness": {"baseColorFactor": [0.1, 0.8, 0.2, 1.0], "metallicFactor": 0.0}}],
+        "cameras": [{"type": "perspective", "perspective": {"yfov": 0.8, "znear": 0.01}}],
+        "extensions": {"KHR_lights_punctual": {"lights": [{"type": "directional", "intensity": 3}]}},
+        "accessors": [
+            {"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3"},
+            {"bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR"}
+        ],
+        "bufferViews": [{"buffer": 0, "byteLength": 36}, {"buffer": 0, "byteOffset": 36, "byteLength": 6}],
+        "buffers": [{"byteLength": 44, "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAAAAABAAIAAAA="}]
+    }"#;
+
+    fn check_scene(scene: GltfScene) {
+        assert_eq!(scene.meshes.len(), 1);
+        assert_eq!(
+            scene.meshes[0].mesh.positions(),
+            vec![
+                PositionVec::new(0.0, 0.0, -2.0),
+                PositionVec::new(2.0, 0.0, -2.0),
+                PositionVec::new(0.0, 2.0, -2.0),
+            ]
+        );
+        let material = &scene.materials[0];
+        assert_eq!((material.metallic, material.roughness), (0.0, 1.0));
+
+        let camera = scene.cameras[0].camera(20, 10);
+        assert_eq!(camera.pos, PositionVec::new(0.0, 0.0, 1.0));
+        assert!((camera.pixel_height - 2.0 * 0.4f64.tan() / 10.0).abs() < 1e-12);
+
+        let light = &scene.lights[0];
+        assert_eq!(light.kind, GltfLightKind::Directional);
+        assert_eq!(light.intensity, 3.0);
+        assert!((light.direction - PositionVec::new(0.0, -1.0, 0.0)).norm() < 1e-6);
+        match scene.lights()[..] {
+            [Light::Distant {
+                direction,
+                radiance,
+            }] => {
+                assert_eq!(direction, light.direction);
+                assert_eq!(radiance, PixelF64::new(3.0, 3.0, 3.0));
+            }
+            _ => panic!("one distant light expected"),
+        }
+
+        let objects = scene.into_objects();
+        let ray = Ray {
+            origin: PositionVec::new(0.5, 0.5, 0.0),
+            direction: PositionVec::new(0.0, 0.0, -1.0),
+        };
+        let hit = objects[0].try_hit(&ray, 0.0, f64::INFINITY).expect("hit");
+        assert!((hit.t - 2.0).abs() < 1e-12);
+        // not metallic, so diffuse
+        let material = hit.material.as_ref().expect("material");
+        let mut rng = StdRng::seed_from_u64(1);
+        let scatter = material.scatter(&ray, &hit, &mut rng).expect("scattered");
+        assert_eq!(scatter.attenuation, PixelF64::new(0.1, 0.8, 0.2));
+        assert!(scatter.ray.direction.z > 0.0);
+    }
+
+    #[test]
+    fn test_gltf_with_data_uri() {
+        check_scene(GltfScene::parse(GLTF.as_bytes(), Path::new("")).expect("parse"));
+    }
+
+    #[test]
+    fn test_glb() {
+        // move the buffer into the binary chunk
+        let mut doc: serde_json::Value = serde_json::from_str(GLTF).unwrap();
+        let uri = doc["buffers"][0]["uri"].as_str().unwrap().to_string();
+        let bin = decode_base64(uri.split_once(',').unwrap().1).unwrap();
+        doc["buffers"][0].as_object_mut().unwrap().remove("uri");
+        let mut json = serde_json::to_vec(&doc).unwrap();
+        while !json.len().is_multiple_of(4) {
+            json.push(b' ');
+        }
+        let mut glb = Vec::new();
+        glb.extend(b"glTF");
+        glb.extend(2u32.to_le_bytes());
+        glb.extend(((12 + 8 + json.len() + 8 + bin.len()) as u32).to_le_bytes());
+        glb.extend((json.len() as u32).to_le_bytes());
+        glb.extend(b"JSON");
+        glb.extend(&json);
+        glb.extend((bin.len() as u32).to_le_bytes());
+        glb.extend(b"BIN\0");
+        glb.extend(&bin);
+        check_scene(GltfScene::parse(&glb, Path::new("")).expect("parse"));
+    }
+
+    #[traced_test]
+    #[test]
+    fn test_textures_are_ignored() {
+        let mut doc: serde_json::Value = serde_json::from_str(GLTF).unwrap();
+        doc["materials"][0]["pbrMetallicRoughness"]["baseColorTexture"] =
+            serde_json::json!({"index": 0});
+        let json = serde_json::to_vec(&doc).unwrap();
+        let scene = GltfScene::parse(&json, Path::new("")).expect("parse");
+        assert!(logs_contain("Textures of material `green` are ignored"));
+        check_scene(scene);
+    }
+
+    #[test]
+    fn test_decode_uri() {
+        assert_eq!(decode_base64("TWFu").unwrap(), b"Man");
+        assert_eq!(decode_base64("TWE=").unwrap(), b"Ma");
+        assert!(decode_base64("T!==").is_err());
+        assert_eq!(decode_percent("my%20model.bin"), "my model.bin");
+        assert!(GltfScene::parse(b"{\"asset\": {\"version\": \"1.0\"}}", Path::new("")).is_err());
+    }
+
+    /// Parse [`GLTF`] after `edit`, expecting invalid data.
+    fn assert_invalid(edit: impl FnOnce(&mut serde_json::Value)) {
+        let mut doc: serde_json::Value = serde_json::from_str(GLTF).unwrap();
+        edit(&mut doc);
+        let json = serde_json::to_vec(&doc).unwrap();
+        assert!(matches!(
+            GltfScene::parse(&json, Path::new("")),
+            Err(Error::InvalidData(_))
+        ));
+    }
+
+    /// Add `accessor` as attribute `name` of the triangle.
+    fn add_attribute(doc: &mut serde_json::Value, name: &str, accessor: serde_json::Value) {
+        let accessors = doc["accessors"].as_array_mut().unwrap();
+        accessors.push(accessor);
+        doc["meshes"][0]["primitives"][0]["attributes"][name] = (accessors.len() - 1).into();
+    }
+
+    #[test]
+    fn test_invalid_accessors() {
+        // positions must be 3D floats
+        assert_invalid(|doc| doc["accessors"][0]["type"] = "SCALAR".into());
+        assert_invalid(|doc| doc["accessors"][0]["componentType"] = 5123.into());
+        // normals must match the positions
+        assert_invalid(|doc| {
+            let normals = r#"{"bufferView": 0, "componentType": 5126, "count": 1, "type": "VEC3"}"#;
+            add_attribute(doc, "NORMAL", serde_json::from_str(normals).unwrap());
+        });
+        // offsets must not overflow
+        assert_invalid(|doc| doc["bufferViews"][0]["byteOffset"] = u64::MAX.into());
+        assert_invalid(|doc| doc["accessors"][0]["byteOffset"] = u64::MAX.into());
+        assert_invalid(|doc| doc["accessors"][0]["count"] = u64::MAX.into());
+        // counts must be backed by data
+        assert_invalid(|doc| {
+            let uvs = r#"{"componentType": 5126, "count": 100000000000, "type": "VEC2"}"#;
+            add_attribute(doc, "TEXCOORD_0", serde_json::from_str(uvs).unwrap());
+        });
+        assert_invalid(|doc| {
+            let indices = doc["accessors"][1].as_object_mut().unwrap();
+            indices.remove("bufferView");
+            indices.insert("count".into(), 100000000000u64.into());
+        });
+    }
+}
@@ -0,0 +1,120 @@
+//! Loaders of geometry created by other tools.
+
+#[cfg(feature = "gltf")]
+pub mod gltf;
+pub mod obj;
+pub mod pbrt;
+pub mod ply;
+pub mod stl;
+
+use crate::types::{NumPosition, PositionVec};
+use std::fmt::{Display, Formatter};
+use std::{fmt, io};
+
+#[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
+pub enum Error {
+    IOError(io::Error),
+    /// malformed text at the given line, counting from 1
+    SyntaxError {
+        line: usize,
+        message: String,
+    },
+    /// malformed or inconsistent data
+    InvalidData(String),
+    /// the file uses a feature this loader does not support
+    Unsupported(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::IOError(e) => write!(f, "I/O error: {e}"),
+            Error::SyntaxError { line, message } => write!(f, "line {line}: {message}"),
+            Error::InvalidData(message) => write!(f, "invalid data: {message}"),
+            Error::Unsupported(message) => write!(f, "unsupported: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(value: io::Error) -> Self {
+        Error::IOError(value)
+    }
+}
+
+/// Split a polygon into triangles by ear clipping in the plane the polygon faces most,
+/// so concave polygons are triangulated correctly. Returns indices into `points`.
+pub(crate) fn triangulate(points: &[PositionVec]) -> Vec<[usize; 3]> {
+    let n = points.len();
+    let fan = |indices: &[usize]| {
+        (1..indices.len() - 1)
+            .map(|i| [indices[0], indices[i], indices[i + 1]])
+            .collect::<Vec<_>>()
+    };
+    if n < 3 {
+        return Vec::new();
+    }
+    let all: Vec<usize> = (0..n).collect();
+    if n == 3 {
+        return fan(&all);
+    }
+    // Newell's method
+    let mut normal = PositionVec::zeros();
+    for i in 0..n {
+        let (a, b) = (points[i], points[(i + 1) % n]);
+        normal += PositionVec::new(
+            (a.y - b.y) * (a.z + b.z),
+            (a.z - b.z) * (a.x + b.x),
+            (a.x - b.x) * (a.y + b.y),
+        );
+    }
+    let axis = normal.iamax();
+    if normal[axis] == 0.0 {
+        return fan(&all);
+    }
+    let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+    let p: Vec<[NumPosition; 2]> = points.iter().map(|p| [p[u], p[v]]).collect();
+    // positive if the projected polygon is counter-clockwise
+    let orientation = normal[axis].signum();
+    let cross = |a: usize, b: usize, c: usize| {
+        ((p[b][0] - p[a][0]) * (p[c][1] - p[a][1]) - (p[b][1] - p[a][1]) * (p[c][0] - p[a][0]))
+            * orientation
+    };
+    let mut remaining = all;
+    let mut triangles = Vec::with_capacity(n - 2);
+    while remaining.len() > 3 {
+        let m = remaining.len();
+        let ear = (0..m).find(|&i| {
+            let (a, b, c) = (
+                remaining[(i + m - 1) % m],
+                remaining[i],
+                remaining[(i + 1) % m],
+            );
+            cross(a, b, c) > 0.0
+                && remaining.iter().all(|&q| {
+                    q == a
+                        || q == b
+                        || q == c
+                        || cross(a, b, q) < 0.0
+                        || cross(b, c, q) < 0.0
+                        || cross(c, a, q) < 0.0
+                })
+        });
+        let Some(i) = ear else {
+            // degenerated polygon
+            triangles.extend(fan(&remaining));
+            return triangles;
+        };
+        triangles.push([
+            remaining[(i + m - 1) % m],
+            remaining[i],
+            remaining[(i + 1) % m],
+        ]);
+        remaining.remove(i);
+    }
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+    triangles
+}
@@ -0,0 +1,550 @@
+//! Wavefront OBJ meshes and MTL material libraries.
+
+use crate::import::{triangulate, Error};
+use crate::material::{Diffuse, Emissive, Glass, Material, Metal, NormalColor};
+use crate::objects::mesh::TriangleMesh;
+use crate::objects::shaded::Shaded;
+use crate::scene::Hittable;
+use crate::types::{NumColorRatio, NumPosition, PixelF64, PositionVec};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::warn;
+
+type Color = [NumColorRatio; 3];
+
+/// Material properties read from an MTL file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MtlMaterial {
+    pub name: String,
+    /// diffuse color (`Kd`)
+    pub diffuse: Color,
+    /// specular color (`Ks`)
+    pub specular: Color,
+    /// specular exponent (`Ns`)
+    pub shininess: NumColorRatio,
+    /// emitted radiance (`Ke`)
+    pub emission: Color,
+    /// index of refraction (`Ni`)
+    pub ior: NumColorRatio,
+    /// `d`, or `1 - Tr`
+    pub opacity: NumColorRatio,
+    /// illumination model (`illum`)
+    pub illum: u32,
+    /// diffuse color texture (`map_Kd`)
+    pub diffuse_map: Option<PathBuf>,
+    /// specular color texture (`map_Ks`)
+    pub specular_map: Option<PathBuf>,
+    /// emission texture (`map_Ke`)
+    pub emission_map: Option<PathBuf>,
+    /// bump map (`bump` or `map_Bump`)
+    pub bump_map: Option<PathBuf>,
+}
+
+impl MtlMaterial {
+    pub fn new(name: String) -> Self {
+        MtlMaterial {
+            name,
+            diffuse: [0.8; 3],
+            specular: [0.0; 3],
+            shininess: 0.0,
+            emission: [0.0; 3],
+            ior: 1.0,
+            opacity: 1.0,
+            illum: 2,
+            diffuse_map: None,
+            specular_map: None,
+            emission_map: None,
+            bump_map: None,
+        }
+    }
+
+    /// Whether any texture map is set.
+    pub fn has_maps(&self) -> bool {
+        [
+            &self.diffuse_map,
+            &self.specular_map,
+            &self.emission_map,
+            &self.bump_map,
+        ]
+        .iter()
+        .any(|map| map.is_some())
+    }
+
+    /// Roughness of the specular highlight, from 1 for `Ns 0` to nearly 0 for `Ns 1000`,
+    /// matching the width of a Blinn-Phong lobe.
+    pub fn roughness(&self) -> NumColorRatio {
+        (2.0 / (self.shininess.max(0.0) + 2.0)).sqrt()
+    }
+
+    /// Material of surfaces using this one. Emitting surfaces become lights, transparent
+    /// ones and illumination models with refraction glass, and those with reflection
+    /// metal blurred by their roughness. Textures are not used.
+    pub fn material(&self) -> Arc<dyn Material> {
+        let color = |[r, g, b]: Color| PixelF64::new(r, g, b);
+        if self.emission.iter().any(|&e| e > 0.0) {
+            return Arc::new(Emissive {
+                radiance: color(self.emission),
+            });
+        }
+        if self.opacity < 1.0 {
+            return Arc::new(Glass { ior: self.ior });
+        }
+        match self.illum {
+            4 | 6 | 7 => Arc::new(Glass { ior: self.ior }),
+            3 | 5 => Arc::new(Metal {
+                albedo: color(self.specular),
+                fuzz: self.roughness(),
+            }),
+            _ => Arc::new(Diffuse {
+                albedo: color(self.diffuse),
+            }),
+        }
+    }
+}
+
+/// Triangles of one group using one material.
+#[derive(Clone, Debug)]
+pub struct ObjMesh {
+    /// name of the group (`g`) or object (`o`), empty if neither is declared
+    pub name: String,
+    /// name of the material (`usemtl`)
+    pub material: Option<String>,
+    pub mesh: TriangleMesh,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ObjModel {
+    pub meshes: Vec<ObjMesh>,
+    /// material libraries referred by `mtllib`
+    pub material_libraries: Vec<String>,
+    pub materials: BTreeMap<String, MtlMaterial>,
+}
+
+fn syntax_error(line: usize, message: impl Into<String>) -> Error {
+    Error::SyntaxError {
+        line,
+        message: message.into(),
+    }
+}
+
+fn parse_floats<const N: usize>(
+    line: usize,
+    args: &[&str],
+    optional: usize,
+) -> Result<[f64; N], Error> {
+    if args.len() < N - optional || args.len() > N {
+        return Err(syntax_error(
+            line,
+            format!("expected {} numbers, got {}", N - optional, args.len()),
+        ));
+    }
+    let mut values = [0.0; N];
+    for (v, arg) in values.iter_mut().zip(args) {
+        *v = arg
+            .parse()
+            .map_err(|_| syntax_error(line, format!("invalid number `{arg}`")))?;
+    }
+    Ok(values)
+}
+
+/// Resolve a 1-based or negative (relative to the end) OBJ index into a 0-based index.
+fn resolve_index(line: usize, s: &str, count: usize) -> Result<usize, Error> {
+    let i: i64 = s
+        .parse()
+        .map_err(|_| syntax_error(line, format!("invalid index `{s}`")))?;
+    let resolved = if i < 0 { count as i64 + i } else { i - 1 };
+    if i == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(syntax_error(line, format!("index {i} out of range")));
+    }
+    Ok(resolved as usize)
+}
+
+/// A mesh being built, with OBJ vertices deduplicated.
+struct MeshBuilder {
+    mesh: ObjMesh,
+    vertices: HashMap<(usize, Option<usize>, Option<usize>), u32>,
+    normals: Vec<Option<PositionVec>>,
+    uvs: Vec<Option<[NumPosition; 2]>>,
+}
+
+impl MeshBuilder {
+    fn vertex(
+        &mut self,
+        key: (usize, Option<usize>, Option<usize>),
+        positions: &[PositionVec],
+        uvs: &[[NumPosition; 2]],
+        normals: &[PositionVec],
+    ) -> u32 {
+        *self.vertices.entry(key).or_insert_with(|| {
+            let mesh = &mut self.mesh.mesh;
+            mesh.positions_mut().push(positions[key.0]);
+            self.uvs.push(key.1.map(|i| uvs[i]));
+            self.normals.push(key.2.map(|i| normals[i]));
+            (mesh.positions().len() - 1) as u32
+        })
+    }
+
+    fn finish(self) -> ObjMesh {
+        let mut mesh = self.mesh;
+        // attributes are kept only if every vertex has them
+        if let Some(normals) = self.normals.into_iter().collect() {
+            *mesh.mesh.normals_mut() = normals;
+        }
+        if let Some(uvs) = self.uvs.into_iter().collect() {
+            *mesh.mesh.uvs_mut() = uvs;
+        }
+        mesh
+    }
+}
+
+impl ObjModel {
+    /// Parse an OBJ file. Material libraries are not loaded.
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        let mut model = ObjModel::default();
+        let mut positions: Vec<PositionVec> = Vec::new();
+        let mut normals: Vec<PositionVec> = Vec::new();
+        let mut uvs: Vec<[NumPosition; 2]> = Vec::new();
+        let mut builders: Vec<MeshBuilder> = Vec::new();
+        let mut builder_indices: HashMap<(String, Option<String>), usize> = HashMap::new();
+        let mut name = String::new();
+        let mut material: Option<String> = None;
+        for (i, line) in s.lines().enumerate() {
+            let line_no = i + 1;
+            let line = line.split('#').next().unwrap_or_default();
+            let mut tokens = line.split_whitespace();
+            let Some(keyword) = tokens.next() else {
+                continue;
+            };
+            let args: Vec<&str> = tokens.collect();
+            match keyword {
+                "v" => {
+                    // the optional weight is ignored
+                    let [x, y, z, _] = parse_floats::<4>(line_no, &args, 1)?;
+                    positions.push(PositionVec::new(x, y, z));
+                }
+                "vn" => {
+                    let [x, y, z] = parse_floats::<3>(line_no, &args, 0)?;
+                    normals.push(PositionVec::new(x, y, z));
+                }
+                "vt" => {
+                    let [u, v, _] = parse_floats::<3>(line_no, &args, 2)?;
+                    uvs.push([u, v]);
+                }
+                "f" => {
+                    if args.len() < 3 {
+                        return Err(syntax_error(line_no, "a face needs at least 3 vertices"));
+                    }
+                    let mut keys = Vec::with_capacity(args.len());
+                    for arg in &args {
+                        let mut parts = arg.split('/');
+                        let v = resolve_index(line_no, parts.next().unwrap(), positions.len())?;
+                        let vt = match parts.next() {
+                            None | Some("") => None,
+                            Some(s) => Some(resolve_index(line_no, s, uvs.len())?),
+                        };
+                        let vn = match parts.next() {
+                            None | Some("") => None,
+                            Some(s) => Some(resolve_index(line_no, s, normals.len())?),
+                        };
+                        if parts.next().is_some() {
+                            return Err(syntax_error(line_no, format!("invalid vertex `{arg}`")));
+                        }
+                        keys.push((v, vt, vn));
+                    }
+                    let index = *builder_indices
+                        .entry((name.clone(), material.clone()))
+                        .or_insert_with(|| {
+                            builders.push(MeshBuilder {
+                                mesh: ObjMesh {
+                                    name: name.clone(),
+                                    material: material.clone(),
+                                    mesh: TriangleMesh::default(),
+                                },
+                                vertices: HashMap::new(),
+                                normals: Vec::new(),
+                                uvs: Vec::new(),
+                            });
+                            builders.len() - 1
+                        });
+                    let builder = &mut builders[index];
+                    let corners: Vec<PositionVec> = keys.iter().map(|k| positions[k.0]).collect();
+                    for triangle in triangulate(&corners) {
+                        let triangle =
+                            triangle.map(|i| builder.vertex(keys[i], &positions, &uvs, &normals));
+                        builder.mesh.mesh.indices_mut().push(triangle);
+                    }
+                }
+                "g" | "o" => name = args.join(" "),
+                "usemtl" => {
+                    if args.is_empty() {
+                        return Err(syntax_error(line_no, "missing material name"));
+                    }
+                    material = Some(args.join(" "));
+                }
+                "mtllib" => model
+                    .material_libraries
+                    .extend(args.iter().map(|s| s.to_string())),
+                // smoothing groups, lines, points, free-form geometry and so on are ignored
+                _ => {}
+            }
+        }
+        model.meshes = builders.into_iter().map(MeshBuilder::finish).collect();
+        Ok(model)
+    }
+
+    /// Load an OBJ file and its material libraries. Paths of textures are resolved
+    /// relative to the material library.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let mut model = Self::parse(&fs::read_to_string(path)?)?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        for library in &model.material_libraries {
+            let path = dir.join(library);
+            let materials = parse_mtl(&fs::read_to_string(&path)?).map_err(|e| match e {
+                Error::SyntaxError { line, message } => Error::SyntaxError {
+                    line,
+                    message: format!("{}: {message}", path.display()),
+                },
+                e => e,
+            })?;
+            let dir = path.parent().unwrap_or(Path::new(""));
+            for mut material in materials {
+                for map in [
+                    &mut material.diffuse_map,
+                    &mut material.specular_map,
+                    &mut material.emission_map,
+                    &mut material.bump_map,
+                ] {
+                    *map = map.as_ref().map(|p| dir.join(p));
+                }
+                model.materials.insert(material.name.clone(), material);
+            }
+        }
+        Ok(model)
+    }
+
+    /// Create an object for each mesh. Meshes without a known material
+    /// show their surface normals.
+    pub fn into_objects(self) -> Vec<Box<dyn Hittable>> {
+        let materials = self.materials;
+        self.meshes
+            .into_iter()
+            .map(|mesh| {
+                let material = mesh
+                    .material
+                    .as_ref()
+                    .and_then(|name| materials.get(name))
+                    .map_or_else(|| Arc::new(NormalColor) as _, MtlMaterial::material);
+                Box::new(Shaded {
+                    object: mesh.mesh,
+                    material,
+                }) as Box<dyn Hittable>
+            })
+            .collect()
+    }
+}
+
+/// Parse materials of an MTL file.
+pub fn parse_mtl(s: &str) -> Result<Vec<MtlMaterial>, Error> {
+    let mut materials: Vec<MtlMaterial> = Vec::new();
+    for (i, line) in s.lines().enumerate() {
+        let line_no = i + 1;
+        let line = line.split('#').next().unwrap_or_default();
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let args: Vec<&str> = tokens.collect();
+        if keyword == "newmtl" {
+            if args.is_empty() {
+                return Err(syntax_error(line_no, "missing material name"));
+            }
+            materials.push(MtlMaterial::new(args.join(" ")));
+            continue;
+        }
+        let material = materials
+            .last_mut()
+            .ok_or_else(|| syntax_error(line_no, format!("`{keyword}` before `newmtl`")))?;
+        // texture options like `-bm 1.0` precede the file name
+        let texture = || args.last().map(PathBuf::from);
+        match keyword {
+            "Kd" => material.diffuse = parse_floats::<3>(line_no, &args, 0)?,
+            "Ks" => material.specular = parse_floats::<3>(line_no, &args, 0)?,
+            "Ke" => material.emission = parse_floats::<3>(line_no, &args, 0)?,
+            "Ns" => material.shininess = parse_floats::<1>(line_no, &args, 0)?[0],
+            "Ni" => material.ior = parse_floats::<1>(line_no, &args, 0)?[0],
+            "d" => material.opacity = parse_floats::<1>(line_no, &args, 0)?[0],
+            "Tr" => material.opacity = 1.0 - parse_floats::<1>(line_no, &args, 0)?[0],
+            "illum" => {
+                material.illum = args
+                    .first()
+                    .and_then(|s| s.parse().ok())
+                    .ok_or_else(|| syntax_error(line_no, "invalid illumination model"))?
+            }
+            "map_Kd" => material.diffuse_map = texture(),
+            "map_Ks" => material.specular_map = texture(),
+            "map_Ke" => material.emission_map = texture(),
+            "bump" | "map_Bump" | "map_bump" => material.bump_map = texture(),
+            // ambient color, transmission filter and so on are ignored
+            _ => {}
+        }
+    }
+    for material in materials.iter().filter(|m| m.has_maps()) {
+        warn!(
+            "Texture maps of material `{}` are ignored, textures are not supported",
+            material.name
+        );
+    }
+    Ok(materials)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::import::obj::{parse_mtl, MtlMaterial, ObjModel};
+    use crate::import::{triangulate, Error};
+    use crate::ray::Ray;
+    use crate::scene::HitEvent;
+    use crate::testing;
+    use crate::types::{PixelF64, PositionVec};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use tracing_test::traced_test;
+
+    #[test]
+    fn test_parse_faces() {
+        let model = ObjModel::parse(
+            "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\nvn 0 0 1\n\
+             g quad\nf 1/1/1 2/2/1 3/3/1 4/4/1\n\
+             g tri\nusemtl red\nf -4//-1 -3//-1 -2//-1\n",
+        )
+        .expect("parse");
+        assert_eq!(model.meshes.len(), 2);
+        let quad = &model.meshes[0];
+        assert_eq!(
+            (quad.name.as_str(), quad.material.as_deref()),
+            ("quad", None)
+        );
+        assert_eq!(quad.mesh.triangle_count(), 2);
+        let corner = quad
+            .mesh
+            .positions()
+            .iter()
+            .position(|p| *p == PositionVec::new(1.0, 1.0, 0.0))
+            .expect("corner");
+        assert_eq!(quad.mesh.uvs()[corner], [1.0, 1.0]);
+        assert_eq!(quad.mesh.normals().len(), 4);
+        let tri = &model.meshes[1];
+        assert_eq!(tri.material.as_deref(), Some("red"));
+        assert_eq!(tri.mesh.positions().len(), 3);
+        assert!(tri.mesh.uvs().is_empty());
+    }
+
+    #[test]
+    fn test_triangulate_concave_polygon() {
+        // an L-shape, fan triangulation from the first vertex would leave the polygon
+        let points = [(0, 0), (2, 0), (2, 1), (1, 1), (1, 2), (0, 2)]
+            .map(|(x, y)| PositionVec::new(x as f64, y as f64, 0.0));
+        let triangles = triangulate(&points);
+        assert_eq!(triangles.len(), 4);
+        let area: f64 = triangles
+            .iter()
+            .map(|t| {
+                let [a, b, c] = t.map(|i| points[i]);
+                (b - a).cross(&(c - a)).z / 2.0
+            })
+            .inspect(|&a| assert!(a > 0.0))
+            .sum();
+        assert_eq!(area, 3.0);
+        assert!(triangulate(&points[..2]).is_empty());
+        assert!(triangulate(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = ObjModel::parse("v 0 0 0\nv 1 0 0\nf 1 2 3\n").unwrap_err();
+        assert!(matches!(err, Error::SyntaxError { line: 3, .. }));
+        let err = ObjModel::parse("v 0 0\n").unwrap_err();
+        assert!(matches!(err, Error::SyntaxError { line: 1, .. }));
+        assert!(parse_mtl("Kd 1 1 1\n").is_err());
+    }
+
+    #[traced_test]
+    #[test]
+    fn test_mtl_materials() {
+        let materials = parse_mtl(
+            "newmtl mirror\nKs 0.9 0.9 0.9\nNs 1000\nillum 3\n\
+             newmtl brushed\nKs 0.9 0.9 0.9\nNs 0\nillum 3\n\
+             newmtl window\nKd 1 1 1\nNi 1.5\nd 0.2\n\
+             newmtl wood\nKd 0.5 0.3 0.1\nmap_Kd wood.png\n",
+        )
+        .expect("parse");
+        let ray = Ray {
+            origin: PositionVec::new(0.0, 0.0, 1.0),
+            direction: -PositionVec::z(),
+        };
+        let hit = HitEvent {
+            hit_pos: PositionVec::zeros(),
+            surface_nv: PositionVec::z(),
+            shading_nv: PositionVec::z(),
+            uv: [0.0, 0.0],
+            t: 1.0,
+            color: None,
+            material: None,
+        };
+        // spread of reflected rays by Ns
+        let spread = |m: &MtlMaterial| {
+            let mut rng = StdRng::seed_from_u64(1);
+            let scatter = m
+                .material()
+                .scatter(&ray, &hit, &mut rng)
+                .expect("reflected");
+            1.0 - scatter.ray.direction.normalize().z
+        };
+        assert!((materials[0].roughness() - 0.0447).abs() < 1e-3);
+        assert_eq!(materials[1].roughness(), 1.0);
+        assert!(spread(&materials[0]) < spread(&materials[1]));
+        // transparent surfaces refract, passing most rays through
+        let mut rng = StdRng::seed_from_u64(1);
+        let scatter = materials[2]
+            .material()
+            .scatter(&ray, &hit, &mut rng)
+            .expect("refracted");
+        assert_eq!(scatter.attenuation, PixelF64::new(1.0, 1.0, 1.0));
+        assert!(logs_contain("Texture maps of material `wood` are ignored"));
+        assert!(!logs_contain("material `window`"));
+    }
+
+    #[test]
+    fn test_load_with_materials() {
+        let model = ObjModel::load(&testing::path("cube.obj")).expect("load");
+        assert_eq!(model.meshes.len(), 2);
+        let triangles: usize = model.meshes.iter().map(|m| m.mesh.triangle_count()).sum();
+        assert_eq!(triangles, 12);
+        let red = &model.materials["red"];
+        assert_eq!(red.diffuse, [0.8, 0.1, 0.1]);
+        assert_eq!(red.shininess, 32.0);
+        assert_eq!(red.diffuse_map, Some(testing::path("red.ppm")));
+        let objects = model.into_objects();
+        let hit = |origin: PositionVec, direction: PositionVec| -> (Ray, HitEvent) {
+            let ray = Ray { origin, direction };
+            let hit = objects
+                .iter()
+                .filter_map(|o| o.try_hit(&ray, 0.0, f64::INFINITY))
+                .min_by(|a, b| a.t.total_cmp(&b.t))
+                .expect("hit");
+            (ray, hit)
+        };
+        let (ray, front) = hit(PositionVec::new(0.0, 0.0, 5.0), -PositionVec::z());
+        assert!((front.t - 4.0).abs() < 1e-12);
+        let material = front.material.as_ref().expect("material");
+        let mut rng = StdRng::seed_from_u64(1);
+        let scatter = material.scatter(&ray, &front, &mut rng).expect("diffuse");
+        assert_eq!(scatter.attenuation, PixelF64::new(0.8, 0.1, 0.1));
+        // the lamp emits its `Ke`
+        let (ray, top) = hit(PositionVec::new(0.0, 5.0, 0.0), -PositionVec::y());
+        let material = top.material.as_ref().expect("material");
+        assert!(material.scatter(&ray, &top, &mut rng).is_none());
+        assert_eq!(material.emitted(&ray, &top), PixelF64::new(0.7, 0.7, 0.7));
+    }
+}
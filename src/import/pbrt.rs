@@ -0,0 +1,1133 @@
+//! A subset of the pbrt-v4 scene format.
+//!
+//! Supported statements are the camera and film settings, transforms, attribute blocks,
+//! `sphere`, `trianglemesh` and `plymesh` shapes, `diffuse`, `conductor` and `dielectric`
+//! materials, area lights and light sources. Unsupported statements and parameters are
+//! skipped with a warning, so scenes render as closely as the renderer allows.
+//!
+//! pbrt uses a left-handed coordinate system. Transforms are applied as written, and the
+//! camera maps camera space `+x` to the right of the image like pbrt does, so images
+//! are not mirrored.
+
+use crate::import::{ply, Error};
+use crate::light::Light;
+use crate::material::{Diffuse, Emissive, Glass, Material, Metal};
+use crate::objects::mesh::TriangleMesh;
+use crate::objects::shaded::Shaded;
+use crate::objects::sphere::Sphere;
+use crate::ppm;
+use crate::scene::{Background, Camera, Hittable};
+use crate::types::{NumColorRatio, NumPosition, PixelF64, PositionVec};
+use nalgebra::{Matrix3, Matrix4, Point3, Rotation3, Unit, Vector3};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::warn;
+
+type Color = [NumColorRatio; 3];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PbrtMaterial {
+    Diffuse {
+        reflectance: Color,
+    },
+    Conductor {
+        /// reflectance, or copper if the material is given by spectral data
+        reflectance: Color,
+        roughness: NumColorRatio,
+    },
+    Dielectric {
+        /// index of refraction
+        eta: NumColorRatio,
+        roughness: NumColorRatio,
+    },
+}
+
+impl Default for PbrtMaterial {
+    fn default() -> Self {
+        PbrtMaterial::Diffuse {
+            reflectance: [0.5; 3],
+        }
+    }
+}
+
+impl PbrtMaterial {
+    /// The closest material of the renderer, ignoring the roughness of dielectrics.
+    pub fn material(&self) -> Arc<dyn Material> {
+        let color = |[r, g, b]: Color| PixelF64::new(r, g, b);
+        match self {
+            PbrtMaterial::Diffuse { reflectance } => Arc::new(Diffuse {
+                albedo: color(*reflectance),
+            }),
+            PbrtMaterial::Conductor {
+                reflectance,
+                roughness,
+            } => Arc::new(Metal {
+                albedo: color(*reflectance),
+                fuzz: roughness.clamp(0.0, 1.0),
+            }),
+            PbrtMaterial::Dielectric { eta, .. } => Arc::new(Glass { ior: *eta }),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum PbrtGeometry {
+    Sphere {
+        center: PositionVec,
+        radius: NumPosition,
+    },
+    /// triangles in world space
+    Mesh(TriangleMesh),
+}
+
+#[derive(Clone, Debug)]
+pub struct PbrtShape {
+    pub geometry: PbrtGeometry,
+    /// index into [`PbrtScene::materials`]
+    pub material: usize,
+    /// emitted radiance of an area light
+    pub emission: Option<Color>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PbrtLightKind {
+    Point {
+        position: PositionVec,
+    },
+    Spot {
+        position: PositionVec,
+        direction: PositionVec,
+        /// half angle of the cone in degrees
+        cone_angle: NumPosition,
+        /// angle in degrees over which the light fades out towards the edge of the cone
+        cone_delta: NumPosition,
+    },
+    /// light arriving from infinitely far away, `direction` is where it travels to
+    Distant {
+        direction: PositionVec,
+    },
+    /// environment light from every direction
+    Infinite,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PbrtLight {
+    pub kind: PbrtLightKind,
+    /// intensity or radiance with the `scale` parameter applied
+    pub color: Color,
+}
+
+/// Perspective camera placed in world space.
+#[derive(Clone, Debug)]
+pub struct PbrtCamera {
+    pub position: PositionVec,
+    /// rotation from camera space to world space, looking at -z like [`Camera`]
+    pub orientation: Matrix3<NumPosition>,
+    /// field of view of the shorter image axis in degrees
+    pub fov: NumPosition,
+}
+
+impl Default for PbrtCamera {
+    fn default() -> Self {
+        PbrtCamera {
+            position: PositionVec::zeros(),
+            // pbrt cameras look at +z, with +x to the right of the image
+            orientation: Matrix3::from_diagonal(&Vector3::new(1.0, 1.0, -1.0)),
+            fov: 90.0,
+        }
+    }
+}
+
+impl PbrtCamera {
+    pub fn camera(&self, width: u32, height: u32) -> Camera {
+        let pixel_size =
+            2.0 * (self.fov.to_radians() / 2.0).tan() / width.min(height) as NumPosition;
+        let mut camera = Camera::new(width, height, pixel_size * width as NumPosition, 1.0);
+        camera.pos = self.position;
+        camera.orientation = self.orientation;
+        camera
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PbrtScene {
+    pub width: u32,
+    pub height: u32,
+    /// samples per pixel
+    pub samples: usize,
+    /// image file name set in `Film`
+    pub output: Option<PathBuf>,
+    pub camera: PbrtCamera,
+    /// materials referenced by shapes, the first one is the default material
+    pub materials: Vec<PbrtMaterial>,
+    pub shapes: Vec<PbrtShape>,
+    pub lights: Vec<PbrtLight>,
+}
+
+impl Default for PbrtScene {
+    fn default() -> Self {
+        PbrtScene {
+            width: 1280,
+            height: 720,
+            samples: 16,
+            output: None,
+            camera: PbrtCamera::default(),
+            materials: vec![PbrtMaterial::default()],
+            shapes: Vec::new(),
+            lights: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    /// statement keyword, or a bare `true` / `false`
+    Word(String),
+    Str(String),
+    Num(f64),
+    Open,
+    Close,
+}
+
+fn tokenize(s: &str) -> Result<Vec<(Token, usize)>, Error> {
+    let mut tokens = Vec::new();
+    let mut chars = s.chars().peekable();
+    let mut line = 1;
+    while let Some(&c) = chars.peek() {
+        match c {
+            '\n' => {
+                line += 1;
+                chars.next();
+            }
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '#' => while chars.next_if(|&c| c != '\n').is_some() {},
+            '[' | ']' => {
+                chars.next();
+                tokens.push((if c == '[' { Token::Open } else { Token::Close }, line));
+            }
+            '"' => {
+                chars.next();
+                let start = line;
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        None => {
+                            return Err(Error::SyntaxError {
+                                line: start,
+                                message: "unterminated string".into(),
+                            })
+                        }
+                        Some('"') => break,
+                        Some('\\') => value.extend(chars.next().map(|c| match c {
+                            'n' => '\n',
+                            't' => '\t',
+                            c => c,
+                        })),
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            value.push(c);
+                        }
+                    }
+                }
+                tokens.push((Token::Str(value), start));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(c) =
+                    chars.next_if(|&c| !c.is_whitespace() && !matches!(c, '[' | ']' | '"' | '#'))
+                {
+                    word.push(c);
+                }
+                let token = if word.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                    Token::Word(word)
+                } else {
+                    Token::Num(word.parse().map_err(|_| Error::SyntaxError {
+                        line,
+                        message: format!("invalid number `{word}`"),
+                    })?)
+                };
+                tokens.push((token, line));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Num(f64),
+    Str(String),
+    Bool(bool),
+}
+
+/// A parameter like `"float radius" 2`.
+#[derive(Debug)]
+struct Param {
+    ty: String,
+    name: String,
+    values: Vec<Value>,
+}
+
+#[derive(Debug, Default)]
+struct Params(Vec<Param>);
+
+impl Params {
+    fn get(&self, name: &str) -> Option<&Param> {
+        self.0.iter().find(|p| p.name == name)
+    }
+
+    fn floats(&self, name: &str) -> Option<Vec<f64>> {
+        let param = self.get(name)?;
+        let floats: Vec<f64> = param
+            .values
+            .iter()
+            .filter_map(|v| match v {
+                Value::Num(v) => Some(*v),
+                _ => None,
+            })
+            .collect();
+        if floats.len() != param.values.len() {
+            warn!("Parameter `{name}` is not numeric and is ignored");
+            return None;
+        }
+        Some(floats)
+    }
+
+    fn float(&self, name: &str, default: f64) -> f64 {
+        self.floats(name)
+            .and_then(|v| v.first().copied())
+            .unwrap_or(default)
+    }
+
+    fn string(&self, name: &str) -> Option<&str> {
+        match self.get(name)?.values.first() {
+            Some(Value::Str(s)) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn point(&self, name: &str, default: PositionVec) -> PositionVec {
+        match self.floats(name).as_deref() {
+            Some(&[x, y, z]) => PositionVec::new(x, y, z),
+            _ => default,
+        }
+    }
+
+    /// A color given as RGB. Spectra, blackbody emitters and textures are not supported.
+    fn color(&self, name: &str) -> Option<Color> {
+        let param = self.get(name)?;
+        match (param.ty.as_str(), self.floats(name).as_deref()) {
+            ("rgb", Some(&[r, g, b])) => Some([r, g, b]),
+            (ty, _) => {
+                warn!("Parameter `{ty} {name}` is not supported, using the default value");
+                None
+            }
+        }
+    }
+
+    fn scaled_color(&self, name: &str, default: Color) -> Color {
+        let scale = self.float("scale", 1.0);
+        self.color(name).unwrap_or(default).map(|c| c * scale)
+    }
+}
+
+/// Graphics state saved by `AttributeBegin`.
+#[derive(Clone)]
+struct State {
+    /// transformation from object space to world space
+    ctm: Matrix4<f64>,
+    material: usize,
+    area_light: Option<Color>,
+    reverse_orientation: bool,
+}
+
+struct Parser {
+    scene: PbrtScene,
+    state: State,
+    stack: Vec<State>,
+    named_materials: HashMap<String, usize>,
+    coordinate_systems: HashMap<String, Matrix4<f64>>,
+    dir: PathBuf,
+    /// files being parsed, each one included by the one before
+    includes: Vec<PathBuf>,
+}
+
+/// Deepest nesting of included files, deeper ones are assumed to be runaway recursion.
+const MAX_INCLUDE_DEPTH: usize = 32;
+
+fn syntax_error(line: usize, message: impl Into<String>) -> Error {
+    Error::SyntaxError {
+        line,
+        message: message.into(),
+    }
+}
+
+/// Number of leading string arguments of each statement with parameters.
+fn positional_strings(keyword: &str) -> Option<usize> {
+    Some(match keyword {
+        "Camera" | "Film" | "Sampler" | "Shape" | "Material" | "LightSource"
+        | "AreaLightSource" | "PixelFilter" | "Integrator" | "Accelerator"
+        | "MakeNamedMaterial" | "MakeNamedMedium" | "Attribute" | "ColorSpace"
+        | "NamedMaterial" | "Include" | "Import" | "ObjectBegin" | "ObjectInstance"
+        | "CoordinateSystem" | "CoordSysTransform" => 1,
+        "Texture" => 3,
+        "Option" => 0,
+        _ => return None,
+    })
+}
+
+fn look_at(eye: PositionVec, target: PositionVec, up: PositionVec) -> Option<Matrix4<f64>> {
+    let dir = (target - eye).try_normalize(0.0)?;
+    let right = up.try_normalize(0.0)?.cross(&dir).try_normalize(0.0)?;
+    let new_up = dir.cross(&right);
+    let mut world_from_camera = Matrix4::identity();
+    for (i, column) in [right, new_up, dir, eye].iter().enumerate() {
+        world_from_camera
+            .fixed_view_mut::<3, 1>(0, i)
+            .copy_from(column);
+    }
+    world_from_camera.try_inverse()
+}
+
+impl Parser {
+    fn new(dir: &Path) -> Self {
+        Parser {
+            scene: PbrtScene::default(),
+            state: State {
+                ctm: Matrix4::identity(),
+                material: 0,
+                area_light: None,
+                reverse_orientation: false,
+            },
+            stack: Vec::new(),
+            named_materials: HashMap::new(),
+            coordinate_systems: HashMap::new(),
+            dir: dir.to_path_buf(),
+            includes: Vec::new(),
+        }
+    }
+
+    fn run(&mut self, source: &str) -> Result<(), Error> {
+        let tokens = tokenize(source)?;
+        let mut pos = 0;
+        while pos < tokens.len() {
+            let (Token::Word(keyword), line) = &tokens[pos] else {
+                return Err(syntax_error(tokens[pos].1, "expected a statement"));
+            };
+            pos += 1;
+            // arguments run to the next keyword, bracketed lists are grouped
+            let mut args: Vec<Vec<Value>> = Vec::new();
+            let mut list: Option<Vec<Value>> = None;
+            while let Some((token, token_line)) = tokens.get(pos) {
+                let value = match token {
+                    Token::Word(w) if w == "true" || w == "false" => Value::Bool(w == "true"),
+                    Token::Word(_) if list.is_none() => break,
+                    Token::Word(w) => {
+                        return Err(syntax_error(*token_line, format!("unexpected `{w}`")))
+                    }
+                    Token::Num(v) => Value::Num(*v),
+                    Token::Str(s) => Value::Str(s.clone()),
+                    Token::Open if list.is_none() => {
+                        list = Some(Vec::new());
+                        pos += 1;
+                        continue;
+                    }
+                    Token::Close if list.is_some() => {
+                        args.extend(list.take());
+                        pos += 1;
+                        continue;
+                    }
+                    _ => return Err(syntax_error(*token_line, "unbalanced brackets")),
+                };
+                match &mut list {
+                    Some(list) => list.push(value),
+                    None => args.push(vec![value]),
+                }
+                pos += 1;
+            }
+            if list.is_some() {
+                return Err(syntax_error(*line, "unterminated list"));
+            }
+            self.statement(keyword, args, *line)?;
+        }
+        Ok(())
+    }
+
+    fn statement(
+        &mut self,
+        keyword: &str,
+        args: Vec<Vec<Value>>,
+        line: usize,
+    ) -> Result<(), Error> {
+        let error = |message: &str| syntax_error(line, format!("{keyword}: {message}"));
+        if keyword == "MediumInterface" {
+            warn!("Participating media are not supported");
+            return Ok(());
+        }
+        let Some(string_count) = positional_strings(keyword) else {
+            // statements with numbers only
+            let numbers: Vec<f64> = args
+                .iter()
+                .flatten()
+                .map(|v| match v {
+                    Value::Num(v) => Ok(*v),
+                    _ => Err(error("expected numbers")),
+                })
+                .collect::<Result<_, _>>()?;
+            return self.transform_statement(keyword, &numbers, line);
+        };
+        let mut args = args.into_iter();
+        let mut strings = Vec::new();
+        for _ in 0..string_count {
+            match args.next().as_deref() {
+                Some([Value::Str(s)]) => strings.push(s.clone()),
+                _ => return Err(error("expected a name")),
+            }
+        }
+        let mut params = Params::default();
+        while let Some(declaration) = args.next() {
+            let (Some(values), [Value::Str(declaration)]) = (args.next(), &declaration[..]) else {
+                return Err(error("expected a parameter"));
+            };
+            let [ty, name] = declaration.split_whitespace().collect::<Vec<_>>()[..] else {
+                return Err(error(&format!("invalid parameter `{declaration}`")));
+            };
+            params.0.push(Param {
+                ty: ty.to_string(),
+                name: name.to_string(),
+                values,
+            });
+        }
+        let name = strings.first().map(String::as_str).unwrap_or_default();
+        match keyword {
+            "Camera" => self.camera(name, &params),
+            "Film" => {
+                if let Some(&[w]) = params.floats("xresolution").as_deref() {
+                    self.scene.width = (w as u32).max(1);
+                }
+                if let Some(&[h]) = params.floats("yresolution").as_deref() {
+                    self.scene.height = (h as u32).max(1);
+                }
+                if ppm::check_size(self.scene.width, self.scene.height).is_err() {
+                    return Err(error(&format!("more than {} pixels", ppm::MAX_PIXELS)));
+                }
+                self.scene.output = params.string("filename").map(PathBuf::from);
+            }
+            "Sampler" => {
+                self.scene.samples = (params.float("pixelsamples", 16.0) as usize).max(1);
+            }
+            "Shape" => self.shape(name, &params)?,
+            "Material" => {
+                self.scene.materials.push(Self::material(name, &params));
+                self.state.material = self.scene.materials.len() - 1;
+            }
+            "MakeNamedMaterial" => {
+                let ty = params.string("type").unwrap_or("diffuse");
+                self.scene.materials.push(Self::material(ty, &params));
+                self.named_materials
+                    .insert(name.to_string(), self.scene.materials.len() - 1);
+            }
+            "NamedMaterial" => {
+                self.state.material = *self
+                    .named_materials
+                    .get(name)
+                    .ok_or_else(|| error(&format!("unknown material `{name}`")))?;
+            }
+            "LightSource" => self.light(name, &params),
+            "AreaLightSource" => {
+                if name != "diffuse" {
+                    warn!("Area light `{name}` is not supported");
+                }
+                self.state.area_light = Some(params.scaled_color("L", [1.0; 3]));
+            }
+            "Include" | "Import" => {
+                let path = self.dir.join(name);
+                let source = fs::read_to_string(&path)?;
+                let canonical = fs::canonicalize(&path)?;
+                if self.includes.contains(&canonical) {
+                    return Err(error(&format!("{} includes itself", path.display())));
+                }
+                if self.includes.len() >= MAX_INCLUDE_DEPTH {
+                    return Err(error(&format!(
+                        "includes nested deeper than {MAX_INCLUDE_DEPTH} files"
+                    )));
+                }
+                self.includes.push(canonical);
+                let result = self.run(&source).map_err(|e| match e {
+                    Error::SyntaxError { line, message } => Error::SyntaxError {
+                        line,
+                        message: format!("{}: {message}", path.display()),
+                    },
+                    e => e,
+                });
+                self.includes.pop();
+                result?;
+            }
+            "CoordinateSystem" => {
+                self.coordinate_systems
+                    .insert(name.to_string(), self.state.ctm);
+            }
+            "CoordSysTransform" => match self.coordinate_systems.get(name) {
+                Some(m) => self.state.ctm = *m,
+                None => warn!("Unknown coordinate system `{name}`"),
+            },
+            "ObjectBegin" | "ObjectInstance" => {
+                return Err(Error::Unsupported(format!("{keyword} statements")))
+            }
+            _ => warn!("{keyword} statements are ignored"),
+        }
+        Ok(())
+    }
+
+    fn transform_statement(&mut self, keyword: &str, v: &[f64], line: usize) -> Result<(), Error> {
+        let ctm = &mut self.state.ctm;
+        let arguments = |n: usize| {
+            if v.len() == n {
+                Ok(())
+            } else {
+                Err(syntax_error(
+                    line,
+                    format!("{keyword}: expected {n} numbers"),
+                ))
+            }
+        };
+        match keyword {
+            "Translate" => {
+                arguments(3)?;
+                *ctm *= Matrix4::new_translation(&Vector3::new(v[0], v[1], v[2]));
+            }
+            "Scale" => {
+                arguments(3)?;
+                *ctm *= Matrix4::new_nonuniform_scaling(&Vector3::new(v[0], v[1], v[2]));
+            }
+            "Rotate" => {
+                arguments(4)?;
+                let axis = Unit::try_new(Vector3::new(v[1], v[2], v[3]), 0.0)
+                    .ok_or_else(|| syntax_error(line, "Rotate: zero axis"))?;
+                *ctm *= Rotation3::from_axis_angle(&axis, v[0].to_radians()).to_homogeneous();
+            }
+            "LookAt" => {
+                arguments(9)?;
+                let p = |i: usize| PositionVec::new(v[i], v[i + 1], v[i + 2]);
+                *ctm *= look_at(p(0), p(3), p(6))
+                    .ok_or_else(|| syntax_error(line, "LookAt: degenerate view"))?;
+            }
+            // matrices are given column by column
+            "Transform" => {
+                arguments(16)?;
+                *ctm = Matrix4::from_column_slice(v);
+            }
+            "ConcatTransform" => {
+                arguments(16)?;
+                *ctm *= Matrix4::from_column_slice(v);
+            }
+            "Identity" => {
+                arguments(0)?;
+                *ctm = Matrix4::identity();
+            }
+            "WorldBegin" => {
+                arguments(0)?;
+                *ctm = Matrix4::identity();
+                self.coordinate_systems
+                    .insert("world".to_string(), Matrix4::identity());
+            }
+            "AttributeBegin" | "TransformBegin" => {
+                arguments(0)?;
+                self.stack.push(self.state.clone());
+            }
+            "AttributeEnd" | "TransformEnd" => {
+                arguments(0)?;
+                let saved = self
+                    .stack
+                    .pop()
+                    .ok_or_else(|| syntax_error(line, format!("{keyword} without begin")))?;
+                if keyword == "TransformEnd" {
+                    self.state.ctm = saved.ctm;
+                } else {
+                    self.state = saved;
+                }
+            }
+            "ReverseOrientation" => {
+                arguments(0)?;
+                self.state.reverse_orientation = !self.state.reverse_orientation;
+            }
+            "ObjectEnd" => return Err(Error::Unsupported("object instancing".into())),
+            _ => return Err(syntax_error(line, format!("unknown statement `{keyword}`"))),
+        }
+        Ok(())
+    }
+
+    fn camera(&mut self, kind: &str, params: &Params) {
+        if kind != "perspective" {
+            warn!("Camera `{kind}` is not supported, using a perspective camera");
+        }
+        let Some(world_from_camera) = self.state.ctm.try_inverse() else {
+            warn!("Camera transform is singular and ignored");
+            return;
+        };
+        self.coordinate_systems
+            .insert("camera".to_string(), world_from_camera);
+        let axis = |i: usize| world_from_camera.fixed_view::<3, 1>(0, i).normalize();
+        self.scene.camera = PbrtCamera {
+            position: world_from_camera.transform_point(&Point3::origin()).coords,
+            orientation: Matrix3::from_columns(&[axis(0), axis(1), -axis(2)]),
+            fov: params.float("fov", 90.0),
+        };
+    }
+
+    fn material(kind: &str, params: &Params) -> PbrtMaterial {
+        let roughness = params.float("roughness", 0.0);
+        match kind {
+            "diffuse" | "coateddiffuse" => PbrtMaterial::Diffuse {
+                reflectance: params.color("reflectance").unwrap_or([0.5; 3]),
+            },
+            "conductor" => PbrtMaterial::Conductor {
+                reflectance: params.color("reflectance").unwrap_or([0.955, 0.638, 0.538]),
+                roughness,
+            },
+            "dielectric" | "thindielectric" => PbrtMaterial::Dielectric {
+                eta: params.float("eta", 1.5),
+                roughness,
+            },
+            _ => {
+                warn!("Material `{kind}` is not supported, using a diffuse material");
+                PbrtMaterial::default()
+            }
+        }
+    }
+
+    fn light(&mut self, kind: &str, params: &Params) {
+        let ctm = &self.state.ctm;
+        let point = |name: &str, default: PositionVec| {
+            ctm.transform_point(&Point3::from(params.point(name, default)))
+                .coords
+        };
+        let from = point("from", PositionVec::zeros());
+        let (kind, color) = match kind {
+            "point" => (
+                PbrtLightKind::Point { position: from },
+                params.scaled_color("I", [1.0; 3]),
+            ),
+            "spot" => (
+                PbrtLightKind::Spot {
+                    position: from,
+                    direction: (point("to", PositionVec::z()) - from).normalize(),
+                    cone_angle: params.float("coneangle", 30.0),
+                    cone_delta: params.float("conedelta", 5.0),
+                },
+                params.scaled_color("I", [1.0; 3]),
+            ),
+            "distant" => (
+                PbrtLightKind::Distant {
+                    direction: (point("to", PositionVec::z()) - from).normalize(),
+                },
+                params.scaled_color("L", [1.0; 3]),
+            ),
+            "infinite" => {
+                if params.get("filename").is_some() {
+                    warn!("Environment maps are not supported, using a constant color");
+                }
+                (PbrtLightKind::Infinite, params.scaled_color("L", [1.0; 3]))
+            }
+            _ => {
+                warn!("Light `{kind}` is not supported");
+                return;
+            }
+        };
+        self.scene.lights.push(PbrtLight { kind, color });
+    }
+
+    fn shape(&mut self, kind: &str, params: &Params) -> Result<(), Error> {
+        let ctm = self.state.ctm;
+        let linear = ctm.fixed_view::<3, 3>(0, 0).into_owned();
+        let geometry = match kind {
+            "sphere" => {
+                if ["zmin", "zmax", "phimax"]
+                    .iter()
+                    .any(|p| params.get(p).is_some())
+                {
+                    warn!("Partial spheres are not supported, rendering full spheres");
+                }
+                PbrtGeometry::Sphere {
+                    center: ctm.transform_point(&Point3::origin()).coords,
+                    // non-uniform scaling is approximated by the average scale
+                    radius: params.float("radius", 1.0) * linear.determinant().abs().cbrt(),
+                }
+            }
+            "trianglemesh" => PbrtGeometry::Mesh(Self::triangle_mesh(params)?),
+            "plymesh" => {
+                let file = params
+                    .string("filename")
+                    .ok_or_else(|| Error::InvalidData("plymesh without filename".into()))?;
+                PbrtGeometry::Mesh(ply::load(&self.dir.join(file))?)
+            }
+            _ => {
+                warn!("Shape `{kind}` is not supported");
+                return Ok(());
+            }
+        };
+        let geometry = match geometry {
+            PbrtGeometry::Mesh(mut mesh) => {
+                let normal_matrix = linear
+                    .try_inverse()
+                    .ok_or_else(|| Error::InvalidData("singular shape transform".into()))?
+                    .transpose();
+                let flip_normals = if self.state.reverse_orientation {
+                    -1.0
+                } else {
+                    1.0
+                };
+                for p in mesh.positions_mut() {
+                    *p = ctm.transform_point(&Point3::from(*p)).coords;
+                }
+                for n in mesh.normals_mut() {
+                    *n = (normal_matrix * *n).normalize() * flip_normals;
+                }
+                if (linear.determinant() < 0.0) != self.state.reverse_orientation {
+                    for t in mesh.indices_mut() {
+                        t.swap(1, 2);
+                    }
+                }
+                PbrtGeometry::Mesh(mesh)
+            }
+            sphere => sphere,
+        };
+        self.scene.shapes.push(PbrtShape {
+            geometry,
+            material: self.state.material,
+            emission: self.state.area_light,
+        });
+        Ok(())
+    }
+
+    fn triangle_mesh(params: &Params) -> Result<TriangleMesh, Error> {
+        let invalid = |message: &str| Error::InvalidData(format!("trianglemesh: {message}"));
+        let floats = |name: &str| params.floats(name).unwrap_or_default();
+        let positions: Vec<PositionVec> = floats("P")
+            .chunks_exact(3)
+            .map(|p| PositionVec::new(p[0], p[1], p[2]))
+            .collect();
+        let indices = match params.floats("indices") {
+            Some(indices) => indices,
+            None if positions.len() == 3 => vec![0.0, 1.0, 2.0],
+            None => return Err(invalid("missing indices")),
+        };
+        if indices.len() % 3 != 0 {
+            return Err(invalid("number of indices is not a multiple of 3"));
+        }
+        if indices
+            .iter()
+            .any(|&i| i < 0.0 || i as usize >= positions.len())
+        {
+            return Err(invalid("vertex index out of range"));
+        }
+        let normals: Vec<PositionVec> = floats("N")
+            .chunks_exact(3)
+            .map(|n| PositionVec::new(n[0], n[1], n[2]))
+            .collect();
+        let uvs: Vec<[NumPosition; 2]> =
+            floats("uv").chunks_exact(2).map(|t| [t[0], t[1]]).collect();
+        let attribute = |len: usize, name: &str| {
+            if len == 0 || len == positions.len() {
+                Ok(())
+            } else {
+                Err(invalid(&format!("{name} does not match P")))
+            }
+        };
+        attribute(normals.len(), "N")?;
+        attribute(uvs.len(), "uv")?;
+        let indices = indices
+            .chunks_exact(3)
+            .map(|t| [t[0] as u32, t[1] as u32, t[2] as u32])
+            .collect();
+        let mut mesh = TriangleMesh::new(positions, indices);
+        *mesh.normals_mut() = normals;
+        *mesh.uvs_mut() = uvs;
+        Ok(mesh)
+    }
+}
+
+impl PbrtScene {
+    /// Load a pbrt file. Included files and meshes are resolved relative to its directory.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let mut parser = Parser::new(path.parent().unwrap_or(Path::new("")));
+        let source = fs::read_to_string(path)?;
+        parser.includes.push(fs::canonicalize(path)?);
+        parser.run(&source)?;
+        Ok(parser.scene)
+    }
+
+    /// Parse a pbrt scene, resolving included files and meshes relative to `dir`.
+    pub fn parse(source: &str, dir: &Path) -> Result<Self, Error> {
+        let mut parser = Parser::new(dir);
+        parser.run(source)?;
+        Ok(parser.scene)
+    }
+
+    /// The background, which is the color of infinite lights or black.
+    pub fn background(&self) -> Background {
+        let mut color = [0.0; 3];
+        for light in &self.lights {
+            if light.kind == PbrtLightKind::Infinite {
+                for (c, l) in color.iter_mut().zip(light.color) {
+                    *c += l;
+                }
+            }
+        }
+        Background::Color(PixelF64::new(color[0], color[1], color[2]))
+    }
+
+    /// Lights without a surface, all lights but the infinite ones making up the background.
+    pub fn lights(&self) -> Vec<Light> {
+        let color = |[r, g, b]: Color| PixelF64::new(r, g, b);
+        self.lights
+            .iter()
+            .filter_map(|light| match light.kind {
+                PbrtLightKind::Point { position } => Some(Light::Point {
+                    position,
+                    intensity: color(light.color),
+                }),
+                PbrtLightKind::Spot {
+                    position,
+                    direction,
+                    cone_angle,
+                    cone_delta,
+                } => Some(Light::Spot {
+                    position,
+                    direction,
+                    cos_outer: cone_angle.to_radians().cos(),
+                    cos_inner: (cone_angle - cone_delta).max(0.0).to_radians().cos(),
+                    intensity: color(light.color),
+                }),
+                PbrtLightKind::Distant { direction } => Some(Light::Distant {
+                    direction,
+                    radiance: color(light.color),
+                }),
+                PbrtLightKind::Infinite => None,
+            })
+            .collect()
+    }
+
+    /// Create an object for each shape, made of its material, or emitting light if it is
+    /// an area light. Materials are shared by the objects using them.
+    pub fn into_objects(self) -> Vec<Box<dyn Hittable>> {
+        let materials: Vec<Arc<dyn Material>> =
+            self.materials.iter().map(PbrtMaterial::material).collect();
+        self.shapes
+            .into_iter()
+            .map(|shape| {
+                let material = match shape.emission {
+                    Some([r, g, b]) => Arc::new(Emissive {
+                        radiance: PixelF64::new(r, g, b),
+                    }),
+                    None => materials[shape.material].clone(),
+                };
+                match shape.geometry {
+                    PbrtGeometry::Sphere { center, radius } => Box::new(Shaded {
+                        object: Sphere { center, radius },
+                        material,
+                    })
+                        as Box<dyn Hittable>,
+                    PbrtGeometry::Mesh(mesh) => Box::new(Shaded {
+                        object: mesh,
+                        material,
+                    }),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::import::pbrt::{
+        tokenize, PbrtGeometry, PbrtLightKind, PbrtMaterial, PbrtScene, Token,
+    };
+    use crate::import::Error;
+    use crate::light::Light;
+    use crate::ray::Ray;
+    use crate::scene::Background;
+    use crate::testing;
+    use crate::types::{PixelF64, PositionVec};
+    use std::fs;
+    use std::path::Path;
+
+    const SCENE: &str = r#"
+# a red sphere in front of a mirrored square
+LookAt 0 0 5   0 0 0   0 1 0
+Camera "perspective" "float fov" [ 45 ]
+Film "rgb" "integer xresolution" [ 40 ] "integer yresolution" [ 20 ]
+    "string filename" "spheres.exr"
+Sampler "halton" "integer pixelsamples" 4
+
+WorldBegin
+LightSource "infinite" "rgb L" [ 0.1 0.2 0.3 ]
+LightSource "distant" "point3 from" [0 10 0] "point3 to" [0 0 0] "float scale" 2
+MakeNamedMaterial "red" "string type" "diffuse" "rgb reflectance" [ 0.8 0.1 0.1 ]
+
+AttributeBegin
+  NamedMaterial "red"
+  Translate 1 0 0
+  Scale 2 2 2
+  Shape "sphere" "float radius" 0.5
+AttributeEnd
+
+AttributeBegin
+  Material "conductor" "float roughness" 0.1
+  Translate 0 0 -1
+  Shape "trianglemesh" "point3 P" [ -1 -1 0  1 -1 0  1 1 0  -1 1 0 ]
+      "integer indices" [ 0 1 2  0 2 3 ]
+AttributeEnd
+
+AttributeBegin
+  AreaLightSource "diffuse" "rgb L" [ 4 4 4 ]
+  Material "dielectric" "float eta" 1.33
+  Shape "sphere"
+AttributeEnd
+"#;
+
+    #[test]
+    fn test_parse_scene() {
+        let scene = PbrtScene::parse(SCENE, Path::new("")).expect("parse");
+        assert_eq!((scene.width, scene.height, scene.samples), (40, 20, 4));
+        assert_eq!(scene.output.as_deref(), Some(Path::new("spheres.exr")));
+
+        let camera = &scene.camera;
+        assert_eq!(camera.fov, 45.0);
+        assert!((camera.position - PositionVec::new(0.0, 0.0, 5.0)).norm() < 1e-12);
+        // pbrt images have camera +x to the right, which is world -x looking at -z
+        let right = camera.orientation.column(0);
+        assert!((right - PositionVec::new(-1.0, 0.0, 0.0)).norm() < 1e-12);
+        let forward = -camera.orientation.column(2);
+        assert!((forward - PositionVec::new(0.0, 0.0, -1.0)).norm() < 1e-12);
+
+        assert_eq!(
+            scene.background(),
+            Background::Color(PixelF64::new(0.1, 0.2, 0.3))
+        );
+        assert_eq!(
+            scene.lights[1].kind,
+            PbrtLightKind::Distant {
+                direction: PositionVec::new(0.0, -1.0, 0.0)
+            }
+        );
+        assert_eq!(scene.lights[1].color, [2.0; 3]);
+        assert_eq!(
+            scene.lights(),
+            vec![Light::Distant {
+                direction: PositionVec::new(0.0, -1.0, 0.0),
+                radiance: PixelF64::new(2.0, 2.0, 2.0),
+            }]
+        );
+
+        assert_eq!(scene.shapes.len(), 3);
+        let PbrtGeometry::Sphere { center, radius } = scene.shapes[0].geometry else {
+            panic!("expected a sphere");
+        };
+        assert_eq!((center, radius), (PositionVec::new(1.0, 0.0, 0.0), 1.0));
+        assert_eq!(
+            scene.materials[scene.shapes[0].material],
+            PbrtMaterial::Diffuse {
+                reflectance: [0.8, 0.1, 0.1]
+            }
+        );
+        let PbrtGeometry::Mesh(mesh) = &scene.shapes[1].geometry else {
+            panic!("expected a mesh");
+        };
+        assert_eq!(mesh.positions()[2], PositionVec::new(1.0, 1.0, -1.0));
+        assert!(matches!(
+            scene.materials[scene.shapes[1].material],
+            PbrtMaterial::Conductor { roughness, .. } if roughness == 0.1
+        ));
+        assert_eq!(scene.shapes[2].emission, Some([4.0; 3]));
+        // attributes are restored after blocks
+        assert_eq!(scene.shapes[0].emission, None);
+
+        let objects = scene.into_objects();
+        let ray = Ray {
+            origin: PositionVec::new(0.5, 0.5, 5.0),
+            direction: PositionVec::new(0.0, 0.0, -1.0),
+        };
+        let hit = objects[1].try_hit(&ray, 0.0, f64::INFINITY).expect("hit");
+        assert!((hit.t - 6.0).abs() < 1e-12);
+        assert!(hit.surface_nv.z > 0.0);
+    }
+
+    #[test]
+    fn test_camera_image() {
+        let scene = PbrtScene::parse(SCENE, Path::new("")).expect("parse");
+        let camera = scene.camera.camera(40, 20);
+        // fov applies to the shorter axis
+        let expected = 2.0 * 22.5f64.to_radians().tan() / 20.0;
+        assert!((camera.pixel_height - expected).abs() < 1e-12);
+        assert!((camera.pixel_width - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_plymesh_and_errors() {
+        let source = r#"Shape "plymesh" "string filename" "square.ply""#;
+        let scene = PbrtScene::parse(source, &testing::path("")).expect("parse");
+        let PbrtGeometry::Mesh(mesh) = &scene.shapes[0].geometry else {
+            panic!("expected a mesh");
+        };
+        assert_eq!(mesh.triangle_count(), 2);
+
+        assert_eq!(
+            tokenize("Translate -1 .5 2e1 # comment\n\"a b\" [").unwrap(),
+            vec![
+                (Token::Word("Translate".into()), 1),
+                (Token::Num(-1.0), 1),
+                (Token::Num(0.5), 1),
+                (Token::Num(20.0), 1),
+                (Token::Str("a b".into()), 2),
+                (Token::Open, 2),
+            ]
+        );
+        for (source, line) in [
+            ("WorldBegin\nTranslate 1 2", 2),
+            ("AttributeEnd", 1),
+            ("\nFoo 1", 2),
+            ("Shape \"sphere\" \"float radius\"", 1),
+            (
+                "Film \"rgb\" \"integer xresolution\" [ 100000 ] \"integer yresolution\" [ 100000 ]",
+                1,
+            ),
+        ] {
+            assert!(
+                matches!(PbrtScene::parse(source, Path::new("")), Err(Error::SyntaxError { line: l, .. }) if l == line),
+                "{source}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_recursive_include() {
+        let dir = std::env::temp_dir().join("rrt_ut_test_recursive_include");
+        fs::create_dir_all(&dir).expect("create dir");
+        let write = |name: &str, source: &str| fs::write(dir.join(name), source).expect("write");
+        write("self.pbrt", "Include \"self.pbrt\"");
+        write("a.pbrt", "Translate 1 0 0\nInclude \"b.pbrt\"");
+        write("b.pbrt", "Include \"a.pbrt\"");
+        // the same file may be included more than once, as long as it does not include itself
+        write(
+            "twice.pbrt",
+            "Include \"shape.pbrt\"\nInclude \"shape.pbrt\"",
+        );
+        write("shape.pbrt", "Shape \"sphere\"");
+        for name in ["self.pbrt", "a.pbrt", "b.pbrt"] {
+            let scene = PbrtScene::load(&dir.join(name));
+            assert!(matches!(scene, Err(Error::SyntaxError { .. })), "{name}");
+        }
+        let scene = PbrtScene::parse("Include \"a.pbrt\"", &dir);
+        assert!(matches!(scene, Err(Error::SyntaxError { .. })));
+        let scene = PbrtScene::load(&dir.join("twice.pbrt")).expect("load");
+        assert_eq!(scene.shapes.len(), 2);
+        fs::remove_dir_all(&dir).expect("clean up");
+    }
+}
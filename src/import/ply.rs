@@ -0,0 +1,454 @@
+//! Stanford PLY meshes, in ASCII and binary formats.
+//!
+//! Vertex positions, normals, colors and texture coordinates are read from the `vertex`
+//! element and polygons from the `face` element. Other elements are skipped.
+
+use crate::import::{triangulate, Error};
+use crate::objects::mesh::TriangleMesh;
+use crate::types::{NumColorRatio, PixelF64, PositionVec};
+use std::fs;
+use std::path::Path;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(s: &str) -> Option<Scalar> {
+        Some(match s {
+            "char" | "int8" => Scalar::I8,
+            "uchar" | "uint8" => Scalar::U8,
+            "short" | "int16" => Scalar::I16,
+            "ushort" | "uint16" => Scalar::U16,
+            "int" | "int32" => Scalar::I32,
+            "uint" | "uint32" => Scalar::U32,
+            "float" | "float32" => Scalar::F32,
+            "double" | "float64" => Scalar::F64,
+            _ => return None,
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    /// The value standing for full intensity of a color channel of this type.
+    fn color_max(self) -> NumColorRatio {
+        match self {
+            Scalar::I8 => i8::MAX as NumColorRatio,
+            Scalar::U8 => u8::MAX as NumColorRatio,
+            Scalar::I16 => i16::MAX as NumColorRatio,
+            Scalar::U16 => u16::MAX as NumColorRatio,
+            Scalar::I32 => i32::MAX as NumColorRatio,
+            Scalar::U32 => u32::MAX as NumColorRatio,
+            Scalar::F32 | Scalar::F64 => 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum PropertyType {
+    Scalar(Scalar),
+    List { count: Scalar, item: Scalar },
+}
+
+#[derive(Clone, Debug)]
+struct Property {
+    name: String,
+    kind: PropertyType,
+}
+
+#[derive(Clone, Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+#[derive(Debug)]
+struct Header {
+    format: Format,
+    elements: Vec<Element>,
+}
+
+fn syntax_error(line: usize, message: impl Into<String>) -> Error {
+    Error::SyntaxError {
+        line,
+        message: message.into(),
+    }
+}
+
+/// Parse the header, returning it and the position where the body starts.
+fn parse_header(data: &[u8]) -> Result<(Header, usize), Error> {
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    let mut pos = 0;
+    let mut line_number = 0;
+    loop {
+        let end = data[pos..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map(|i| pos + i)
+            .ok_or_else(|| syntax_error(line_number + 1, "header without `end_header`"))?;
+        let line = std::str::from_utf8(&data[pos..end])
+            .map_err(|_| syntax_error(line_number + 1, "header is not valid text"))?;
+        pos = end + 1;
+        line_number += 1;
+        let mut words = line.split_whitespace();
+        let keyword = words.next();
+        if line_number == 1 {
+            if keyword != Some("ply") {
+                return Err(syntax_error(1, "not a PLY file"));
+            }
+            continue;
+        }
+        let args: Vec<&str> = words.collect();
+        let error = |message: &str| syntax_error(line_number, message);
+        match keyword {
+            None | Some("comment") | Some("obj_info") => {}
+            Some("format") => {
+                format = Some(match args.first().copied() {
+                    Some("ascii") => Format::Ascii,
+                    Some("binary_little_endian") => Format::BinaryLittleEndian,
+                    Some("binary_big_endian") => Format::BinaryBigEndian,
+                    _ => return Err(error("unknown format")),
+                });
+            }
+            Some("element") => {
+                let [name, count] = args[..] else {
+                    return Err(error("expected element name and count"));
+                };
+                elements.push(Element {
+                    name: name.to_string(),
+                    count: count.parse().map_err(|_| error("invalid element count"))?,
+                    properties: Vec::new(),
+                });
+            }
+            Some("property") => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| error("property before any element"))?;
+                let scalar =
+                    |s: &str| Scalar::parse(s).ok_or_else(|| error("unknown property type"));
+                let (kind, name) = match args[..] {
+                    ["list", count, item, name] => (
+                        PropertyType::List {
+                            count: scalar(count)?,
+                            item: scalar(item)?,
+                        },
+                        name,
+                    ),
+                    [kind, name] => (PropertyType::Scalar(scalar(kind)?), name),
+                    _ => return Err(error("expected property type and name")),
+                };
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    kind,
+                });
+            }
+            Some("end_header") => break,
+            Some(keyword) => return Err(error(&format!("unknown keyword `{keyword}`"))),
+        }
+    }
+    let format = format.ok_or_else(|| syntax_error(line_number, "header without format"))?;
+    Ok((Header { format, elements }, pos))
+}
+
+/// Reads values of the body one by one.
+struct BodyReader<'a> {
+    format: Format,
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl BodyReader<'_> {
+    fn read(&mut self, scalar: Scalar) -> Result<f64, Error> {
+        if self.format == Format::Ascii {
+            return self.read_ascii();
+        }
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + scalar.size())
+            .ok_or_else(|| Error::InvalidData("unexpected end of file".into()))?;
+        self.pos += scalar.size();
+        let mut buf = [0u8; 8];
+        buf[..bytes.len()].copy_from_slice(bytes);
+        if self.format == Format::BinaryBigEndian {
+            buf[..bytes.len()].reverse();
+        }
+        Ok(match scalar {
+            Scalar::I8 => buf[0] as i8 as f64,
+            Scalar::U8 => buf[0] as f64,
+            Scalar::I16 => i16::from_le_bytes([buf[0], buf[1]]) as f64,
+            Scalar::U16 => u16::from_le_bytes([buf[0], buf[1]]) as f64,
+            Scalar::I32 => i32::from_le_bytes(buf[..4].try_into().unwrap()) as f64,
+            Scalar::U32 => u32::from_le_bytes(buf[..4].try_into().unwrap()) as f64,
+            Scalar::F32 => f32::from_le_bytes(buf[..4].try_into().unwrap()) as f64,
+            Scalar::F64 => f64::from_le_bytes(buf),
+        })
+    }
+
+    fn read_ascii(&mut self) -> Result<f64, Error> {
+        let rest = &self.data[self.pos..];
+        let start = rest
+            .iter()
+            .position(|b| !b.is_ascii_whitespace())
+            .ok_or_else(|| Error::InvalidData("unexpected end of file".into()))?;
+        let len = rest[start..]
+            .iter()
+            .position(|b| b.is_ascii_whitespace())
+            .unwrap_or(rest.len() - start);
+        self.pos += start + len;
+        std::str::from_utf8(&rest[start..start + len])
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| {
+                Error::InvalidData(format!(
+                    "invalid number `{}`",
+                    String::from_utf8_lossy(&rest[start..start + len])
+                ))
+            })
+    }
+}
+
+/// Find a scalar property by one of its common names.
+fn find(element: &Element, names: &[&str]) -> Option<(usize, Scalar)> {
+    element
+        .properties
+        .iter()
+        .enumerate()
+        .find_map(|(i, p)| match p.kind {
+            PropertyType::Scalar(s) if names.contains(&p.name.as_str()) => Some((i, s)),
+            _ => None,
+        })
+}
+
+/// Parse the content of a PLY file. Polygons are split into triangles.
+pub fn parse(data: &[u8]) -> Result<TriangleMesh, Error> {
+    let (header, body) = parse_header(data)?;
+    let mut reader = BodyReader {
+        format: header.format,
+        data,
+        pos: body,
+    };
+    let mut mesh = TriangleMesh::default();
+    let mut faces: Vec<Vec<u32>> = Vec::new();
+    for element in &header.elements {
+        let position = ["x", "y", "z"].map(|n| find(element, &[n]));
+        let normal = ["nx", "ny", "nz"].map(|n| find(element, &[n]));
+        let color = [
+            &["red", "r", "diffuse_red"][..],
+            &["green", "g", "diffuse_green"],
+            &["blue", "b", "diffuse_blue"],
+        ]
+        .map(|n| find(element, n));
+        let uv = [
+            &["u", "s", "texture_u", "texture_s"][..],
+            &["v", "t", "texture_v", "texture_t"],
+        ]
+        .map(|n| find(element, n));
+        let face_list = element
+            .properties
+            .iter()
+            .position(|p| p.name == "vertex_indices" || p.name == "vertex_index");
+        let is_vertex = element.name == "vertex";
+        let is_face = element.name == "face";
+        if is_vertex && position.iter().any(Option::is_none) {
+            return Err(Error::InvalidData("vertex without x, y or z".into()));
+        }
+        if is_face && face_list.is_none() {
+            return Err(Error::InvalidData("face without vertex_indices".into()));
+        }
+
+        let mut scalars = vec![0.0; element.properties.len()];
+        for _ in 0..element.count {
+            for (i, property) in element.properties.iter().enumerate() {
+                match property.kind {
+                    PropertyType::Scalar(s) => scalars[i] = reader.read(s)?,
+                    PropertyType::List { count, item } => {
+                        let n = reader.read(count)? as usize;
+                        let items = (0..n)
+                            .map(|_| reader.read(item))
+                            .collect::<Result<Vec<_>, _>>()?;
+                        if is_face && Some(i) == face_list {
+                            faces.push(items.into_iter().map(|v| v as u32).collect());
+                        }
+                    }
+                }
+            }
+            if !is_vertex {
+                continue;
+            }
+            let get = |p: Option<(usize, Scalar)>| p.map(|(i, _)| scalars[i]);
+            let vector = |v: [Option<(usize, Scalar)>; 3]| {
+                Some(PositionVec::new(get(v[0])?, get(v[1])?, get(v[2])?))
+            };
+            mesh.positions_mut().push(vector(position).unwrap());
+            if let Some(n) = vector(normal) {
+                mesh.normals_mut().push(n.try_normalize(0.0).unwrap_or(n));
+            }
+            if let [Some(r), Some(g), Some(b)] = color {
+                let channel = |(i, s): (usize, Scalar)| scalars[i] / s.color_max();
+                mesh.colors_mut()
+                    .push(PixelF64::new(channel(r), channel(g), channel(b)));
+            }
+            if let [Some(u), Some(v)] = uv {
+                mesh.uvs_mut().push([scalars[u.0], scalars[v.0]]);
+            }
+        }
+    }
+
+    let vertex_count = mesh.positions().len();
+    for face in faces {
+        if face.len() < 3 {
+            return Err(Error::InvalidData(
+                "a face needs at least 3 vertices".into(),
+            ));
+        }
+        if let Some(v) = face.iter().find(|&&v| v as usize >= vertex_count) {
+            return Err(Error::InvalidData(format!("vertex index {v} out of range")));
+        }
+        let corners: Vec<PositionVec> =
+            face.iter().map(|&v| mesh.positions()[v as usize]).collect();
+        for [a, b, c] in triangulate(&corners) {
+            mesh.indices_mut().push([face[a], face[b], face[c]]);
+        }
+    }
+    Ok(mesh)
+}
+
+pub fn load(path: &Path) -> Result<TriangleMesh, Error> {
+    parse(&fs::read(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::import::ply::parse;
+    use crate::import::Error;
+    use crate::types::{PixelF64, PositionVec};
+
+    /// A unit square in the xy plane with colored corners, as one quad.
+    const ASCII: &str = "ply
+format ascii 1.0
+comment made by hand
+element vertex 4
+property float x
+property float y
+property float z
+property float nx
+property float ny
+property float nz
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 0 0 2 255 0 0
+1 0 0 0 0 2 0 255 0
+1 1 0 0 0 2 0 0 255
+0 1 0 0 0 2 255 255 255
+4 0 1 2 3
+";
+
+    fn check_square(mesh: &crate::objects::mesh::TriangleMesh) {
+        assert_eq!(mesh.positions().len(), 4);
+        assert_eq!(mesh.positions()[2], PositionVec::new(1.0, 1.0, 0.0));
+        assert_eq!(mesh.normals()[0], PositionVec::new(0.0, 0.0, 1.0));
+        assert_eq!(mesh.colors()[1], PixelF64::new(0.0, 1.0, 0.0));
+        assert_eq!(mesh.triangle_count(), 2);
+        for i in 0..2 {
+            let [a, b, c] = mesh.vertices(i);
+            // counter-clockwise seen from +z
+            assert!((b - a).cross(&(c - a)).z > 0.0);
+        }
+    }
+
+    /// Convert the ASCII square to a binary file.
+    fn binary(big_endian: bool) -> Vec<u8> {
+        let format = if big_endian {
+            "binary_big_endian"
+        } else {
+            "binary_little_endian"
+        };
+        let (header, body) = ASCII.split_once("end_header\n").unwrap();
+        let mut data = header.replace("ascii", format).into_bytes();
+        data.extend(b"end_header\n");
+        let lines: Vec<&str> = body.lines().collect();
+        for line in &lines[..4] {
+            let values: Vec<f64> = line.split(' ').map(|v| v.parse().unwrap()).collect();
+            for &v in &values[..6] {
+                let bytes = if big_endian {
+                    (v as f32).to_be_bytes()
+                } else {
+                    (v as f32).to_le_bytes()
+                };
+                data.extend(bytes);
+            }
+            data.extend(values[6..].iter().map(|&v| v as u8));
+        }
+        data.push(4);
+        for v in 0..4i32 {
+            data.extend(if big_endian {
+                v.to_be_bytes()
+            } else {
+                v.to_le_bytes()
+            });
+        }
+        data
+    }
+
+    #[test]
+    fn test_parse_ascii() {
+        check_square(&parse(ASCII.as_bytes()).expect("parse"));
+    }
+
+    #[test]
+    fn test_parse_binary() {
+        check_square(&parse(&binary(false)).expect("parse little endian"));
+        check_square(&parse(&binary(true)).expect("parse big endian"));
+    }
+
+    #[test]
+    fn test_invalid_file() {
+        assert!(matches!(
+            parse(b"ply\nformat ascii 1.0\nelement vertex 1\nproperty foo x\nend_header\n"),
+            Err(Error::SyntaxError { line: 4, .. })
+        ));
+        let data = binary(false);
+        assert!(matches!(
+            parse(&data[..data.len() - 10]),
+            Err(Error::InvalidData(_))
+        ));
+        let out_of_range = ASCII.replace("4 0 1 2 3", "3 0 1 7");
+        assert!(matches!(
+            parse(out_of_range.as_bytes()),
+            Err(Error::InvalidData(_))
+        ));
+        let empty_face = ASCII.replace("4 0 1 2 3", "0");
+        assert!(matches!(
+            parse(empty_face.as_bytes()),
+            Err(Error::InvalidData(_))
+        ));
+    }
+}
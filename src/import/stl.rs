@@ -0,0 +1,166 @@
+//! STL meshes, in ASCII and binary formats.
+//!
+//! Triangles sharing a vertex position are connected in the resulting mesh.
+//! Facet normals are ignored since they are implied by the vertex order.
+
+use crate::import::Error;
+use crate::objects::mesh::TriangleMesh;
+use crate::types::PositionVec;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Builds an indexed mesh from separate triangles.
+#[derive(Default)]
+struct MeshBuilder {
+    mesh: TriangleMesh,
+    vertices: HashMap<[u64; 3], u32>,
+}
+
+impl MeshBuilder {
+    fn vertex(&mut self, p: PositionVec) -> u32 {
+        let key = [p.x, p.y, p.z].map(|v| (v + 0.0).to_bits());
+        *self.vertices.entry(key).or_insert_with(|| {
+            self.mesh.positions_mut().push(p);
+            (self.mesh.positions().len() - 1) as u32
+        })
+    }
+
+    fn triangle(&mut self, vertices: [PositionVec; 3]) {
+        let indices = vertices.map(|p| self.vertex(p));
+        self.mesh.indices_mut().push(indices);
+    }
+}
+
+fn parse_binary(data: &[u8]) -> Result<TriangleMesh, Error> {
+    let count = u32::from_le_bytes(data[80..84].try_into().unwrap()) as usize;
+    let mut builder = MeshBuilder::default();
+    for i in 0..count {
+        // normal, 3 vertices and 2 bytes of attributes
+        let facet = data
+            .get(84 + i * 50..84 + (i + 1) * 50)
+            .ok_or_else(|| Error::InvalidData(format!("only {i} of {count} facets")))?;
+        let value =
+            |j: usize| f32::from_le_bytes(facet[j * 4..j * 4 + 4].try_into().unwrap()) as f64;
+        let vertex =
+            |k: usize| PositionVec::new(value(3 + k * 3), value(4 + k * 3), value(5 + k * 3));
+        builder.triangle([vertex(0), vertex(1), vertex(2)]);
+    }
+    Ok(builder.mesh)
+}
+
+fn parse_ascii(s: &str) -> Result<TriangleMesh, Error> {
+    let mut builder = MeshBuilder::default();
+    let mut facet: Vec<PositionVec> = Vec::new();
+    for (i, line) in s.lines().enumerate() {
+        let error = |message: String| Error::SyntaxError {
+            line: i + 1,
+            message,
+        };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.first().copied() {
+            Some("vertex") => {
+                let values = words[1..]
+                    .iter()
+                    .map(|v| v.parse::<f64>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| error(format!("invalid vertex: {e}")))?;
+                let [x, y, z] = values[..] else {
+                    return Err(error("expected 3 vertex coordinates".into()));
+                };
+                facet.push(PositionVec::new(x, y, z));
+            }
+            Some("endloop") => {
+                let [a, b, c] = facet[..] else {
+                    return Err(error(format!("facet with {} vertices", facet.len())));
+                };
+                builder.triangle([a, b, c]);
+                facet.clear();
+            }
+            Some("solid" | "facet" | "outer" | "endfacet" | "endsolid") | None => {}
+            Some(keyword) => return Err(error(format!("unknown keyword `{keyword}`"))),
+        }
+    }
+    Ok(builder.mesh)
+}
+
+/// Parse the content of an STL file.
+pub fn parse(data: &[u8]) -> Result<TriangleMesh, Error> {
+    // binary files may also start with "solid", so check the size first
+    let binary_size = data
+        .get(80..84)
+        .map(|b| 84 + 50 * u32::from_le_bytes(b.try_into().unwrap()) as usize);
+    let ascii = data.starts_with(b"solid") && data.iter().all(|&b| b != 0 && b.is_ascii());
+    if binary_size == Some(data.len()) || !ascii {
+        if data.len() < 84 {
+            return Err(Error::InvalidData("truncated STL file".into()));
+        }
+        return parse_binary(data);
+    }
+    parse_ascii(std::str::from_utf8(data).expect("checked to be ASCII"))
+}
+
+pub fn load(path: &Path) -> Result<TriangleMesh, Error> {
+    parse(&fs::read(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::import::stl::parse;
+    use crate::import::Error;
+    use crate::types::PositionVec;
+
+    const ASCII: &str = "solid square
+facet normal 0 0 1
+  outer loop
+    vertex 0 0 0
+    vertex 1 0 0
+    vertex 1 1 0
+  endloop
+endfacet
+facet normal 0 0 1
+  outer loop
+    vertex 0 0 0
+    vertex 1 1 0
+    vertex 0 1 0
+  endloop
+endfacet
+endsolid square
+";
+
+    #[test]
+    fn test_parse_ascii_and_binary() {
+        let mut binary = b"solid but binary".to_vec();
+        binary.resize(80, 0);
+        binary.extend(2u32.to_le_bytes());
+        for facet in [
+            [
+                0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0f32,
+            ],
+            [0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0],
+        ] {
+            binary.extend(facet.iter().flat_map(|v| v.to_le_bytes()));
+            binary.extend([0, 0]);
+        }
+        for data in [ASCII.as_bytes(), &binary] {
+            let mesh = parse(data).expect("parse");
+            // shared vertices are merged
+            assert_eq!(mesh.positions().len(), 4);
+            assert_eq!(mesh.indices(), vec![[0, 1, 2], [0, 2, 3]]);
+            assert_eq!(mesh.positions()[3], PositionVec::new(0.0, 1.0, 0.0));
+        }
+        assert!(matches!(parse(&binary[..120]), Err(Error::InvalidData(_))));
+    }
+
+    #[test]
+    fn test_invalid_ascii() {
+        let s = ASCII.replace(
+            "vertex 1 1 0\n    vertex 0 1 0",
+            "vertex 1 1\n    vertex 0 1 0",
+        );
+        assert!(matches!(
+            parse(s.as_bytes()),
+            Err(Error::SyntaxError { line: 12, .. })
+        ));
+    }
+}
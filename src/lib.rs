@@ -0,0 +1,44 @@
+//! A CPU ray tracer.
+//!
+//! Build a [`Renderer`] from a [`Camera`] and a [`Scene`], render it into an [`Image`]
+//! and save the result with [`output::ImageOutput`].
+
+pub mod aabb;
+pub mod bvh;
+pub mod compare;
+#[cfg(feature = "exr")]
+pub mod exr;
+pub mod framebuffer;
+#[cfg(feature = "hdr")]
+pub mod hdr;
+pub mod import;
+pub mod light;
+pub mod material;
+pub mod objects;
+pub mod output;
+#[cfg(feature = "hdr")]
+pub mod pfm;
+#[cfg(feature = "png")]
+pub mod png;
+pub mod polynomial;
+pub mod ppm;
+pub mod ray;
+pub mod renderer;
+pub mod scene;
+#[cfg(feature = "scene-file")]
+pub mod scenefile;
+#[cfg(test)]
+mod testing;
+pub mod tlas;
+pub mod tonemap;
+pub mod transform;
+pub mod types;
+#[cfg(any(feature = "png", feature = "exr"))]
+pub mod zlib;
+
+pub use objects::sphere::Sphere;
+pub use ppm::Image;
+pub use ray::Ray;
+pub use renderer::{RenderError, Renderer};
+pub use scene::{Bounded, Camera, HitEvent, Hittable, Scene, SkiedWorld};
+pub use types::{Pixel, PixelF64, PixelU8};
@@ -0,0 +1,214 @@
+//! Lights without a surface, like point lights and the sun.
+//!
+//! Rays never hit these lights, so the renderer samples them directly from every surface
+//! reflecting light from all directions, testing whether anything is in between.
+
+use crate::transform::Transform;
+use crate::types::{NumPosition, Pixel, PixelF64, PositionVec};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Light {
+    /// light emitted evenly in all directions from a point
+    Point {
+        position: PositionVec,
+        /// radiant intensity, the radiance arriving at distance 1
+        intensity: PixelF64,
+    },
+    /// a point light shining into a cone, fading out towards its edge
+    Spot {
+        position: PositionVec,
+        /// unit vector along the axis of the cone
+        direction: PositionVec,
+        /// cosine of the half angle of the whole cone
+        cos_outer: NumPosition,
+        /// cosine of the half angle of the inner cone, which is lit at full intensity
+        cos_inner: NumPosition,
+        intensity: PixelF64,
+    },
+    /// parallel light from infinitely far away, like sunlight
+    Distant {
+        /// unit vector where the light travels to
+        direction: PositionVec,
+        radiance: PixelF64,
+    },
+}
+
+/// Light arriving at a point from a [`Light`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LightSample {
+    /// unit vector from the point towards the light
+    pub direction: PositionVec,
+    /// distance to the light, infinite for distant lights
+    pub distance: NumPosition,
+    /// radiance arriving from `direction`
+    pub radiance: PixelF64,
+}
+
+/// Hermite interpolation from 0 at `a` to 1 at `b`.
+fn smoothstep(a: NumPosition, b: NumPosition, x: NumPosition) -> NumPosition {
+    if a == b {
+        return if x < a { 0.0 } else { 1.0 };
+    }
+    let t = ((x - a) / (b - a)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Light from a point light at `position` arriving at `p`, scaled by `falloff`
+/// of the direction it leaves the light in.
+fn point_sample(
+    position: &PositionVec,
+    intensity: &PixelF64,
+    p: &PositionVec,
+    falloff: impl Fn(&PositionVec) -> NumPosition,
+) -> Option<LightSample> {
+    let to_light = position - p;
+    let distance = to_light.norm();
+    let direction = to_light.try_normalize(0.0)?;
+    let mut radiance = *intensity;
+    radiance *= falloff(&-direction) / (distance * distance);
+    Some(LightSample {
+        direction,
+        distance,
+        radiance,
+    })
+}
+
+impl Light {
+    /// Light arriving at point `p`, `None` if it does not reach the point.
+    pub fn sample(&self, p: &PositionVec) -> Option<LightSample> {
+        let sample = match self {
+            Light::Point {
+                position,
+                intensity,
+            } => point_sample(position, intensity, p, |_| 1.0)?,
+            Light::Spot {
+                position,
+                direction,
+                cos_outer,
+                cos_inner,
+                intensity,
+            } => point_sample(position, intensity, p, |d| {
+                smoothstep(*cos_outer, *cos_inner, d.dot(direction))
+            })?,
+            Light::Distant {
+                direction,
+                radiance,
+            } => LightSample {
+                direction: -direction,
+                distance: NumPosition::INFINITY,
+                radiance: *radiance,
+            },
+        };
+        let black = [Pixel::red, Pixel::green, Pixel::blue]
+            .iter()
+            .all(|c| c(&sample.radiance) <= 0.0);
+        (!black).then_some(sample)
+    }
+
+    /// The light moved by `transform`, like the objects of an instance.
+    pub fn transformed(&self, transform: &Transform) -> Light {
+        let direction = |d: &PositionVec| transform.vector(d).normalize();
+        match *self {
+            Light::Point {
+                position,
+                intensity,
+            } => Light::Point {
+                position: transform.point(&position),
+                intensity,
+            },
+            Light::Spot {
+                position,
+                direction: d,
+                cos_outer,
+                cos_inner,
+                intensity,
+            } => Light::Spot {
+                position: transform.point(&position),
+                direction: direction(&d),
+                cos_outer,
+                cos_inner,
+                intensity,
+            },
+            Light::Distant {
+                direction: d,
+                radiance,
+            } => Light::Distant {
+                direction: direction(&d),
+                radiance,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::light::Light;
+    use crate::transform::{axis_angle, Transform};
+    use crate::types::{Pixel, PixelF64, PositionVec};
+
+    #[test]
+    fn test_sample() {
+        let p = PositionVec::new(0.0, 0.0, 0.0);
+        let point = Light::Point {
+            position: PositionVec::new(0.0, 2.0, 0.0),
+            intensity: PixelF64::new(4.0, 8.0, 0.0),
+        };
+        let sample = point.sample(&p).expect("lit");
+        assert_eq!(sample.direction, PositionVec::y());
+        assert_eq!(sample.distance, 2.0);
+        assert_eq!(sample.radiance, PixelF64::new(1.0, 2.0, 0.0));
+
+        let distant = Light::Distant {
+            direction: PositionVec::new(0.0, -1.0, 0.0),
+            radiance: PixelF64::new(3.0, 3.0, 3.0),
+        };
+        let sample = distant.sample(&p).expect("lit");
+        assert_eq!(sample.direction, PositionVec::y());
+        assert!(sample.distance.is_infinite());
+        assert_eq!(sample.radiance.red(), 3.0);
+
+        // pointing down, lit fully below the inner cone of 30 degrees and not beyond 60
+        let spot = |x: f64| {
+            let light = Light::Spot {
+                position: PositionVec::new(0.0, 1.0, 0.0),
+                direction: -PositionVec::y(),
+                cos_outer: 60f64.to_radians().cos(),
+                cos_inner: 30f64.to_radians().cos(),
+                intensity: PixelF64::new(1.0, 1.0, 1.0),
+            };
+            light
+                .sample(&PositionVec::new(x, 0.0, 0.0))
+                .map(|s| s.radiance.red() * (1.0 + x * x))
+        };
+        assert_eq!(spot(0.0), Some(1.0));
+        assert!((spot(0.5).expect("lit") - 1.0).abs() < 1e-12);
+        let edge = spot(1.0).expect("lit");
+        assert!(edge > 0.0 && edge < 1.0, "{edge}");
+        assert_eq!(spot(2.0), None);
+    }
+
+    #[test]
+    fn test_transformed() {
+        let transform = Transform::from_trs(
+            &PositionVec::new(1.0, 0.0, 0.0),
+            &axis_angle(&PositionVec::z(), std::f64::consts::FRAC_PI_2),
+            &PositionVec::new(2.0, 2.0, 2.0),
+        );
+        let point = Light::Point {
+            position: PositionVec::new(1.0, 0.0, 0.0),
+            intensity: PixelF64::new(1.0, 1.0, 1.0),
+        };
+        let Light::Point { position, .. } = point.transformed(&transform) else {
+            panic!("point light expected");
+        };
+        assert!((position - PositionVec::new(1.0, 2.0, 0.0)).norm() < 1e-12);
+        let distant = Light::Distant {
+            direction: PositionVec::new(1.0, 0.0, 0.0),
+            radiance: PixelF64::new(1.0, 1.0, 1.0),
+        };
+        let Light::Distant { direction, .. } = distant.transformed(&transform) else {
+            panic!("distant light expected");
+        };
+        assert!((direction - PositionVec::y()).norm() < 1e-12);
+    }
+}
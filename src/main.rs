@@ -1,24 +1,558 @@
-use crate::objects::sphere::NormalVectorVisualizedSphere;
-use crate::scene::Hittable;
-use crate::types::{PixelF64, PositionVec};
-use tracing::debug;
-
-mod objects;
-mod ppm;
-mod ray;
-mod renderer;
-mod scene;
-mod testing;
-mod types;
-
-fn main() {
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use rrt::compare::{compare, diff_image, Tolerance};
+#[cfg(feature = "gltf")]
+use rrt::import::gltf::GltfScene;
+use rrt::import::pbrt::PbrtScene;
+use rrt::output::ImageOutput;
+use rrt::ppm::{self, ImageFormat};
+use rrt::scene::Integrator;
+use rrt::scenefile::{LoadedScene, RenderSettings, SceneDescription};
+use rrt::tonemap::{srgb_eotf, DisplayTransform, ToneMapping};
+use rrt::{Image, Pixel, PixelF64};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use tracing::{debug, info};
+
+/// A CPU ray tracer and the image tools around it.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Render a scene and save the image
+    Render(RenderArgs),
+    /// Convert an image file to another format
+    Convert(ConvertArgs),
+    /// Compare two images with error metrics
+    Diff(DiffArgs),
+    /// Print information about an image or scene file
+    Info(InfoArgs),
+}
+
+/// Options override settings of the scene file.
+#[derive(Args, Debug)]
+struct RenderArgs {
+    /// scene description, glTF or pbrt file, renders the built-in demo scene if not set
+    scene: Option<PathBuf>,
+    /// image width in pixels [default: 640]
+    #[arg(short = 'W', long, value_parser = clap::value_parser!(u32).range(1..))]
+    width: Option<u32>,
+    /// image height in pixels [default: 480]
+    #[arg(short = 'H', long, value_parser = clap::value_parser!(u32).range(1..))]
+    height: Option<u32>,
+    /// samples per pixel [default: 100]
+    #[arg(short, long, value_parser = clap::value_parser!(u64).range(1..))]
+    samples: Option<u64>,
+    /// output image file [default: result.ppm]
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// output image format, guessed from the output file extension if not set
+    #[arg(short, long, value_parser = parse_format)]
+    format: Option<ImageFormat>,
+    /// number of worker threads, one per CPU core if not set
+    #[arg(short = 'j', long, value_parser = clap::value_parser!(u64).range(1..))]
+    threads: Option<u64>,
+    /// seed of the random sampling pattern, for reproducible images
+    #[arg(long)]
+    seed: Option<u64>,
+    /// how hit points are shaded [default: path]
+    #[arg(short, long, value_enum)]
+    integrator: Option<IntegratorArg>,
+    /// also save the scene with all options applied to this scene file
+    #[arg(long)]
+    save_scene: Option<PathBuf>,
+    #[command(flatten)]
+    display: DisplayArgs,
+}
+
+#[derive(Args, Debug)]
+struct ConvertArgs {
+    input: PathBuf,
+    output: PathBuf,
+    /// output image format, guessed from the output file extension if not set
+    #[arg(short, long, value_parser = parse_format)]
+    format: Option<ImageFormat>,
+    /// applied when converting a high dynamic range image to a low dynamic range one
+    #[command(flatten)]
+    display: DisplayArgs,
+}
+
+#[derive(Args, Debug)]
+struct DiffArgs {
+    expected: PathBuf,
+    actual: PathBuf,
+    /// write a false-color image of per-pixel errors to this file
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// error shown in full red in the diff image
+    #[arg(long, default_value_t = 0.1)]
+    scale: f64,
+    /// fail if mean squared error is greater than this
+    #[arg(long)]
+    max_mse: Option<f64>,
+    /// fail if PSNR in dB is less than this
+    #[arg(long)]
+    min_psnr: Option<f64>,
+    /// fail if SSIM is less than this
+    #[arg(long)]
+    min_ssim: Option<f64>,
+    /// fail if any channel of any pixel differs more than this
+    #[arg(long)]
+    max_error: Option<f64>,
+}
+
+#[derive(Args, Debug)]
+struct InfoArgs {
+    path: PathBuf,
+}
+
+#[derive(Args, Debug)]
+struct DisplayArgs {
+    /// exposure adjustment in stops [default: 0]
+    #[arg(long, allow_negative_numbers = true)]
+    exposure: Option<f64>,
+    /// tone mapping operator [default: clamp]
+    #[arg(long, value_enum)]
+    tone_mapping: Option<ToneMappingArg>,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum IntegratorArg {
+    Path,
+    Normal,
+    Depth,
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum ToneMappingArg {
+    Clamp,
+    Reinhard,
+    Aces,
+    Agx,
+}
+
+impl From<IntegratorArg> for Integrator {
+    fn from(value: IntegratorArg) -> Self {
+        match value {
+            IntegratorArg::Path => Integrator::Path,
+            IntegratorArg::Normal => Integrator::Normal,
+            IntegratorArg::Depth => Integrator::Depth,
+        }
+    }
+}
+
+impl From<ToneMappingArg> for ToneMapping {
+    fn from(value: ToneMappingArg) -> Self {
+        match value {
+            ToneMappingArg::Clamp => ToneMapping::Clamp,
+            ToneMappingArg::Reinhard => ToneMapping::Reinhard,
+            ToneMappingArg::Aces => ToneMapping::AcesFilmic,
+            ToneMappingArg::Agx => ToneMapping::AgX,
+        }
+    }
+}
+
+impl DisplayArgs {
+    fn apply(&self, display: &mut DisplayTransform) {
+        if let Some(exposure) = self.exposure {
+            display.exposure = exposure;
+        }
+        if let Some(tone_mapping) = self.tone_mapping {
+            display.tone_mapping = tone_mapping.into();
+        }
+    }
+}
+
+fn parse_format(s: &str) -> Result<ImageFormat, String> {
+    ImageFormat::from_extension(s).ok_or_else(|| format!("unsupported image format `{s}`"))
+}
+
+fn load_image(path: &Path) -> Result<Image<PixelF64>, Box<dyn Error>> {
+    Image::import(path).map_err(|e| format!("can not load {}: {e}", path.display()).into())
+}
+
+/// Check the file extension, ignoring case.
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| extensions.iter().any(|e| ext.eq_ignore_ascii_case(e)))
+}
+
+fn render(args: RenderArgs) -> Result<(), Box<dyn Error>> {
+    if let Some(path) = args
+        .scene
+        .as_deref()
+        .filter(|path| has_extension(path, &["pbrt"]))
+    {
+        return render_pbrt(path, &args);
+    }
+    #[cfg(feature = "gltf")]
+    if let Some(path) = args
+        .scene
+        .as_deref()
+        .filter(|path| has_extension(path, &["gltf", "glb"]))
+    {
+        return render_gltf(path, &args);
+    }
+    let mut desc = match &args.scene {
+        Some(path) => SceneDescription::load(path)
+            .map_err(|e| format!("can not load {}: {e}", path.display()))?,
+        None => SceneDescription::demo(),
+    };
+    let output = apply_render_args(&args, &mut desc.render)?;
+
+    // mesh files are next to the scene file
+    let dir = args
+        .scene
+        .as_deref()
+        .and_then(Path::parent)
+        .unwrap_or(Path::new(""));
+    let scene = desc.build_relative_to(dir)?;
+    save_scene(&desc, dir, &args)?;
+    render_scene(&scene, &output)
+}
+
+#[cfg(feature = "gltf")]
+fn render_gltf(path: &Path, args: &RenderArgs) -> Result<(), Box<dyn Error>> {
+    let gltf =
+        GltfScene::load(path).map_err(|e| format!("can not load {}: {e}", path.display()))?;
+    let mut desc = SceneDescription::from_gltf(path, &gltf);
+    let output = apply_render_args(args, &mut desc.render)?;
+    let scene = desc.build_gltf(gltf)?;
+    save_scene(&desc, path.parent().unwrap_or(Path::new("")), args)?;
+    render_scene(&scene, &output)
+}
+
+/// Save the scene if requested by `--save-scene`, with paths of mesh files relative to `dir`.
+fn save_scene(
+    desc: &SceneDescription,
+    dir: &Path,
+    args: &RenderArgs,
+) -> Result<(), Box<dyn Error>> {
+    if let Some(path) = &args.save_scene {
+        desc.save_relative_to(path, dir)
+            .map_err(|e| format!("can not save {}: {e}", path.display()))?;
+    }
+    Ok(())
+}
+
+fn render_pbrt(path: &Path, args: &RenderArgs) -> Result<(), Box<dyn Error>> {
+    if args.save_scene.is_some() {
+        return Err("scenes imported from pbrt files can not be saved".into());
+    }
+    let pbrt =
+        PbrtScene::load(path).map_err(|e| format!("can not load {}: {e}", path.display()))?;
+    let mut settings = RenderSettings {
+        width: pbrt.width,
+        height: pbrt.height,
+        samples: pbrt.samples,
+        // pbrt writes images next to the scene file
+        output: pbrt
+            .output
+            .as_ref()
+            .map(|p| path.parent().unwrap_or(Path::new("")).join(p)),
+        ..RenderSettings::default()
+    };
+    let output = apply_render_args(args, &mut settings)?;
+    let scene = LoadedScene {
+        camera: pbrt.camera.camera(settings.width, settings.height),
+        background: pbrt.background(),
+        lights: pbrt.lights(),
+        objects: pbrt.into_objects(),
+        settings,
+    };
+    render_scene(&scene, &output)
+}
+
+/// Override render settings with command-line options, returning where to save the image.
+fn apply_render_args(
+    args: &RenderArgs,
+    settings: &mut RenderSettings,
+) -> Result<ImageOutput, Box<dyn Error>> {
+    settings.width = args.width.unwrap_or(settings.width);
+    settings.height = args.height.unwrap_or(settings.height);
+    ppm::check_size(settings.width, settings.height)?;
+    settings.samples = args.samples.map_or(settings.samples, |v| v as usize);
+    settings.threads = args.threads.map(|v| v as usize).or(settings.threads);
+    settings.seed = args.seed.or(settings.seed);
+    if let Some(integrator) = args.integrator {
+        settings.integrator = integrator.into();
+    }
+    let mut display = settings.get_display_transform();
+    args.display.apply(&mut display);
+    settings.exposure = display.exposure;
+    settings.tone_mapping = display.tone_mapping;
+    let path = args
+        .output
+        .clone()
+        .or(settings.output.take())
+        .unwrap_or_else(|| PathBuf::from("result.ppm"));
+    settings.output = Some(path.clone());
+    let output = ImageOutput {
+        format: args.format,
+        ..ImageOutput::new(path)
+    };
+    // fail before rendering if the image can not be saved
+    output.get_format()?;
+    Ok(output)
+}
+
+fn render_scene(scene: &LoadedScene, output: &ImageOutput) -> Result<(), Box<dyn Error>> {
+    let renderer = scene.renderer();
+    let image = renderer.render_linear(scene.settings.samples)?;
+    let path = output.write(&image, renderer.get_display_transform())?;
+    info!("Image saved to {}", path.display());
+    Ok(())
+}
+
+fn convert(args: ConvertArgs) -> Result<(), Box<dyn Error>> {
+    let image = load_image(&args.input)?;
+    let output = ImageOutput {
+        format: args.format,
+        ..ImageOutput::new(args.output)
+    };
+    let input_hdr = ImageFormat::from_path(&args.input).is_some_and(|f| f.is_high_dynamic_range());
+    let display = if input_hdr {
+        let mut display = DisplayTransform::default();
+        args.display.apply(&mut display);
+        display
+    } else {
+        // display values are stored as they are
+        DisplayTransform {
+            srgb: false,
+            ..DisplayTransform::default()
+        }
+    };
+    let image = if !input_hdr && output.get_format()?.is_high_dynamic_range() {
+        // high dynamic range formats store linear values
+        image.map(|p| {
+            PixelF64::new(
+                srgb_eotf(p.red()),
+                srgb_eotf(p.green()),
+                srgb_eotf(p.blue()),
+            )
+        })
+    } else {
+        image
+    };
+    output.write(&image, &display)?;
+    Ok(())
+}
+
+fn diff(args: DiffArgs) -> Result<(), Box<dyn Error>> {
+    let expected = load_image(&args.expected)?;
+    let actual = load_image(&args.actual)?;
+    let metrics = compare(&expected, &actual)?;
+    println!("MSE:       {}", metrics.mse);
+    println!("PSNR:      {} dB", metrics.psnr);
+    println!("SSIM:      {}", metrics.ssim);
+    println!("max error: {}", metrics.max_error);
+    if let Some(path) = &args.output {
+        diff_image(&expected, &actual, args.scale)?.export(path)?;
+    }
+    let tolerance = Tolerance {
+        max_mse: args.max_mse,
+        min_psnr: args.min_psnr,
+        min_ssim: args.min_ssim,
+        max_error: args.max_error,
+    };
+    if !metrics.within(&tolerance) {
+        return Err("images differ beyond tolerance".into());
+    }
+    Ok(())
+}
+
+type ChannelGetter = fn(&PixelF64) -> f64;
+
+fn scene_info(path: &Path) -> Result<(), Box<dyn Error>> {
+    let desc = SceneDescription::load(path)
+        .map_err(|e| format!("can not load {}: {e}", path.display()))?;
+    // report invalid values as well
+    desc.build_relative_to(path.parent().unwrap_or(Path::new("")))?;
+    let settings = &desc.render;
+    println!("size:       {}x{}", settings.width, settings.height);
+    println!("samples:    {}", settings.samples);
+    println!("integrator: {:?}", settings.integrator);
+    println!(
+        "camera:     at {:?} looking at {:?}, fov {}",
+        desc.camera.position, desc.camera.look_at, desc.camera.fov
+    );
+    println!("background: {:?}", desc.background);
+    println!("materials:  {}", desc.materials.len());
+    println!("objects:    {}", desc.objects.len());
+    Ok(())
+}
+
+fn info(args: InfoArgs) -> Result<(), Box<dyn Error>> {
+    if has_extension(&args.path, &["toml"]) {
+        return scene_info(&args.path);
+    }
+    let image = load_image(&args.path)?;
+    let format = ImageFormat::from_path(&args.path).expect("loaded image has a known format");
+    println!("format: {format:?}");
+    println!("size:   {}x{}", image.get_width(), image.get_height());
+    let getters: [(&str, ChannelGetter); 3] = [
+        ("red", PixelF64::red),
+        ("green", PixelF64::green),
+        ("blue", PixelF64::blue),
+    ];
+    let n = (image.get_width() * image.get_height()) as f64;
+    for (name, f) in getters {
+        let (min, max, sum) = image.iter().map(|(_, _, p)| f(p)).fold(
+            (f64::INFINITY, f64::NEG_INFINITY, 0.0),
+            |(min, max, sum), v| (min.min(v), max.max(v), sum + v),
+        );
+        println!("{name:<6}  min {min:.6}  max {max:.6}  mean {:.6}", sum / n);
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
     tracing_subscriber::fmt::init();
     debug!("Debug logging is enabled");
-    let sphere = NormalVectorVisualizedSphere {
-        center: PositionVec::new(0.0, 0.0, -1.0),
-        radius: 0.5,
+    let result = match Cli::parse().command {
+        Command::Render(args) => render(args),
+        Command::Convert(args) => convert(args),
+        Command::Diff(args) => diff(args),
+        Command::Info(args) => info(args),
     };
-    let objects: Vec<&dyn Hittable<PixelF64>> = vec![&sphere];
-    let renderer = renderer::new_skied_world(objects);
-    renderer.render(100);
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// the tests convert between all image formats
+#[cfg(all(test, feature = "png", feature = "hdr", feature = "exr"))]
+mod tests {
+    use crate::{convert, diff, info, ConvertArgs, DiffArgs, DisplayArgs, InfoArgs};
+    use rrt::tonemap::srgb_eotf;
+    use rrt::{Image, Pixel, PixelF64};
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rrt_ut_{name}"));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("create temp dir");
+        dir
+    }
+
+    fn convert_args(input: PathBuf, output: PathBuf) -> ConvertArgs {
+        ConvertArgs {
+            input,
+            output,
+            format: None,
+            display: DisplayArgs {
+                exposure: None,
+                tone_mapping: None,
+            },
+        }
+    }
+
+    fn diff_args(expected: PathBuf, actual: PathBuf) -> DiffArgs {
+        DiffArgs {
+            expected,
+            actual,
+            output: None,
+            scale: 0.1,
+            max_mse: None,
+            min_psnr: None,
+            min_ssim: None,
+            max_error: None,
+        }
+    }
+
+    #[test]
+    fn test_convert() {
+        let dir = temp_dir("test_convert");
+        let mut image: Image<PixelF64> = Image::new(3, 2);
+        for (x, y, pix) in image.iter_mut() {
+            *pix = PixelF64::new(x as f64 / 2.0, y as f64, 0.2);
+        }
+        let png = dir.join("in.png");
+        image.export(&png).expect("save png");
+
+        // low dynamic range values are kept as they are
+        let ppm = dir.join("out.ppm");
+        convert(convert_args(png.clone(), ppm.clone())).expect("convert to ppm");
+        assert!(diff(diff_args(png.clone(), ppm)).is_ok());
+
+        // and linearized for high dynamic range formats
+        for name in ["out.exr", "out.pfm"] {
+            let path = dir.join(name);
+            convert(convert_args(png.clone(), path.clone())).expect("convert to hdr");
+            let linear: Image<PixelF64> = Image::import(&path).expect("load hdr");
+            let expected = srgb_eotf((0.5f64 * 255.0).round() / 255.0);
+            assert!((linear.get_pixel(1, 0).red() - expected).abs() < 1e-6);
+            assert_eq!(linear.get_pixel(2, 1).green(), 1.0);
+        }
+
+        // high dynamic range input is tone mapped for display
+        let mut bright: Image<PixelF64> = Image::new(1, 1);
+        bright.set_pixel(0, 0, PixelF64::new(4.0, 0.0, 0.0));
+        let exr = dir.join("bright.exr");
+        bright.export(&exr).expect("save exr");
+        let out = dir.join("bright.png");
+        convert(convert_args(exr, out.clone())).expect("convert to png");
+        let display: Image<PixelF64> = Image::import(&out).expect("load png");
+        assert_eq!(display.get_pixel(0, 0), PixelF64::new(1.0, 0.0, 0.0));
+
+        assert!(convert(convert_args(dir.join("in.xyz"), dir.join("out.png"))).is_err());
+    }
+
+    #[test]
+    fn test_diff() {
+        let dir = temp_dir("test_diff");
+        let mut expected: Image<PixelF64> = Image::new(4, 4);
+        for (x, y, pix) in expected.iter_mut() {
+            *pix = PixelF64::new(x as f64 / 4.0, y as f64 / 4.0, 0.5);
+        }
+        let mut actual = expected.map(|p| *p);
+        actual.set_pixel(1, 2, PixelF64::new(1.0, 1.0, 1.0));
+        let (a, b) = (dir.join("expected.exr"), dir.join("actual.exr"));
+        expected.export(&a).expect("save exr");
+        actual.export(&b).expect("save exr");
+
+        assert!(diff(diff_args(a.clone(), a.clone())).is_ok());
+        // without tolerances only the metrics are reported
+        let output = dir.join("diff.png");
+        let mut args = diff_args(a.clone(), b.clone());
+        args.output = Some(output.clone());
+        assert!(diff(args).is_ok());
+        let diff_image: Image<PixelF64> = Image::import(&output).expect("load diff image");
+        assert_eq!(diff_image.get_pixel(1, 2).red(), 1.0);
+        assert_eq!(diff_image.get_pixel(0, 0).red(), 0.0);
+
+        let mut args = diff_args(a.clone(), b.clone());
+        args.max_error = Some(0.1);
+        assert!(diff(args).is_err());
+        let mut args = diff_args(a, b);
+        args.max_error = Some(1.0);
+        assert!(diff(args).is_ok());
+    }
+
+    #[test]
+    fn test_info() {
+        let dir = temp_dir("test_info");
+        let image: Image<PixelF64> = Image::new(2, 2);
+        for name in ["a.png", "a.exr", "a.hdr", "a.pfm", "a.ppm"] {
+            let path = dir.join(name);
+            image.export(&path).expect("save image");
+            assert!(info(InfoArgs { path }).is_ok(), "{name}");
+        }
+        let path = dir.join("broken.png");
+        fs::write(&path, b"not a png").expect("write file");
+        assert!(info(InfoArgs { path }).is_err());
+        let path = dir.join("missing.exr");
+        assert!(info(InfoArgs { path }).is_err());
+    }
 }
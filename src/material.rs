@@ -0,0 +1,294 @@
+//! Materials deciding how surfaces scatter and emit light, independent of their geometry.
+//!
+//! A [`Material`] is attached to an object by wrapping it in [`Shaded`], and reaches the
+//! renderer through [`HitEvent::material`]. The renderer follows scattered rays, multiplying
+//! their radiance by the attenuation of every bounce, and adds emitted radiance on the way.
+//!
+//! [`Shaded`]: crate::objects::shaded::Shaded
+
+use crate::objects::normal_color;
+use crate::ray::Ray;
+use crate::scene::HitEvent;
+use crate::types::{NumColorRatio, NumPosition, Pixel, PixelF64, PositionVec};
+use rand::{Rng, RngCore};
+use std::f64::consts::PI;
+
+/// A ray leaving a surface, and the fraction of its radiance reaching the incoming ray.
+pub struct Scatter {
+    pub ray: Ray,
+    pub attenuation: PixelF64,
+}
+
+/// Colors of materials are radiance and reflectance in double precision, independent of
+/// the pixel type of the image, so lights may be brighter than white.
+pub trait Material: Send + Sync {
+    /// Scatter the incoming `ray` at `hit`, `None` if the light is absorbed.
+    fn scatter(&self, ray: &Ray, hit: &HitEvent, rng: &mut dyn RngCore) -> Option<Scatter>;
+
+    /// Radiance emitted at `hit` towards where the incoming `ray` comes from.
+    fn emitted(&self, _ray: &Ray, _hit: &HitEvent) -> PixelF64 {
+        PixelF64::black()
+    }
+
+    /// Fraction of the radiance arriving at `hit` from unit vector `direction` which is
+    /// reflected to where the incoming `ray` comes from, including the cosine of the angle
+    /// of incidence. `None` if light is only reflected in discrete directions like by a
+    /// mirror, which light from a point never reaches.
+    fn reflected(&self, _ray: &Ray, _hit: &HitEvent, _direction: &PositionVec) -> Option<PixelF64> {
+        None
+    }
+}
+
+/// Whether the ray hits the outer side of the surface, and the shading normal
+/// turned towards the side it comes from.
+fn facing(ray: &Ray, hit: &HitEvent) -> (bool, PositionVec) {
+    let front = ray.direction.dot(&hit.surface_nv) < 0.0;
+    let n = if front {
+        hit.shading_nv
+    } else {
+        -hit.shading_nv
+    };
+    (front, n)
+}
+
+/// The albedo tinted by vertex colors of the surface.
+fn albedo(albedo: &PixelF64, hit: &HitEvent) -> PixelF64 {
+    match &hit.color {
+        Some(color) => albedo.tinted(color),
+        None => *albedo,
+    }
+}
+
+/// Uniformly distributed direction.
+fn random_unit_vector(rng: &mut dyn RngCore) -> PositionVec {
+    loop {
+        let v = PositionVec::from_fn(|_, _| rng.gen_range(-1.0..1.0));
+        let length = v.norm_squared();
+        if length > 1e-12 && length <= 1.0 {
+            return v / length.sqrt();
+        }
+    }
+}
+
+/// Mirror direction `d` at unit normal `n`.
+fn reflect(d: &PositionVec, n: &PositionVec) -> PositionVec {
+    d - 2.0 * d.dot(n) * n
+}
+
+/// A matte surface scattering light evenly in all directions (Lambertian).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Diffuse {
+    /// fraction of light reflected in each channel
+    pub albedo: PixelF64,
+}
+
+impl Material for Diffuse {
+    fn scatter(&self, ray: &Ray, hit: &HitEvent, rng: &mut dyn RngCore) -> Option<Scatter> {
+        let (_, n) = facing(ray, hit);
+        // cosine-weighted around the normal
+        let direction = (n + random_unit_vector(rng))
+            .try_normalize(1e-9)
+            .unwrap_or(n);
+        Some(Scatter {
+            ray: Ray {
+                origin: hit.hit_pos,
+                direction,
+            },
+            attenuation: albedo(&self.albedo, hit),
+        })
+    }
+
+    fn reflected(&self, ray: &Ray, hit: &HitEvent, direction: &PositionVec) -> Option<PixelF64> {
+        let (_, n) = facing(ray, hit);
+        let mut reflected = albedo(&self.albedo, hit);
+        reflected *= n.dot(direction).max(0.0) / PI;
+        Some(reflected)
+    }
+}
+
+/// A reflective surface, a perfect mirror without fuzz.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Metal {
+    /// fraction of light reflected in each channel
+    pub albedo: PixelF64,
+    /// blur of reflections, from 0 for a mirror to 1
+    pub fuzz: NumPosition,
+}
+
+impl Material for Metal {
+    fn scatter(&self, ray: &Ray, hit: &HitEvent, rng: &mut dyn RngCore) -> Option<Scatter> {
+        let (_, n) = facing(ray, hit);
+        let mut direction = reflect(&ray.direction.normalize(), &n);
+        if self.fuzz > 0.0 {
+            direction = (direction + self.fuzz * random_unit_vector(rng)).try_normalize(0.0)?;
+        }
+        if direction.dot(&n) <= 0.0 {
+            // blurred below the surface
+            return None;
+        }
+        Some(Scatter {
+            ray: Ray {
+                origin: hit.hit_pos,
+                direction,
+            },
+            attenuation: albedo(&self.albedo, hit),
+        })
+    }
+}
+
+/// A clear surface which reflects or refracts light, like glass or water.
+/// Surface normals point out of the denser medium.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Glass {
+    /// index of refraction relative to the outside
+    pub ior: NumColorRatio,
+}
+
+impl Material for Glass {
+    fn scatter(&self, ray: &Ray, hit: &HitEvent, rng: &mut dyn RngCore) -> Option<Scatter> {
+        let (front, n) = facing(ray, hit);
+        let eta = if front { 1.0 / self.ior } else { self.ior };
+        let d = ray.direction.normalize();
+        let cos = (-d.dot(&n)).min(1.0);
+        let sin = (1.0 - cos * cos).sqrt();
+        // reflectance by Schlick's approximation
+        let r0 = ((1.0 - eta) / (1.0 + eta)).powi(2);
+        let reflectance = r0 + (1.0 - r0) * (1.0 - cos).powi(5);
+        let direction = if eta * sin > 1.0 || reflectance > rng.gen::<NumColorRatio>() {
+            reflect(&d, &n)
+        } else {
+            let perpendicular = eta * (d + cos * n);
+            perpendicular - (1.0 - perpendicular.norm_squared()).abs().sqrt() * n
+        };
+        Some(Scatter {
+            ray: Ray {
+                origin: hit.hit_pos,
+                direction,
+            },
+            attenuation: PixelF64::new(1.0, 1.0, 1.0),
+        })
+    }
+}
+
+/// A surface emitting light on both sides and absorbing all light, like a lamp.
+/// It also shows a flat color, looking the same under any lighting.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Emissive {
+    pub radiance: PixelF64,
+}
+
+impl Material for Emissive {
+    fn scatter(&self, _ray: &Ray, _hit: &HitEvent, _rng: &mut dyn RngCore) -> Option<Scatter> {
+        None
+    }
+
+    fn emitted(&self, _ray: &Ray, _hit: &HitEvent) -> PixelF64 {
+        self.radiance
+    }
+}
+
+/// Shows the shading normal as color without lighting, for inspecting geometry.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct NormalColor;
+
+impl Material for NormalColor {
+    fn scatter(&self, _ray: &Ray, _hit: &HitEvent, _rng: &mut dyn RngCore) -> Option<Scatter> {
+        None
+    }
+
+    fn emitted(&self, _ray: &Ray, hit: &HitEvent) -> PixelF64 {
+        normal_color(&hit.shading_nv)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::material::{Diffuse, Glass, Material, Metal};
+    use crate::ray::Ray;
+    use crate::scene::HitEvent;
+    use crate::types::{Pixel, PixelF64, PositionVec};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    /// Hit of the ground plane y = 0 at the origin, by a ray coming down along `direction`.
+    fn ground_hit(direction: PositionVec) -> (Ray, HitEvent) {
+        let ray = Ray {
+            origin: -direction,
+            direction,
+        };
+        let hit = HitEvent {
+            hit_pos: PositionVec::zeros(),
+            surface_nv: PositionVec::y(),
+            shading_nv: PositionVec::y(),
+            uv: [0.0, 0.0],
+            t: 1.0,
+            color: None,
+            material: None,
+        };
+        (ray, hit)
+    }
+
+    #[test]
+    fn test_diffuse_and_metal() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let (ray, mut hit) = ground_hit(PositionVec::new(1.0, -1.0, 0.0));
+        let diffuse = Diffuse {
+            albedo: PixelF64::new(0.5, 0.5, 1.0),
+        };
+        for _ in 0..100 {
+            let scatter = diffuse.scatter(&ray, &hit, &mut rng).expect("scattered");
+            assert!(scatter.ray.direction.y >= 0.0);
+            assert_eq!(scatter.attenuation, PixelF64::new(0.5, 0.5, 1.0));
+        }
+        // vertex colors tint the albedo
+        hit.color = Some(PixelF64::new(1.0, 0.5, 0.0));
+        let scatter = diffuse.scatter(&ray, &hit, &mut rng).expect("scattered");
+        assert_eq!(scatter.attenuation, PixelF64::new(0.5, 0.25, 0.0));
+        // seen from below, light scatters downwards
+        let (below, hit) = ground_hit(PositionVec::new(1.0, 1.0, 0.0));
+        let scatter = diffuse.scatter(&below, &hit, &mut rng).expect("scattered");
+        assert!(scatter.ray.direction.y <= 0.0);
+
+        let mut metal = Metal {
+            albedo: PixelF64::new(0.9, 0.9, 0.9),
+            fuzz: 0.0,
+        };
+        let scatter = metal.scatter(&ray, &hit, &mut rng).expect("reflected");
+        let mirrored = PositionVec::new(1.0, 1.0, 0.0).normalize();
+        assert!((scatter.ray.direction - mirrored).norm() < 1e-12);
+        metal.fuzz = 1.0;
+        let (grazing, hit) = ground_hit(PositionVec::new(1.0, -0.01, 0.0));
+        let absorbed = (0..100)
+            .filter(|_| metal.scatter(&grazing, &hit, &mut rng).is_none())
+            .count();
+        assert!(absorbed > 10, "{absorbed}");
+    }
+
+    #[test]
+    fn test_glass() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let glass = Glass { ior: 1.5 };
+        // mostly passing straight through at normal incidence
+        let (ray, hit) = ground_hit(-PositionVec::y());
+        let passed = (0..1000)
+            .map(|_| glass.scatter(&ray, &hit, &mut rng).expect("scattered"))
+            .filter(|s| (s.ray.direction + PositionVec::y()).norm() < 1e-12)
+            .count();
+        assert!((940..=980).contains(&passed), "{passed}");
+        // bent towards the normal entering the glass, by Snell's law
+        let (ray, hit) = ground_hit(PositionVec::new(1.0, -1.0, 0.0));
+        let sin = 0.5f64.sqrt() / 1.5;
+        let refracted = (0..100)
+            .map(|_| glass.scatter(&ray, &hit, &mut rng).expect("scattered"))
+            .find(|s| s.ray.direction.y < 0.0)
+            .expect("refracted");
+        assert!((refracted.ray.direction.x - sin).abs() < 1e-12);
+        assert_eq!(refracted.attenuation.red(), 1.0);
+        // totally reflected leaving the glass at a flat angle
+        let (ray, hit) = ground_hit(PositionVec::new(1.0, 0.5, 0.0));
+        for _ in 0..100 {
+            let scatter = glass.scatter(&ray, &hit, &mut rng).expect("scattered");
+            assert!(scatter.ray.direction.y < 0.0);
+        }
+    }
+}
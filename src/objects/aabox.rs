@@ -0,0 +1,132 @@
+use crate::aabb::Aabb;
+use crate::ray::Ray;
+use crate::scene::{Bounded, HitEvent, Hittable};
+use crate::types::{PositionVec, Time};
+
+/// A solid box with faces perpendicular to the axes.
+pub struct AxisAlignedBox {
+    /// corner with the smallest coordinates
+    pub min: PositionVec,
+    /// corner with the largest coordinates
+    pub max: PositionVec,
+}
+
+impl Hittable for AxisAlignedBox {
+    fn try_hit(&self, ray: &Ray, t1: Time, t2: Time) -> Option<HitEvent> {
+        // intersect the slabs between the faces of each axis,
+        // remembering the axis where the ray enters and leaves the box last and first
+        let (mut t_near, mut near_axis) = (Time::NEG_INFINITY, 0);
+        let (mut t_far, mut far_axis) = (Time::INFINITY, 0);
+        for axis in 0..3 {
+            let inverse = 1.0 / ray.direction[axis];
+            let ta = (self.min[axis] - ray.origin[axis]) * inverse;
+            let tb = (self.max[axis] - ray.origin[axis]) * inverse;
+            if ta.is_nan() || tb.is_nan() {
+                // parallel to the slab and on its boundary
+                continue;
+            }
+            let (ta, tb) = (ta.min(tb), ta.max(tb));
+            if ta > t_near {
+                (t_near, near_axis) = (ta, axis);
+            }
+            if tb < t_far {
+                (t_far, far_axis) = (tb, axis);
+            }
+        }
+        if t_near > t_far {
+            return None;
+        }
+        // try entering the box first, then leaving it from inside
+        let (t, axis) = if (t1..t2).contains(&t_near) {
+            (t_near, near_axis)
+        } else if (t1..t2).contains(&t_far) {
+            (t_far, far_axis)
+        } else {
+            return None;
+        };
+        let hit_pos = ray.at(t);
+        let center = 0.5 * (self.min + self.max);
+        let mut surface_nv = PositionVec::zeros();
+        surface_nv[axis] = if hit_pos[axis] > center[axis] {
+            1.0
+        } else {
+            -1.0
+        };
+        // coordinates of the other axes on the face, from 0 to 1
+        let size = self.max - self.min;
+        let [u, v] = [(axis + 1) % 3, (axis + 2) % 3]
+            .map(|i| ((hit_pos[i] - self.min[i]) / size[i]).clamp(0.0, 1.0));
+        Some(HitEvent {
+            hit_pos,
+            surface_nv,
+            shading_nv: surface_nv,
+            uv: [u, v],
+            t,
+            color: None,
+            material: None,
+        })
+    }
+}
+
+impl Bounded for AxisAlignedBox {
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb {
+            min: self.min,
+            max: self.max,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::objects::aabox::AxisAlignedBox;
+    use crate::ray::Ray;
+    use crate::scene::{HitEvent, Hittable};
+    use crate::types::PositionVec;
+
+    #[test]
+    fn test_hit_box() {
+        let unit = AxisAlignedBox {
+            min: PositionVec::new(-1.0, -1.0, -1.0),
+            max: PositionVec::new(1.0, 1.0, 1.0),
+        };
+        let hit = |origin: PositionVec, direction: PositionVec, t1: f64| -> Option<HitEvent> {
+            unit.try_hit(&Ray { origin, direction }, t1, f64::INFINITY)
+        };
+        let from_right = hit(
+            PositionVec::new(3.0, 0.5, 0.0),
+            PositionVec::new(-1.0, 0.0, 0.0),
+            0.0,
+        )
+        .expect("hit");
+        assert_eq!(from_right.t, 2.0);
+        assert_eq!(from_right.surface_nv, PositionVec::new(1.0, 0.0, 0.0));
+        assert_eq!(from_right.uv, [0.75, 0.5]);
+
+        // normals point outwards when leaving the box too
+        let inside = hit(PositionVec::zeros(), PositionVec::new(0.0, 0.0, -2.0), 0.0).expect("hit");
+        assert_eq!(inside.t, 0.5);
+        assert_eq!(inside.surface_nv, PositionVec::new(0.0, 0.0, -1.0));
+        let behind = hit(
+            PositionVec::new(0.0, 0.0, 3.0),
+            PositionVec::new(0.0, 0.0, -1.0),
+            3.0,
+        )
+        .expect("hit");
+        assert_eq!(behind.t, 4.0);
+        assert_eq!(behind.surface_nv, PositionVec::new(0.0, 0.0, -1.0));
+
+        assert!(hit(
+            PositionVec::new(3.0, 1.5, 0.0),
+            PositionVec::new(-1.0, 0.0, 0.0),
+            0.0
+        )
+        .is_none());
+        assert!(hit(
+            PositionVec::new(3.0, 0.0, 0.0),
+            PositionVec::new(1.0, 0.0, 0.0),
+            0.0
+        )
+        .is_none());
+    }
+}
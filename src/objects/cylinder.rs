@@ -0,0 +1,252 @@
+use crate::aabb::Aabb;
+use crate::objects::{disk_bounds, hit_event, Frame};
+use crate::polynomial::solve_quadratic;
+use crate::ray::Ray;
+use crate::scene::{Bounded, HitEvent, Hittable};
+use crate::types::{NumPosition, PositionVec, Time};
+use std::f64::consts::PI;
+
+/// The earliest hit in time range `t1` <= t < `t2` among the parts of a surface,
+/// with its normal and texture coordinates in local coordinates.
+struct Nearest {
+    t1: Time,
+    t2: Time,
+    hit: Option<(Time, PositionVec, [NumPosition; 2])>,
+}
+
+impl Nearest {
+    fn new(t1: Time, t2: Time) -> Self {
+        Nearest { t1, t2, hit: None }
+    }
+
+    fn consider(&mut self, t: Time, nv: PositionVec, uv: [NumPosition; 2]) {
+        if (self.t1..self.t2).contains(&t) && self.hit.is_none_or(|(nearest, _, _)| t < nearest) {
+            self.hit = Some((t, nv, uv));
+        }
+    }
+}
+
+/// Angle of a local position around the z axis, from 0 to 1.
+fn turn(p: &PositionVec) -> NumPosition {
+    p.y.atan2(p.x).rem_euclid(2.0 * PI) / (2.0 * PI)
+}
+
+/// Consider the hit of a disk cap of given radius at height `z` of the local z axis,
+/// facing towards `nz`.
+fn consider_cap(nearest: &mut Nearest, local: &Ray, z: NumPosition, nz: f64, radius: NumPosition) {
+    if local.direction.z == 0.0 {
+        return;
+    }
+    let t = (z - local.origin.z) / local.direction.z;
+    let p = local.at(t);
+    let r = p.xy().norm();
+    if r <= radius {
+        nearest.consider(t, PositionVec::new(0.0, 0.0, nz), [turn(&p), r / radius]);
+    }
+}
+
+/// A finite circular cylinder, optionally closed by disks at both ends.
+pub struct Cylinder {
+    /// center of the bottom end
+    pub base: PositionVec,
+    /// vector from the center of the bottom end to the center of the top end
+    pub axis: PositionVec,
+    pub radius: NumPosition,
+    pub capped: bool,
+}
+
+impl Hittable for Cylinder {
+    fn try_hit(&self, ray: &Ray, t1: Time, t2: Time) -> Option<HitEvent> {
+        let frame = Frame::new(self.base, &self.axis);
+        let height = self.axis.norm();
+        let local = frame.ray_to_local(ray);
+        let (o, d) = (local.origin, local.direction);
+        let mut nearest = Nearest::new(t1, t2);
+        // infinite tube x^2 + y^2 = r^2, cut to the height of the cylinder
+        let a = d.x * d.x + d.y * d.y;
+        let b = 2.0 * (o.x * d.x + o.y * d.y);
+        let c = o.x * o.x + o.y * o.y - self.radius * self.radius;
+        for t in solve_quadratic(a, b, c) {
+            let p = local.at(t);
+            if (0.0..=height).contains(&p.z) {
+                let nv = PositionVec::new(p.x, p.y, 0.0).normalize();
+                nearest.consider(t, nv, [turn(&p), p.z / height]);
+            }
+        }
+        if self.capped {
+            consider_cap(&mut nearest, &local, 0.0, -1.0, self.radius);
+            consider_cap(&mut nearest, &local, height, 1.0, self.radius);
+        }
+        let (t, nv, uv) = nearest.hit?;
+        Some(hit_event(ray, t, frame.vector_to_world(&nv), uv))
+    }
+}
+
+impl Bounded for Cylinder {
+    fn bounding_box(&self) -> Option<Aabb> {
+        let nv = self.axis.normalize();
+        let bottom = disk_bounds(&self.base, &nv, self.radius);
+        Some(bottom.union(&disk_bounds(&(self.base + self.axis), &nv, self.radius)))
+    }
+}
+
+/// A finite circular cone with its apex at the end of the axis,
+/// optionally closed by a disk at its base.
+pub struct Cone {
+    /// center of the base
+    pub base: PositionVec,
+    /// vector from the center of the base to the apex
+    pub axis: PositionVec,
+    /// radius of the base
+    pub radius: NumPosition,
+    pub capped: bool,
+}
+
+impl Hittable for Cone {
+    fn try_hit(&self, ray: &Ray, t1: Time, t2: Time) -> Option<HitEvent> {
+        let frame = Frame::new(self.base, &self.axis);
+        let height = self.axis.norm();
+        let local = frame.ray_to_local(ray);
+        let (o, d) = (local.origin, local.direction);
+        let mut nearest = Nearest::new(t1, t2);
+        // double cone x^2 + y^2 = k^2 (h - z)^2, cut to the nappe below the apex
+        let k = self.radius / height;
+        let h = height - o.z;
+        let a = d.x * d.x + d.y * d.y - k * k * d.z * d.z;
+        let b = 2.0 * (o.x * d.x + o.y * d.y + k * k * h * d.z);
+        let c = o.x * o.x + o.y * o.y - k * k * h * h;
+        for t in solve_quadratic(a, b, c) {
+            let p = local.at(t);
+            if (0.0..=height).contains(&p.z) {
+                // gradient of the implicit surface, undefined at the apex
+                let nv = PositionVec::new(p.x, p.y, k * p.xy().norm())
+                    .try_normalize(0.0)
+                    .unwrap_or(PositionVec::z());
+                nearest.consider(t, nv, [turn(&p), p.z / height]);
+            }
+        }
+        if self.capped {
+            consider_cap(&mut nearest, &local, 0.0, -1.0, self.radius);
+        }
+        let (t, nv, uv) = nearest.hit?;
+        Some(hit_event(ray, t, frame.vector_to_world(&nv), uv))
+    }
+}
+
+impl Bounded for Cone {
+    fn bounding_box(&self) -> Option<Aabb> {
+        let base = disk_bounds(&self.base, &self.axis.normalize(), self.radius);
+        Some(base.grow(&(self.base + self.axis)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::objects::cylinder::{Cone, Cylinder};
+    use crate::ray::Ray;
+    use crate::scene::{HitEvent, Hittable};
+    use crate::types::PositionVec;
+
+    fn hit(
+        object: &dyn Hittable,
+        origin: PositionVec,
+        direction: PositionVec,
+        t1: f64,
+    ) -> Option<HitEvent> {
+        object.try_hit(&Ray { origin, direction }, t1, f64::INFINITY)
+    }
+
+    fn assert_close(actual: PositionVec, expected: PositionVec) {
+        assert!((actual - expected).norm() < 1e-9, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn test_cylinder() {
+        // standing on the ground, 2 high
+        let mut cylinder = Cylinder {
+            base: PositionVec::zeros(),
+            axis: PositionVec::new(0.0, 2.0, 0.0),
+            radius: 1.0,
+            capped: false,
+        };
+        let side = hit(
+            &cylinder,
+            PositionVec::new(3.0, 1.5, 0.0),
+            PositionVec::new(-1.0, 0.0, 0.0),
+            0.0,
+        )
+        .expect("hit");
+        assert!((side.t - 2.0).abs() < 1e-12);
+        assert_close(side.surface_nv, PositionVec::new(1.0, 0.0, 0.0));
+        assert!((side.uv[1] - 0.75).abs() < 1e-12);
+        // the far side of the tube, seen from inside
+        let inside = hit(
+            &cylinder,
+            PositionVec::new(3.0, 1.5, 0.0),
+            PositionVec::new(-1.0, 0.0, 0.0),
+            2.5,
+        )
+        .expect("hit");
+        assert!((inside.t - 4.0).abs() < 1e-12);
+        assert_close(inside.surface_nv, PositionVec::new(-1.0, 0.0, 0.0));
+        // through the open top and bottom
+        let down = PositionVec::new(0.0, -1.0, 0.0);
+        assert!(hit(&cylinder, PositionVec::new(0.5, 3.0, 0.0), down, 0.0).is_none());
+        assert!(hit(
+            &cylinder,
+            PositionVec::new(0.0, 2.5, 3.0),
+            -PositionVec::z(),
+            0.0
+        )
+        .is_none());
+
+        cylinder.capped = true;
+        let top = hit(&cylinder, PositionVec::new(0.5, 3.0, 0.0), down, 0.0).expect("hit");
+        assert!((top.t - 1.0).abs() < 1e-12);
+        assert_close(top.surface_nv, PositionVec::new(0.0, 1.0, 0.0));
+        assert!((top.uv[1] - 0.5).abs() < 1e-12);
+        let bottom = hit(&cylinder, PositionVec::new(0.5, 3.0, 0.0), down, 1.5).expect("hit");
+        assert!((bottom.t - 3.0).abs() < 1e-12);
+        assert_close(bottom.surface_nv, PositionVec::new(0.0, -1.0, 0.0));
+    }
+
+    #[test]
+    fn test_cone() {
+        // lying along x, apex at x = 2
+        let mut cone = Cone {
+            base: PositionVec::zeros(),
+            axis: PositionVec::new(2.0, 0.0, 0.0),
+            radius: 1.0,
+            capped: false,
+        };
+        let side = hit(
+            &cone,
+            PositionVec::new(1.0, 3.0, 0.0),
+            PositionVec::new(0.0, -1.0, 0.0),
+            0.0,
+        )
+        .expect("hit");
+        // radius is half the base radius halfway to the apex
+        assert!((side.t - 2.5).abs() < 1e-12);
+        assert_close(side.surface_nv, PositionVec::new(0.5, 1.0, 0.0).normalize());
+        assert!((side.uv[1] - 0.5).abs() < 1e-12);
+        // the mirrored nappe beyond the apex is not part of the cone
+        assert!(hit(
+            &cone,
+            PositionVec::new(3.0, 3.0, 0.0),
+            PositionVec::new(0.0, -1.0, 0.0),
+            0.0
+        )
+        .is_none());
+        let along = PositionVec::new(1.0, 0.0, 0.0);
+        let open = hit(&cone, PositionVec::new(-1.0, 0.25, 0.0), along, 0.0).expect("hit");
+        assert!((open.t - 2.5).abs() < 1e-12);
+        // seen from inside
+        assert!(open.surface_nv.dot(&along) > 0.0);
+
+        cone.capped = true;
+        let base = hit(&cone, PositionVec::new(-1.0, 0.25, 0.0), along, 0.0).expect("hit");
+        assert!((base.t - 1.0).abs() < 1e-12);
+        assert_close(base.surface_nv, PositionVec::new(-1.0, 0.0, 0.0));
+    }
+}
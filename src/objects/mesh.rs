@@ -0,0 +1,293 @@
+use crate::aabb::Aabb;
+use crate::bvh::BvhTree;
+use crate::objects::triangle::{intersect, Triangle};
+use crate::ray::Ray;
+use crate::scene::{Bounded, HitEvent, Hittable};
+use crate::types::{NumColorRatio, NumPosition, Pixel, PixelF64, PositionVec, Time};
+use std::sync::OnceLock;
+
+/// Triangles sharing vertex buffers, so vertices of adjacent triangles are stored once.
+/// Triangles are front facing when their vertices are in counter-clockwise order.
+#[derive(Clone, Debug, Default)]
+pub struct TriangleMesh {
+    positions: Vec<PositionVec>,
+    /// per-vertex normals, empty or as many as `positions`
+    normals: Vec<PositionVec>,
+    /// per-vertex texture coordinates, empty or as many as `positions`
+    uvs: Vec<[NumPosition; 2]>,
+    /// per-vertex colors, empty or as many as `positions`
+    colors: Vec<PixelF64>,
+    /// vertex indices of each triangle
+    indices: Vec<[u32; 3]>,
+    /// hierarchy over the triangles, built when the mesh is first hit or bounded,
+    /// dropped when positions or indices change
+    bvh: OnceLock<BvhTree>,
+}
+
+impl TriangleMesh {
+    /// Triangles given by `indices` into `positions`, without other vertex attributes.
+    pub fn new(positions: Vec<PositionVec>, indices: Vec<[u32; 3]>) -> Self {
+        TriangleMesh {
+            positions,
+            indices,
+            ..Default::default()
+        }
+    }
+
+    pub fn positions(&self) -> &[PositionVec] {
+        &self.positions
+    }
+
+    pub fn normals(&self) -> &[PositionVec] {
+        &self.normals
+    }
+
+    pub fn uvs(&self) -> &[[NumPosition; 2]] {
+        &self.uvs
+    }
+
+    pub fn colors(&self) -> &[PixelF64] {
+        &self.colors
+    }
+
+    pub fn indices(&self) -> &[[u32; 3]] {
+        &self.indices
+    }
+
+    /// Edit the positions, the hierarchy is rebuilt on the next hit.
+    pub fn positions_mut(&mut self) -> &mut Vec<PositionVec> {
+        self.bvh.take();
+        &mut self.positions
+    }
+
+    pub fn normals_mut(&mut self) -> &mut Vec<PositionVec> {
+        &mut self.normals
+    }
+
+    pub fn uvs_mut(&mut self) -> &mut Vec<[NumPosition; 2]> {
+        &mut self.uvs
+    }
+
+    pub fn colors_mut(&mut self) -> &mut Vec<PixelF64> {
+        &mut self.colors
+    }
+
+    /// Edit the triangles, the hierarchy is rebuilt on the next hit.
+    pub fn indices_mut(&mut self) -> &mut Vec<[u32; 3]> {
+        self.bvh.take();
+        &mut self.indices
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len()
+    }
+
+    /// Positions of the vertices of triangle `i`.
+    pub fn vertices(&self, i: usize) -> [PositionVec; 3] {
+        self.indices[i].map(|v| self.positions[v as usize])
+    }
+
+    /// Hierarchy over the triangles, built on first use.
+    fn bvh(&self) -> &BvhTree {
+        self.bvh.get_or_init(|| {
+            let bounds: Vec<Option<Aabb>> = (0..self.triangle_count())
+                .map(|i| Some(Aabb::from_points(&self.vertices(i))))
+                .collect();
+            BvhTree::new(&bounds)
+        })
+    }
+
+    /// Attribute values at the vertices of triangle `i`, if there is one value per position.
+    /// Attributes of another length are ignored instead of indexed out of bounds.
+    fn attribute<T: Copy>(&self, values: &[T], i: usize) -> Option<[T; 3]> {
+        (values.len() == self.positions.len()).then(|| self.indices[i].map(|v| values[v as usize]))
+    }
+
+    /// Triangle `i` with its vertex attributes.
+    pub fn triangle(&self, i: usize) -> Triangle {
+        Triangle {
+            vertices: self.vertices(i),
+            normals: self.attribute(&self.normals, i),
+            uvs: self.attribute(&self.uvs, i),
+        }
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn try_hit(&self, ray: &Ray, t1: Time, t2: Time) -> Option<HitEvent> {
+        let (i, t, barycentric) = self.bvh().traverse(ray, t1, t2, |i, t_max| {
+            let (t, barycentric) = intersect(&self.vertices(i), ray, t1, t_max)?;
+            Some((t, (i, t, barycentric)))
+        })?;
+        let mut hit = self.triangle(i).hit_event(ray, t, barycentric);
+        if let Some(colors) = self.attribute(&self.colors, i) {
+            let mix = |f: fn(&PixelF64) -> NumColorRatio| {
+                (0..3).map(|k| barycentric[k] * f(&colors[k])).sum()
+            };
+            hit.color = Some(PixelF64::new(
+                mix(Pixel::red),
+                mix(Pixel::green),
+                mix(Pixel::blue),
+            ));
+        }
+        Some(hit)
+    }
+}
+
+impl Bounded for TriangleMesh {
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bvh().bounds()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::objects::mesh::TriangleMesh;
+    use crate::ray::Ray;
+    use crate::scene::{Bounded, HitEvent, Hittable};
+    use crate::types::PositionVec;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn test_hit_nearest_triangle() {
+        // two unit squares facing +z, at z = -1 and z = -2
+        let mut mesh = TriangleMesh::default();
+        for z in [-2.0, -1.0] {
+            let base = mesh.positions.len() as u32;
+            mesh.positions.extend([
+                PositionVec::new(-1.0, -1.0, z),
+                PositionVec::new(1.0, -1.0, z),
+                PositionVec::new(1.0, 1.0, z),
+                PositionVec::new(-1.0, 1.0, z),
+            ]);
+            mesh.indices
+                .extend([[base, base + 1, base + 2], [base, base + 2, base + 3]]);
+        }
+        let ray = Ray {
+            origin: PositionVec::zeros(),
+            direction: PositionVec::new(0.1, 0.2, -1.0),
+        };
+        let hit = mesh.try_hit(&ray, 0.0, f64::INFINITY).expect("hit");
+        assert!((hit.t - 1.0).abs() < 1e-12);
+        assert!((hit.surface_nv - PositionVec::new(0.0, 0.0, 1.0)).norm() < 1e-12);
+        let hit = mesh.try_hit(&ray, 1.5, f64::INFINITY).expect("hit");
+        assert!((hit.t - 2.0).abs() < 1e-12);
+        assert!(mesh.try_hit(&ray, 0.0, 1.0).is_none());
+        let miss = Ray {
+            origin: PositionVec::zeros(),
+            direction: PositionVec::new(2.0, 0.0, -1.0),
+        };
+        assert!(mesh.try_hit(&miss, 0.0, f64::INFINITY).is_none());
+
+        // editing the mesh after the first hit rebuilds the hierarchy
+        mesh.indices_mut().truncate(2);
+        let hit = mesh.try_hit(&ray, 0.0, f64::INFINITY).expect("hit");
+        assert!((hit.t - 2.0).abs() < 1e-12);
+        for p in mesh.positions_mut() {
+            p.z -= 1.0;
+        }
+        let hit = mesh.try_hit(&ray, 0.0, f64::INFINITY).expect("hit");
+        assert!((hit.t - 3.0).abs() < 1e-12);
+        assert_eq!(mesh.bounding_box().expect("bounded").min.z, -3.0);
+    }
+
+    #[test]
+    fn test_vertex_attributes() {
+        // a square facing +z, with normals tilted to the right and uvs covering it
+        let mesh = TriangleMesh {
+            positions: vec![
+                PositionVec::new(0.0, 0.0, -1.0),
+                PositionVec::new(1.0, 0.0, -1.0),
+                PositionVec::new(1.0, 1.0, -1.0),
+                PositionVec::new(0.0, 1.0, -1.0),
+            ],
+            normals: vec![
+                PositionVec::new(0.0, 0.0, 1.0),
+                PositionVec::new(1.0, 0.0, 1.0).normalize(),
+                PositionVec::new(1.0, 0.0, 1.0).normalize(),
+                PositionVec::new(0.0, 0.0, 1.0),
+            ],
+            uvs: vec![[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]],
+            indices: vec![[0, 1, 2], [0, 2, 3]],
+            ..Default::default()
+        };
+        for (x, y) in [(0.75, 0.25), (0.25, 0.75), (0.5, 0.5)] {
+            let ray = Ray {
+                origin: PositionVec::new(x, y, 0.0),
+                direction: PositionVec::new(0.0, 0.0, -1.0),
+            };
+            let hit = mesh.try_hit(&ray, 0.0, f64::INFINITY).expect("hit");
+            assert!((hit.uv[0] - x).abs() < 1e-12 && (hit.uv[1] - y).abs() < 1e-12);
+            assert_eq!(hit.surface_nv, PositionVec::new(0.0, 0.0, 1.0));
+            assert!(hit.shading_nv.x > 0.0 && (hit.shading_nv.norm() - 1.0).abs() < 1e-12);
+        }
+
+        // attributes not matching the positions are ignored
+        let mut mesh = mesh;
+        mesh.normals_mut().truncate(1);
+        mesh.uvs_mut().truncate(1);
+        let ray = Ray {
+            origin: PositionVec::new(0.75, 0.25, 0.0),
+            direction: PositionVec::new(0.0, 0.0, -1.0),
+        };
+        let hit = mesh.try_hit(&ray, 0.0, f64::INFINITY).expect("hit");
+        assert_eq!(hit.shading_nv, hit.surface_nv);
+        let triangle = mesh.triangle(0);
+        assert!(triangle.normals.is_none() && triangle.uvs.is_none());
+    }
+
+    #[test]
+    fn test_same_hits_as_linear_scan() {
+        // a soup of small triangles, sharing some vertices
+        let mut rng = StdRng::seed_from_u64(11);
+        let mut mesh = TriangleMesh::default();
+        for _ in 0..1000 {
+            let p = PositionVec::new(
+                rng.gen_range(-5.0..5.0),
+                rng.gen_range(-5.0..5.0),
+                rng.gen_range(-5.0..5.0),
+            );
+            mesh.positions.push(p);
+        }
+        for _ in 0..1000 {
+            let a = rng.gen_range(0..mesh.positions.len() as u32);
+            let p = mesh.positions[a as usize];
+            let base = mesh.positions.len() as u32;
+            mesh.positions
+                .push(p + PositionVec::new(rng.gen_range(-1.0..1.0), 0.2, 0.0));
+            mesh.positions
+                .push(p + PositionVec::new(0.0, rng.gen_range(-1.0..1.0), 0.3));
+            mesh.indices.push([a, base, base + 1]);
+        }
+        let mut hits = 0;
+        for _ in 0..1000 {
+            let ray = Ray {
+                origin: PositionVec::new(rng.gen_range(-8.0..8.0), rng.gen_range(-8.0..8.0), 8.0),
+                direction: PositionVec::new(
+                    rng.gen_range(-0.5..0.5),
+                    rng.gen_range(-0.5..0.5),
+                    -1.0,
+                ),
+            };
+            let mut expected: Option<HitEvent> = None;
+            for i in 0..mesh.triangle_count() {
+                let t_max = expected.as_ref().map_or(f64::INFINITY, |hit| hit.t);
+                if let Some(hit) = mesh.triangle(i).try_hit(&ray, 0.0, t_max) {
+                    expected = Some(hit);
+                }
+            }
+            let hit = mesh.try_hit(&ray, 0.0, f64::INFINITY);
+            assert_eq!(
+                hit.map(|h| (h.t, h.surface_nv, h.uv)),
+                expected.as_ref().map(|h| (h.t, h.surface_nv, h.uv))
+            );
+            hits += expected.is_some() as usize;
+        }
+        assert!(hits > 200, "only {hits} rays hit");
+        let bounds = mesh.bounding_box().expect("bounded");
+        assert!((0..mesh.triangle_count())
+            .flat_map(|i| mesh.vertices(i))
+            .all(|p| bounds.grow(&p) == bounds));
+    }
+}
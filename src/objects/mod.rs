@@ -1 +1,98 @@
+pub mod aabox;
+pub mod cylinder;
+pub mod mesh;
+pub mod plane;
+pub mod shaded;
 pub mod sphere;
+pub mod torus;
+pub mod triangle;
+
+use crate::aabb::Aabb;
+use crate::ray::Ray;
+use crate::scene::HitEvent;
+use crate::types::{NumPosition, PixelF64, PositionVec, Time};
+
+/// Visualize a unit normal vector as color, mapping each component from [-1, 1] to [0, 1].
+pub(crate) fn normal_color(nv: &PositionVec) -> PixelF64 {
+    let c = 0.5 * (nv + PositionVec::new(1.0, 1.0, 1.0));
+    PixelF64::new(c.x, c.y, c.z)
+}
+
+/// Hit event of a surface without vertex attributes.
+pub(crate) fn hit_event(
+    ray: &Ray,
+    t: Time,
+    surface_nv: PositionVec,
+    uv: [NumPosition; 2],
+) -> HitEvent {
+    HitEvent {
+        hit_pos: ray.at(t),
+        surface_nv,
+        shading_nv: surface_nv,
+        uv,
+        t,
+        color: None,
+        material: None,
+    }
+}
+
+/// Two unit vectors perpendicular to unit vector `n` and to each other,
+/// forming a right-handed basis with `n` (Duff et al., 2017).
+pub(crate) fn orthonormal_basis(n: &PositionVec) -> (PositionVec, PositionVec) {
+    let sign = 1.0f64.copysign(n.z);
+    let a = -1.0 / (sign + n.z);
+    let b = n.x * n.y * a;
+    (
+        PositionVec::new(1.0 + sign * n.x * n.x * a, sign * b, -sign * n.x),
+        PositionVec::new(b, sign + n.y * n.y * a, -n.y),
+    )
+}
+
+/// Bounds of a disk with unit normal `nv`, which extends less along axes closer to the normal.
+pub(crate) fn disk_bounds(center: &PositionVec, nv: &PositionVec, radius: NumPosition) -> Aabb {
+    let half = nv.map(|c| radius * (1.0 - c * c).max(0.0).sqrt());
+    Aabb::around(center, &half)
+}
+
+/// A right-handed orthonormal frame with its `axis` as the local z axis,
+/// for objects that are simplest to intersect in their own coordinates.
+pub(crate) struct Frame {
+    origin: PositionVec,
+    tangent: PositionVec,
+    bitangent: PositionVec,
+    axis: PositionVec,
+}
+
+impl Frame {
+    /// Frame at `origin` with the direction of nonzero vector `axis` as local z.
+    pub(crate) fn new(origin: PositionVec, axis: &PositionVec) -> Self {
+        let axis = axis.normalize();
+        let (tangent, bitangent) = orthonormal_basis(&axis);
+        Frame {
+            origin,
+            tangent,
+            bitangent,
+            axis,
+        }
+    }
+
+    /// Ray in local coordinates, keeping the length of its direction so hit times agree.
+    pub(crate) fn ray_to_local(&self, ray: &Ray) -> Ray {
+        Ray {
+            origin: self.vector_to_local(&(ray.origin - self.origin)),
+            direction: self.vector_to_local(&ray.direction),
+        }
+    }
+
+    pub(crate) fn vector_to_local(&self, v: &PositionVec) -> PositionVec {
+        PositionVec::new(
+            v.dot(&self.tangent),
+            v.dot(&self.bitangent),
+            v.dot(&self.axis),
+        )
+    }
+
+    pub(crate) fn vector_to_world(&self, v: &PositionVec) -> PositionVec {
+        v.x * self.tangent + v.y * self.bitangent + v.z * self.axis
+    }
+}
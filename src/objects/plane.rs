@@ -0,0 +1,177 @@
+use crate::aabb::Aabb;
+use crate::objects::{disk_bounds, hit_event, orthonormal_basis};
+use crate::ray::Ray;
+use crate::scene::{Bounded, HitEvent, Hittable};
+use crate::types::{NumPosition, PositionVec, Time};
+use std::f64::consts::PI;
+
+/// Hit time of the plane through `point` with normal `nv`, in time range `t1` <= t < `t2`.
+fn hit_plane(point: &PositionVec, nv: &PositionVec, ray: &Ray, t1: Time, t2: Time) -> Option<Time> {
+    let denominator = nv.dot(&ray.direction);
+    if denominator == 0.0 {
+        // parallel to the plane
+        return None;
+    }
+    let t = nv.dot(&(point - ray.origin)) / denominator;
+    (t1..t2).contains(&t).then_some(t)
+}
+
+/// An infinite plane, its outer side is where the normal points to.
+pub struct Plane {
+    pub point: PositionVec,
+    /// unit normal vector
+    pub normal: PositionVec,
+}
+
+impl Hittable for Plane {
+    fn try_hit(&self, ray: &Ray, t1: Time, t2: Time) -> Option<HitEvent> {
+        let t = hit_plane(&self.point, &self.normal, ray, t1, t2)?;
+        // unbounded coordinates along two directions in the plane
+        let (tangent, bitangent) = orthonormal_basis(&self.normal);
+        let p = ray.at(t) - self.point;
+        Some(hit_event(
+            ray,
+            t,
+            self.normal,
+            [p.dot(&tangent), p.dot(&bitangent)],
+        ))
+    }
+}
+
+impl Bounded for Plane {}
+
+/// A parallelogram spanned by two edges from a corner,
+/// front facing when `u` turns counter-clockwise to `v`.
+pub struct Quad {
+    pub corner: PositionVec,
+    pub u: PositionVec,
+    pub v: PositionVec,
+}
+
+impl Hittable for Quad {
+    fn try_hit(&self, ray: &Ray, t1: Time, t2: Time) -> Option<HitEvent> {
+        let n = self.u.cross(&self.v);
+        let t = hit_plane(&self.corner, &n, ray, t1, t2)?;
+        // coordinates along the edges, from 0 to 1 inside the quad
+        let p = ray.at(t) - self.corner;
+        let w = n / n.norm_squared();
+        let uv = [w.dot(&p.cross(&self.v)), w.dot(&self.u.cross(&p))];
+        if !uv.iter().all(|c| (0.0..=1.0).contains(c)) {
+            return None;
+        }
+        Some(hit_event(ray, t, n.normalize(), uv))
+    }
+}
+
+impl Bounded for Quad {
+    fn bounding_box(&self) -> Option<Aabb> {
+        let corners = [0.0, 1.0].map(|a| [0.0, 1.0].map(|b| self.corner + a * self.u + b * self.v));
+        Some(Aabb::from_points(corners.iter().flatten()))
+    }
+}
+
+/// A flat disk, its outer side is where the normal points to.
+pub struct Disk {
+    pub center: PositionVec,
+    /// unit normal vector
+    pub normal: PositionVec,
+    pub radius: NumPosition,
+}
+
+impl Hittable for Disk {
+    fn try_hit(&self, ray: &Ray, t1: Time, t2: Time) -> Option<HitEvent> {
+        let t = hit_plane(&self.center, &self.normal, ray, t1, t2)?;
+        let p = ray.at(t) - self.center;
+        let r = p.norm();
+        if r > self.radius {
+            return None;
+        }
+        // angle around the center and distance from it
+        let (tangent, bitangent) = orthonormal_basis(&self.normal);
+        let phi = p
+            .dot(&bitangent)
+            .atan2(p.dot(&tangent))
+            .rem_euclid(2.0 * PI);
+        Some(hit_event(
+            ray,
+            t,
+            self.normal,
+            [phi / (2.0 * PI), r / self.radius],
+        ))
+    }
+}
+
+impl Bounded for Disk {
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(disk_bounds(&self.center, &self.normal, self.radius))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::objects::plane::{Disk, Plane, Quad};
+    use crate::ray::Ray;
+    use crate::scene::{HitEvent, Hittable};
+    use crate::types::PositionVec;
+
+    fn down(x: f64, y: f64) -> Ray {
+        Ray {
+            origin: PositionVec::new(x, 2.0, y),
+            direction: PositionVec::new(0.0, -1.0, 0.0),
+        }
+    }
+
+    fn hit(object: &dyn Hittable, ray: &Ray) -> Option<HitEvent> {
+        object.try_hit(ray, 0.0, f64::INFINITY)
+    }
+
+    #[test]
+    fn test_plane() {
+        let plane = Plane {
+            point: PositionVec::new(0.0, 1.0, 0.0),
+            normal: PositionVec::new(0.0, 1.0, 0.0),
+        };
+        let event = hit(&plane, &down(100.0, -50.0)).expect("hit");
+        assert_eq!(event.t, 1.0);
+        assert_eq!(event.surface_nv, PositionVec::new(0.0, 1.0, 0.0));
+        assert!(plane.try_hit(&down(0.0, 0.0), 0.0, 1.0).is_none());
+        let parallel = Ray {
+            origin: PositionVec::zeros(),
+            direction: PositionVec::new(1.0, 0.0, 0.0),
+        };
+        assert!(hit(&plane, &parallel).is_none());
+    }
+
+    #[test]
+    fn test_quad() {
+        // 2 x 1 rectangle on the ground, facing up
+        let quad = Quad {
+            corner: PositionVec::new(0.0, 0.0, 0.0),
+            u: PositionVec::new(0.0, 0.0, 1.0),
+            v: PositionVec::new(2.0, 0.0, 0.0),
+        };
+        let event = hit(&quad, &down(1.5, 0.25)).expect("hit");
+        assert_eq!(event.t, 2.0);
+        assert_eq!(event.surface_nv, PositionVec::new(0.0, 1.0, 0.0));
+        assert!((event.uv[0] - 0.25).abs() < 1e-12 && (event.uv[1] - 0.75).abs() < 1e-12);
+        assert!(hit(&quad, &down(2.5, 0.25)).is_none());
+        assert!(hit(&quad, &down(1.0, -0.1)).is_none());
+    }
+
+    #[test]
+    fn test_disk() {
+        let disk = Disk {
+            center: PositionVec::zeros(),
+            normal: PositionVec::new(0.0, 1.0, 0.0),
+            radius: 2.0,
+        };
+        let event = hit(&disk, &down(1.0, 0.0)).expect("hit");
+        assert_eq!(event.surface_nv, PositionVec::new(0.0, 1.0, 0.0));
+        assert!((event.uv[1] - 0.5).abs() < 1e-12);
+        let other = hit(&disk, &down(0.0, 1.0)).expect("hit");
+        // a quarter turn apart
+        let turn = (other.uv[0] - event.uv[0]).rem_euclid(1.0);
+        assert!((turn - 0.25).abs() < 1e-12 || (turn - 0.75).abs() < 1e-12);
+        assert!(hit(&disk, &down(1.5, 1.5)).is_none());
+    }
+}
@@ -0,0 +1,27 @@
+use crate::aabb::Aabb;
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::scene::{Bounded, HitEvent, Hittable};
+use crate::types::Time;
+use std::sync::Arc;
+
+/// Wraps an object to give its surface a material, replacing materials of its parts.
+pub struct Shaded<H> {
+    pub object: H,
+    /// shared by all objects made of it
+    pub material: Arc<dyn Material>,
+}
+
+impl<H: Hittable> Hittable for Shaded<H> {
+    fn try_hit(&self, ray: &Ray, t1: Time, t2: Time) -> Option<HitEvent> {
+        let mut hit = self.object.try_hit(ray, t1, t2)?;
+        hit.material = Some(self.material.clone());
+        Some(hit)
+    }
+}
+
+impl<H: Bounded> Bounded for Shaded<H> {
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.object.bounding_box()
+    }
+}
@@ -1,14 +1,16 @@
+use crate::aabb::Aabb;
 use crate::ray::Ray;
-use crate::scene::{HitEvent, Hittable};
-use crate::types::{NumPosition, Pixel, PositionVec, Time};
+use crate::scene::{Bounded, HitEvent, Hittable};
+use crate::types::{NumPosition, PositionVec, Time};
+use std::f64::consts::PI;
 
-pub struct NormalVectorVisualizedSphere {
+pub struct Sphere {
     pub center: PositionVec,
     pub radius: NumPosition,
 }
 
-impl<T: Pixel> Hittable<T> for NormalVectorVisualizedSphere {
-    fn try_hit(&self, ray: &Ray, t1: Time, t2: Time) -> Option<HitEvent<T>> {
+impl Hittable for Sphere {
+    fn try_hit(&self, ray: &Ray, t1: Time, t2: Time) -> Option<HitEvent> {
         let oc = ray.origin - self.center;
         let a = ray.direction.norm_squared();
         let b = 2.0 * oc.dot(&ray.direction);
@@ -29,12 +31,26 @@ impl<T: Pixel> Hittable<T> for NormalVectorVisualizedSphere {
         }
         let hit_pos = ray.at(t);
         let surface_nv = (hit_pos - self.center).normalize();
-        let color = 0.5 * (surface_nv + PositionVec::new(1.0, 1.0, 1.0));
+        // longitude around the y axis, and latitude from the bottom pole
+        let phi = (-surface_nv.z).atan2(surface_nv.x) + PI;
+        let theta = (-surface_nv.y).clamp(-1.0, 1.0).acos();
         Some(HitEvent {
             hit_pos,
             surface_nv,
+            shading_nv: surface_nv,
+            uv: [phi / (2.0 * PI), theta / PI],
             t,
-            color: T::from_rgb_normalized(color.x, color.y, color.z),
+            color: None,
+            material: None,
         })
     }
 }
+
+impl Bounded for Sphere {
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::around(
+            &self.center,
+            &PositionVec::repeat(self.radius.abs()),
+        ))
+    }
+}
@@ -0,0 +1,128 @@
+use crate::aabb::Aabb;
+use crate::objects::{disk_bounds, hit_event, Frame};
+use crate::polynomial::solve_quartic;
+use crate::ray::Ray;
+use crate::scene::{Bounded, HitEvent, Hittable};
+use crate::types::{NumPosition, PositionVec, Time};
+use std::f64::consts::PI;
+
+/// A ring shaped surface, sweeping a circle around an axis in the plane of the axis.
+pub struct Torus {
+    pub center: PositionVec,
+    /// unit vector perpendicular to the plane of the ring
+    pub axis: PositionVec,
+    /// distance from the center to the middle of the tube
+    pub major_radius: NumPosition,
+    /// radius of the tube
+    pub minor_radius: NumPosition,
+}
+
+impl Hittable for Torus {
+    fn try_hit(&self, ray: &Ray, t1: Time, t2: Time) -> Option<HitEvent> {
+        let frame = Frame::new(self.center, &self.axis);
+        let local = frame.ray_to_local(ray);
+        let (r_major, r_minor) = (self.major_radius, self.minor_radius);
+        // solve with a unit direction from the point of the ray closest to the center,
+        // which keeps the coefficients of the quartic small for rays from far away
+        let scale = local.direction.norm();
+        let d = local.direction / scale;
+        let closest = -local.origin.dot(&d);
+        let o = local.origin + closest * d;
+        // the torus is (|p|^2 - R^2 - r^2)^2 = 4 R^2 (r^2 - z^2),
+        // a quartic in s with |p|^2 = s^2 + 2 f s + |o|^2 along the ray
+        let f = o.dot(&d);
+        let e = o.norm_squared() - r_major * r_major - r_minor * r_minor;
+        let four_r2 = 4.0 * r_major * r_major;
+        let roots = solve_quartic(
+            1.0,
+            4.0 * f,
+            2.0 * e + 4.0 * f * f + four_r2 * d.z * d.z,
+            4.0 * f * e + 2.0 * four_r2 * o.z * d.z,
+            e * e - four_r2 * (r_minor * r_minor - o.z * o.z),
+        );
+        // roots are sorted, the first one in range is the nearest hit
+        let t = roots
+            .into_iter()
+            .map(|s| (closest + s) / scale)
+            .find(|t| (t1..t2).contains(t))?;
+        let p = local.at(t);
+        // the normal points away from the nearest point on the middle circle of the tube
+        let ring = PositionVec::new(p.x, p.y, 0.0)
+            .try_normalize(0.0)
+            .unwrap_or(PositionVec::x());
+        let nv = (p - r_major * ring).normalize();
+        // angle around the axis, and around the tube starting from the outer equator
+        let u = p.y.atan2(p.x).rem_euclid(2.0 * PI) / (2.0 * PI);
+        let v = nv.z.atan2(nv.dot(&ring)).rem_euclid(2.0 * PI) / (2.0 * PI);
+        Some(hit_event(ray, t, frame.vector_to_world(&nv), [u, v]))
+    }
+}
+
+impl Bounded for Torus {
+    fn bounding_box(&self) -> Option<Aabb> {
+        // the middle circle of the tube, thickened by the tube
+        let ring = disk_bounds(&self.center, &self.axis, self.major_radius);
+        let tube = PositionVec::repeat(self.minor_radius);
+        Some(Aabb {
+            min: ring.min - tube,
+            max: ring.max + tube,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::objects::torus::Torus;
+    use crate::ray::Ray;
+    use crate::scene::Hittable;
+    use crate::types::PositionVec;
+
+    fn hit(torus: &Torus, origin: PositionVec, direction: PositionVec, t1: f64) -> Option<f64> {
+        torus
+            .try_hit(&Ray { origin, direction }, t1, f64::INFINITY)
+            .map(|e| e.t)
+    }
+
+    #[test]
+    fn test_torus() {
+        // lying on the ground, the tube spans 1 to 3 from the center
+        let torus = Torus {
+            center: PositionVec::zeros(),
+            axis: PositionVec::new(0.0, 1.0, 0.0),
+            major_radius: 2.0,
+            minor_radius: 1.0,
+        };
+        let origin = PositionVec::new(-5.0, 0.0, 0.0);
+        let direction = PositionVec::new(2.0, 0.0, 0.0);
+        // all four crossings of the tube along a diameter
+        assert!((hit(&torus, origin, direction, 0.0).unwrap() - 1.0).abs() < 1e-9);
+        assert!((hit(&torus, origin, direction, 1.1).unwrap() - 2.0).abs() < 1e-9);
+        assert!((hit(&torus, origin, direction, 2.1).unwrap() - 3.0).abs() < 1e-9);
+        assert!((hit(&torus, origin, direction, 3.1).unwrap() - 4.0).abs() < 1e-9);
+        assert!(hit(&torus, origin, direction, 4.1).is_none());
+        // through the hole
+        let down = PositionVec::new(0.0, -1.0, 0.0);
+        assert!(hit(&torus, PositionVec::new(0.0, 5.0, 0.0), down, 0.0).is_none());
+
+        let event = torus
+            .try_hit(
+                &Ray {
+                    origin: PositionVec::new(0.0, 5.0, 2.0),
+                    direction: down,
+                },
+                0.0,
+                f64::INFINITY,
+            )
+            .expect("hit");
+        assert!((event.t - 4.0).abs() < 1e-9);
+        assert!((event.surface_nv - PositionVec::new(0.0, 1.0, 0.0)).norm() < 1e-9);
+
+        // rays from far away land on the surface
+        let far = PositionVec::new(1e5, 3e4, -2e5);
+        let target = PositionVec::new(2.5, 0.5, 0.5);
+        let t = hit(&torus, far, target - far, 0.0).expect("hit");
+        let p = far + t * (target - far);
+        let tube = (PositionVec::new(p.x, 0.0, p.z).norm() - 2.0).hypot(p.y);
+        assert!((tube - 1.0).abs() < 1e-6, "{p}");
+    }
+}
@@ -0,0 +1,187 @@
+use crate::aabb::Aabb;
+use crate::ray::Ray;
+use crate::scene::{Bounded, HitEvent, Hittable};
+use crate::types::{NumPosition, PositionVec, Time};
+
+/// A single triangle, front facing when its vertices are in counter-clockwise order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Triangle {
+    pub vertices: [PositionVec; 3],
+    /// vertex normals for smooth shading
+    pub normals: Option<[PositionVec; 3]>,
+    /// vertex texture coordinates
+    pub uvs: Option<[[NumPosition; 2]; 3]>,
+}
+
+impl Triangle {
+    pub fn new(a: PositionVec, b: PositionVec, c: PositionVec) -> Self {
+        Triangle {
+            vertices: [a, b, c],
+            normals: None,
+            uvs: None,
+        }
+    }
+
+    /// Unit normal of the front face, `None` if the triangle is degenerated.
+    pub fn normal(&self) -> Option<PositionVec> {
+        let [a, b, c] = self.vertices;
+        (b - a).cross(&(c - a)).try_normalize(0.0)
+    }
+
+    /// Create the hit event at time `t` with given barycentric coordinates.
+    pub(crate) fn hit_event(&self, ray: &Ray, t: Time, barycentric: [NumPosition; 3]) -> HitEvent {
+        let [w, u, v] = barycentric;
+        let surface_nv = self.normal().unwrap_or_else(|| -ray.direction.normalize());
+        let shading_nv = self
+            .normals
+            .and_then(|[na, nb, nc]| (w * na + u * nb + v * nc).try_normalize(0.0))
+            .unwrap_or(surface_nv);
+        let uv = match self.uvs {
+            Some([ta, tb, tc]) => [0, 1].map(|i| w * ta[i] + u * tb[i] + v * tc[i]),
+            None => [u, v],
+        };
+        HitEvent {
+            hit_pos: ray.at(t),
+            surface_nv,
+            shading_nv,
+            uv,
+            t,
+            color: None,
+            material: None,
+        }
+    }
+}
+
+/// Watertight ray-triangle intersection (Woop, Benthin and Wald, 2013), returning the hit
+/// time and the barycentric coordinates of the vertices if the ray hits the triangle in time
+/// range `t1` <= t < `t2`. Rays through an edge or a vertex shared by adjacent triangles
+/// hit at least one of them.
+pub(crate) fn intersect(
+    vertices: &[PositionVec; 3],
+    ray: &Ray,
+    t1: Time,
+    t2: Time,
+) -> Option<(Time, [NumPosition; 3])> {
+    let d = &ray.direction;
+    // make the dominant axis of the ray direction z, keeping the winding
+    let kz = d.iamax();
+    let mut kx = (kz + 1) % 3;
+    let mut ky = (kx + 1) % 3;
+    if d[kz] < 0.0 {
+        std::mem::swap(&mut kx, &mut ky);
+    }
+    // shear so the ray points at +z from the origin
+    let sx = d[kx] / d[kz];
+    let sy = d[ky] / d[kz];
+    let sz = 1.0 / d[kz];
+    let [a, b, c] = vertices.map(|p| {
+        let p = p - ray.origin;
+        PositionVec::new(p[kx] - sx * p[kz], p[ky] - sy * p[kz], sz * p[kz])
+    });
+    // scaled barycentric coordinates are signed areas of the edges seen from the ray
+    let e0 = c.x * b.y - c.y * b.x;
+    let e1 = a.x * c.y - a.y * c.x;
+    let e2 = b.x * a.y - b.y * a.x;
+    if (e0 < 0.0 || e1 < 0.0 || e2 < 0.0) && (e0 > 0.0 || e1 > 0.0 || e2 > 0.0) {
+        return None;
+    }
+    let det = e0 + e1 + e2;
+    if det == 0.0 {
+        // parallel to the triangle, or a degenerated triangle
+        return None;
+    }
+    let t = (e0 * a.z + e1 * b.z + e2 * c.z) / det;
+    if !(t1..t2).contains(&t) {
+        return None;
+    }
+    Some((t, [e0 / det, e1 / det, e2 / det]))
+}
+
+impl Hittable for Triangle {
+    fn try_hit(&self, ray: &Ray, t1: Time, t2: Time) -> Option<HitEvent> {
+        let (t, barycentric) = intersect(&self.vertices, ray, t1, t2)?;
+        Some(self.hit_event(ray, t, barycentric))
+    }
+}
+
+impl Bounded for Triangle {
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::from_points(&self.vertices))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::objects::triangle::{intersect, Triangle};
+    use crate::ray::Ray;
+    use crate::scene::Hittable;
+    use crate::types::PositionVec;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn test_interpolated_attributes() {
+        let mut triangle = Triangle::new(
+            PositionVec::new(0.0, 0.0, -1.0),
+            PositionVec::new(1.0, 0.0, -1.0),
+            PositionVec::new(0.0, 1.0, -1.0),
+        );
+        let ray = Ray {
+            origin: PositionVec::new(0.25, 0.5, 0.0),
+            direction: PositionVec::new(0.0, 0.0, -2.0),
+        };
+        let hit = triangle.try_hit(&ray, 0.0, f64::INFINITY).expect("hit");
+        assert!((hit.t - 0.5).abs() < 1e-12);
+        assert_eq!(hit.surface_nv, PositionVec::new(0.0, 0.0, 1.0));
+        assert_eq!(hit.shading_nv, hit.surface_nv);
+        // barycentric coordinates of the 2nd and 3rd vertex
+        assert!((hit.uv[0] - 0.25).abs() < 1e-12 && (hit.uv[1] - 0.5).abs() < 1e-12);
+
+        triangle.normals = Some([
+            PositionVec::new(0.0, 0.0, 1.0),
+            PositionVec::new(1.0, 0.0, 0.0),
+            PositionVec::new(0.0, 1.0, 0.0),
+        ]);
+        triangle.uvs = Some([[0.0, 0.0], [2.0, 0.0], [0.0, 4.0]]);
+        let hit = triangle.try_hit(&ray, 0.0, f64::INFINITY).expect("hit");
+        let expected = PositionVec::new(0.25, 0.5, 0.25).normalize();
+        assert!((hit.shading_nv - expected).norm() < 1e-12);
+        assert!((hit.uv[0] - 0.5).abs() < 1e-12 && (hit.uv[1] - 2.0).abs() < 1e-12);
+
+        assert!(triangle.try_hit(&ray, 0.0, 0.5).is_none());
+        let parallel = Ray {
+            origin: PositionVec::new(0.25, 0.5, -1.0),
+            direction: PositionVec::new(1.0, 0.0, 0.0),
+        };
+        assert!(triangle.try_hit(&parallel, 0.0, 9.0).is_none());
+    }
+
+    #[test]
+    fn test_watertight_shared_edge() {
+        // rays through the shared diagonal of a quad never slip between the triangles
+        let quad = [
+            PositionVec::new(-1.0, -1.0, 0.0),
+            PositionVec::new(1.0, -1.0, 0.0),
+            PositionVec::new(1.0, 1.0, 0.0),
+            PositionVec::new(-1.0, 1.0, 0.0),
+        ];
+        let lower = [quad[0], quad[1], quad[2]];
+        let upper = [quad[0], quad[2], quad[3]];
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..10000 {
+            let s: f64 = rng.gen_range(-1.0..1.0);
+            let origin = PositionVec::new(
+                rng.gen_range(-3.0..3.0),
+                rng.gen_range(-3.0..3.0),
+                rng.gen_range(0.5..3.0),
+            );
+            let ray = Ray {
+                origin,
+                direction: PositionVec::new(s, s, 0.0) - origin,
+            };
+            let hits = intersect(&lower, &ray, 0.0, f64::INFINITY).is_some() as u32
+                + intersect(&upper, &ray, 0.0, f64::INFINITY).is_some() as u32;
+            assert!(hits > 0, "{ray:?} slipped through the edge");
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use crate::ppm::{Error, Image, ImageFormat};
+use crate::renderer::RenderError;
+use crate::tonemap::DisplayTransform;
+use crate::types::PixelF64;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// What to do when the output file already exists.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum OverwritePolicy {
+    #[default]
+    Overwrite,
+    /// refuse to write and report an error
+    Fail,
+    /// write to the first free path with a numeric suffix, like `result-1.png`
+    Rename,
+}
+
+/// Saves rendered images to a file.
+#[derive(Clone, Debug)]
+pub struct ImageOutput {
+    pub path: PathBuf,
+    /// file format, guessed from file extension of `path` if not set
+    pub format: Option<ImageFormat>,
+    pub overwrite: OverwritePolicy,
+}
+
+impl ImageOutput {
+    pub fn new(path: PathBuf) -> Self {
+        ImageOutput {
+            path,
+            format: None,
+            overwrite: OverwritePolicy::default(),
+        }
+    }
+
+    pub fn get_format(&self) -> Result<ImageFormat, Error> {
+        self.format
+            .or_else(|| ImageFormat::from_path(&self.path))
+            .ok_or_else(|| Error::UnsupportedFormat(self.path.to_string_lossy().into_owned()))
+    }
+
+    /// Create the file to write to, without replacing an existing file unless
+    /// overwriting is allowed. Creating fails if the file appears after checking,
+    /// so concurrent writers never write to the same file.
+    fn create_file(&self) -> Result<(PathBuf, File), RenderError> {
+        let create = |path: &Path| {
+            let mut options = OpenOptions::new();
+            match self.overwrite {
+                OverwritePolicy::Overwrite => options.create(true).truncate(true),
+                OverwritePolicy::Fail | OverwritePolicy::Rename => options.create_new(true),
+            };
+            options.write(true).open(path)
+        };
+        let exists = |e: &io::Error| e.kind() == io::ErrorKind::AlreadyExists;
+        match create(&self.path) {
+            Ok(file) => return Ok((self.path.clone(), file)),
+            Err(e) if exists(&e) && self.overwrite == OverwritePolicy::Rename => {}
+            Err(e) if exists(&e) => return Err(RenderError::OutputExists(self.path.clone())),
+            Err(e) => return Err(Error::from(e).into()),
+        }
+        let stem = self
+            .path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let extension = self.path.extension().map(|s| s.to_string_lossy());
+        let parent = self.path.parent().unwrap_or(Path::new(""));
+        for i in 1.. {
+            let path = parent.join(match &extension {
+                Some(ext) => format!("{stem}-{i}.{ext}"),
+                None => format!("{stem}-{i}"),
+            });
+            match create(&path) {
+                Ok(file) => return Ok((path, file)),
+                Err(e) if exists(&e) => continue,
+                Err(e) => return Err(Error::from(e).into()),
+            }
+        }
+        Err(RenderError::OutputExists(self.path.clone()))
+    }
+
+    /// Save a linear image. Low dynamic range formats get `display` applied first,
+    /// high dynamic range formats keep linear radiance for later processing.
+    /// Returns the path actually written.
+    pub fn write(
+        &self,
+        linear: &Image<PixelF64>,
+        display: &DisplayTransform,
+    ) -> Result<PathBuf, RenderError> {
+        let format = self.get_format()?;
+        let (path, file) = self.create_file()?;
+        let mut writer = BufWriter::new(file);
+        if format.is_high_dynamic_range() {
+            linear.encode_as(&mut writer, format)?;
+        } else {
+            let image: Image<PixelF64> = display.apply(linear);
+            image.encode_as(&mut writer, format)?;
+        }
+        writer.flush().map_err(Error::from)?;
+        Ok(path)
+    }
+}
+
+// the tests write PFM files to check linear output
+#[cfg(all(test, feature = "hdr"))]
+mod tests {
+    use crate::output::{ImageOutput, OverwritePolicy};
+    use crate::ppm::{Image, ImageFormat};
+    use crate::renderer::RenderError;
+    use crate::tonemap::DisplayTransform;
+    use crate::types::{Pixel, PixelF64};
+    use std::fs;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_overwrite_policy() {
+        let dir = std::env::temp_dir().join("rrt_ut_test_overwrite_policy");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("create temp dir");
+        let mut image: Image<PixelF64> = Image::new(1, 1);
+        image.set_pixel(0, 0, PixelF64::new(4.0, 0.0, 0.0));
+        let display = DisplayTransform::default();
+
+        let path = dir.join("out.pfm");
+        let mut output = ImageOutput::new(path.clone());
+        assert_eq!(output.write(&image, &display).expect("write"), path);
+        // linear values are kept in high dynamic range formats
+        assert_eq!(
+            Image::<PixelF64>::load_pfm(&path)
+                .unwrap()
+                .get_pixel(0, 0)
+                .red(),
+            4.0
+        );
+
+        output.overwrite = OverwritePolicy::Fail;
+        assert!(matches!(
+            output.write(&image, &display),
+            Err(RenderError::OutputExists(_))
+        ));
+        output.overwrite = OverwritePolicy::Rename;
+        assert_eq!(
+            output.write(&image, &display).unwrap(),
+            dir.join("out-1.pfm")
+        );
+        assert_eq!(
+            output.write(&image, &display).unwrap(),
+            dir.join("out-2.pfm")
+        );
+
+        // explicit format takes precedence over file extension
+        let output = ImageOutput {
+            path: dir.join("out.image"),
+            format: Some(ImageFormat::Ppm),
+            overwrite: OverwritePolicy::Overwrite,
+        };
+        let path = output.write(&image, &display).expect("write ppm");
+        assert_eq!(
+            Image::<PixelF64>::load(&path)
+                .unwrap()
+                .get_pixel(0, 0)
+                .red(),
+            1.0
+        );
+        assert!(ImageOutput::new(PathBuf::from("out.xyz"))
+            .write(&image, &display)
+            .is_err());
+        fs::remove_dir_all(&dir).expect("remove temp dir");
+    }
+
+    #[test]
+    fn test_existing_files_are_kept() {
+        let dir = std::env::temp_dir().join("rrt_ut_test_existing_files_are_kept");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("create temp dir");
+        let image: Image<PixelF64> = Image::new(1, 1);
+        let display = DisplayTransform::default();
+        for name in ["out.pfm", "out-1.pfm"] {
+            fs::write(dir.join(name), name).expect("write file");
+        }
+
+        let mut output = ImageOutput::new(dir.join("out.pfm"));
+        output.overwrite = OverwritePolicy::Fail;
+        assert!(output.write(&image, &display).is_err());
+        output.overwrite = OverwritePolicy::Rename;
+        assert_eq!(
+            output.write(&image, &display).unwrap(),
+            dir.join("out-2.pfm")
+        );
+        for name in ["out.pfm", "out-1.pfm"] {
+            assert_eq!(fs::read_to_string(dir.join(name)).unwrap(), name);
+        }
+        fs::remove_dir_all(&dir).expect("remove temp dir");
+    }
+}
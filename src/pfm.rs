@@ -0,0 +1,163 @@
+//! Portable Float Map, an uncompressed format storing 32-bit floating point samples.
+
+use crate::ppm::Error::{InvalidHeader, InvalidMagic, TruncatedData};
+use crate::ppm::{create_file, Error, Image, ImageSize};
+use crate::types::{NumColorRatio, Pixel};
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+impl<T: Pixel> Image<T> {
+    /// Save the image as a color PFM file, keeping samples outside of range [0, 1].
+    pub fn save_pfm(&self, path: &Path) -> Result<(), Error> {
+        let mut writer = create_file(path)?;
+        self.encode_pfm(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Write the image in little-endian PFM format to `w`.
+    pub fn encode_pfm<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        // negative scale indicates little endian
+        write!(w, "PF\n{} {}\n-1.0\n", self.get_width(), self.get_height())?;
+        let mut row = Vec::with_capacity(self.get_width() as usize * 12);
+        // scanlines are stored from bottom to top
+        for y in (0..self.get_height()).rev() {
+            row.clear();
+            for x in 0..self.get_width() {
+                let pix = self.get_pixel(x, y);
+                for v in [pix.red(), pix.green(), pix.blue()] {
+                    row.extend_from_slice(&(v as f32).to_le_bytes());
+                }
+            }
+            w.write_all(&row)?;
+        }
+        Ok(())
+    }
+
+    /// Load a PFM file. Both color (PF) and grayscale (Pf) variants are supported.
+    pub fn load_pfm(path: &Path) -> Result<Self, Error> {
+        let data = fs::read(path)?;
+        Self::decode_pfm(&data)
+    }
+
+    /// Decode a PFM image from its file content.
+    pub fn decode_pfm(data: &[u8]) -> Result<Self, Error> {
+        if data.len() < 2 {
+            return Err(TruncatedData);
+        }
+        let channels = match &data[..2] {
+            b"PF" => 3,
+            b"Pf" => 1,
+            magic => return Err(InvalidMagic([magic[0], magic[1]])),
+        };
+        let mut pos = 2;
+        let mut tokens = Vec::with_capacity(3);
+        while tokens.len() < 3 {
+            while data.get(pos).ok_or(TruncatedData)?.is_ascii_whitespace() {
+                pos += 1;
+            }
+            let start = pos;
+            while pos < data.len() && !data[pos].is_ascii_whitespace() {
+                pos += 1;
+            }
+            let token = std::str::from_utf8(&data[start..pos])
+                .map_err(|_| InvalidHeader("header is not ASCII"))?;
+            tokens.push(token);
+        }
+        let width: ImageSize = tokens[0]
+            .parse()
+            .map_err(|_| InvalidHeader("invalid image width"))?;
+        let height: ImageSize = tokens[1]
+            .parse()
+            .map_err(|_| InvalidHeader("invalid image height"))?;
+        let scale: f32 = tokens[2]
+            .parse()
+            .map_err(|_| InvalidHeader("invalid scale factor"))?;
+        if width == 0 || height == 0 {
+            return Err(InvalidHeader("image size must be positive"));
+        }
+        if scale == 0.0 || !scale.is_finite() {
+            return Err(InvalidHeader("scale factor must be finite and non-zero"));
+        }
+        // exactly one whitespace character separates the header and the raster
+        pos += 1;
+        let little_endian = scale < 0.0;
+        let row_size = width as usize * channels * 4;
+        let raster = data.get(pos..).ok_or(TruncatedData)?;
+        if raster.len() / row_size < height as usize {
+            return Err(TruncatedData);
+        }
+        let mut img = Image::new(width, height);
+        for (i, row) in raster
+            .chunks_exact(row_size)
+            .take(height as usize)
+            .enumerate()
+        {
+            let y = height - 1 - i as ImageSize;
+            let samples: Vec<NumColorRatio> = row
+                .chunks_exact(4)
+                .map(|b| {
+                    let b = [b[0], b[1], b[2], b[3]];
+                    let v = if little_endian {
+                        f32::from_le_bytes(b)
+                    } else {
+                        f32::from_be_bytes(b)
+                    };
+                    v as NumColorRatio
+                })
+                .collect();
+            for (x, pix) in samples.chunks_exact(channels).enumerate() {
+                let (r, g, b) = if channels == 3 {
+                    (pix[0], pix[1], pix[2])
+                } else {
+                    (pix[0], pix[0], pix[0])
+                };
+                img.set_pixel(x as ImageSize, y, T::from_rgb_normalized(r, g, b));
+            }
+        }
+        Ok(img)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ppm::{Error, Image};
+    use crate::types::{Pixel, PixelF64};
+
+    #[test]
+    fn test_pfm_roundtrip() {
+        let mut img: Image<PixelF64> = Image::new(4, 3);
+        for (x, y, pix) in img.iter_mut() {
+            *pix = PixelF64::new(x as f64 * 10.5, -(y as f64), 1.0 / 64.0);
+        }
+        let mut data = Vec::new();
+        img.encode_pfm(&mut data).expect("encode pfm");
+        assert!(data.starts_with(b"PF\n4 3\n-1.0\n"));
+        assert_eq!(data.len(), 12 + 4 * 3 * 12);
+        // the first stored scanline is the bottom one
+        assert_eq!(&data[12..16], &0.0f32.to_le_bytes());
+        assert_eq!(&data[16..20], &(-2.0f32).to_le_bytes());
+        let loaded: Image<PixelF64> = Image::decode_pfm(&data).expect("decode pfm");
+        for ((_, _, a), (_, _, b)) in img.iter().zip(loaded.iter()) {
+            assert_eq!(a.red(), b.red());
+            assert_eq!(a.green(), b.green());
+            assert_eq!(a.blue(), b.blue());
+        }
+    }
+
+    #[test]
+    fn test_pfm_grayscale_big_endian() {
+        let mut data = b"Pf\n2 1\n1.0\n".to_vec();
+        data.extend_from_slice(&0.5f32.to_be_bytes());
+        data.extend_from_slice(&2.0f32.to_be_bytes());
+        let img: Image<PixelF64> = Image::decode_pfm(&data).expect("decode pfm");
+        assert_eq!(img.get_pixel(0, 0).green(), 0.5);
+        assert_eq!(img.get_pixel(1, 0).blue(), 2.0);
+        assert!(matches!(
+            Image::<PixelF64>::decode_pfm(&data[..data.len() - 1]),
+            Err(Error::TruncatedData)
+        ));
+    }
+}
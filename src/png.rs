@@ -0,0 +1,484 @@
+use crate::ppm::Error::{InvalidData, InvalidHeader, TruncatedData, UnsupportedFormat};
+use crate::ppm::{create_file, quantize, BitDepth, Error, Image, ImageSize};
+use crate::types::{NumColorRatio, Pixel};
+use crate::zlib;
+use std::io::Write;
+use std::path::Path;
+use std::{fs, io};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// PNG writer limits chunk size to keep memory usage of readers low.
+const MAX_IDAT_SIZE: usize = 1 << 20;
+
+const COLOR_TYPE_GRAY: u8 = 0;
+const COLOR_TYPE_RGB: u8 = 2;
+const COLOR_TYPE_PALETTE: u8 = 3;
+const COLOR_TYPE_GRAY_ALPHA: u8 = 4;
+const COLOR_TYPE_RGBA: u8 = 6;
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct PngOptions {
+    pub depth: BitDepth,
+    /// write an opaque alpha channel (RGBA color type)
+    pub alpha: bool,
+}
+
+fn write_chunk<W: Write>(w: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    w.write_all(&(data.len() as u32).to_be_bytes())?;
+    w.write_all(kind)?;
+    w.write_all(data)?;
+    let crc = zlib::crc32_update(zlib::crc32_update(0xffff_ffff, kind), data) ^ 0xffff_ffff;
+    w.write_all(&crc.to_be_bytes())
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = (
+        (p - a as i16).abs(),
+        (p - b as i16).abs(),
+        (p - c as i16).abs(),
+    );
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Apply the filter that minimizes the sum of absolute differences (the heuristic
+/// recommended by the PNG specification) to `row`, appending filter type and bytes to `out`.
+fn filter_row(row: &[u8], prev: &[u8], bpp: usize, out: &mut Vec<u8>) {
+    let mut best: Option<(u64, u8, Vec<u8>)> = None;
+    for filter in 0..5u8 {
+        let filtered: Vec<u8> = (0..row.len())
+            .map(|i| {
+                let a = if i >= bpp { row[i - bpp] } else { 0 };
+                let b = prev[i];
+                let c = if i >= bpp { prev[i - bpp] } else { 0 };
+                let predictor = match filter {
+                    0 => 0,
+                    1 => a,
+                    2 => b,
+                    3 => ((a as u16 + b as u16) / 2) as u8,
+                    _ => paeth(a, b, c),
+                };
+                row[i].wrapping_sub(predictor)
+            })
+            .collect();
+        let cost = filtered
+            .iter()
+            .map(|&v| (v as i8).unsigned_abs() as u64)
+            .sum();
+        if best.as_ref().is_none_or(|(c, _, _)| cost < *c) {
+            best = Some((cost, filter, filtered));
+        }
+    }
+    let (_, filter, filtered) = best.expect("at least one filter is evaluated");
+    out.push(filter);
+    out.extend(filtered);
+}
+
+/// Undo the row filters of `raw`, which holds `stride` bytes per row, each row
+/// preceded by its filter type.
+fn unfilter(raw: &[u8], stride: usize, bpp: usize) -> Result<Vec<u8>, Error> {
+    let mut out: Vec<u8> = Vec::with_capacity(raw.len());
+    for (y, line) in raw.chunks_exact(stride + 1).enumerate() {
+        for i in 0..stride {
+            let a = if i >= bpp {
+                out[y * stride + i - bpp]
+            } else {
+                0
+            };
+            let b = if y > 0 { out[(y - 1) * stride + i] } else { 0 };
+            let c = if y > 0 && i >= bpp {
+                out[(y - 1) * stride + i - bpp]
+            } else {
+                0
+            };
+            let predictor = match line[0] {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                4 => paeth(a, b, c),
+                _ => return Err(InvalidData("invalid filter type")),
+            };
+            out.push(line[i + 1].wrapping_add(predictor));
+        }
+    }
+    Ok(out)
+}
+
+/// Sample `i` of a row packed with `depth` bits per sample, most significant bits first.
+fn sample(row: &[u8], i: usize, depth: u8) -> u16 {
+    match depth {
+        16 => u16::from_be_bytes([row[2 * i], row[2 * i + 1]]),
+        8 => row[i] as u16,
+        _ => {
+            let bit = i * depth as usize;
+            let shift = 8 - depth as usize - bit % 8;
+            ((row[bit / 8] >> shift) & ((1 << depth) - 1)) as u16
+        }
+    }
+}
+
+impl<T: Pixel> Image<T> {
+    /// Load a PNG file.
+    pub fn load_png(path: &Path) -> Result<Self, Error> {
+        let data = fs::read(path)?;
+        Self::decode_png(&data)
+    }
+
+    /// Decode a PNG image from its file content. All color types and bit depths are
+    /// supported except interlaced images. Samples are returned as stored, which is
+    /// sRGB encoded in nearly all files, and alpha is ignored.
+    pub fn decode_png(data: &[u8]) -> Result<Self, Error> {
+        if !data.starts_with(&SIGNATURE) {
+            return Err(InvalidHeader("missing PNG signature"));
+        }
+        let mut pos = SIGNATURE.len();
+        let mut header: Option<[u8; 13]> = None;
+        let mut palette: &[u8] = &[];
+        let mut idat = Vec::new();
+        loop {
+            let head = data.get(pos..pos + 8).ok_or(TruncatedData)?;
+            let len = u32::from_be_bytes([head[0], head[1], head[2], head[3]]) as usize;
+            let kind = &head[4..8];
+            let body = data.get(pos + 8..pos + 8 + len).ok_or(TruncatedData)?;
+            let crc = data
+                .get(pos + 8 + len..pos + 12 + len)
+                .ok_or(TruncatedData)?;
+            if zlib::crc32(&data[pos + 4..pos + 8 + len]).to_be_bytes() != crc {
+                return Err(InvalidData("chunk CRC mismatch"));
+            }
+            pos += 12 + len;
+            match kind {
+                b"IHDR" => {
+                    header = Some(
+                        body.try_into()
+                            .map_err(|_| InvalidHeader("invalid IHDR chunk size"))?,
+                    )
+                }
+                b"PLTE" => palette = body,
+                b"IDAT" => idat.extend_from_slice(body),
+                b"IEND" => break,
+                _ => {}
+            }
+        }
+        let header = header.ok_or(InvalidHeader("missing IHDR chunk"))?;
+        let width = ImageSize::from_be_bytes([header[0], header[1], header[2], header[3]]);
+        let height = ImageSize::from_be_bytes([header[4], header[5], header[6], header[7]]);
+        let [depth, color_type, compression, filter, interlace] =
+            [header[8], header[9], header[10], header[11], header[12]];
+        if width == 0 || height == 0 {
+            return Err(InvalidHeader("image size must be positive"));
+        }
+        let channels = match (color_type, depth) {
+            (COLOR_TYPE_GRAY, 1 | 2 | 4 | 8 | 16) => 1,
+            (COLOR_TYPE_RGB, 8 | 16) => 3,
+            (COLOR_TYPE_PALETTE, 1 | 2 | 4 | 8) => 1,
+            (COLOR_TYPE_GRAY_ALPHA, 8 | 16) => 2,
+            (COLOR_TYPE_RGBA, 8 | 16) => 4,
+            _ => return Err(InvalidHeader("invalid color type and bit depth")),
+        };
+        if compression != 0 || filter != 0 {
+            return Err(InvalidHeader("unknown compression or filter method"));
+        }
+        if interlace != 0 {
+            return Err(UnsupportedFormat("interlaced PNG".to_string()));
+        }
+        if color_type == COLOR_TYPE_PALETTE
+            && (palette.is_empty() || !palette.len().is_multiple_of(3))
+        {
+            return Err(InvalidData("missing or invalid palette"));
+        }
+
+        let bits_per_pixel = channels * depth as usize;
+        let stride = (width as usize)
+            .checked_mul(bits_per_pixel)
+            .map(|bits| bits.div_ceil(8))
+            .ok_or(InvalidHeader("image size is too large"))?;
+        let raw = zlib::decompress(&idat).map_err(|_| InvalidData("corrupted image data"))?;
+        // checking the decompressed size rejects impossible sizes before allocating pixels
+        if raw.len() / (stride + 1) < height as usize {
+            return Err(TruncatedData);
+        }
+        let raw = &raw[..(stride + 1) * height as usize];
+        let pixels = unfilter(raw, stride, bits_per_pixel.div_ceil(8))?;
+
+        let max_value = ((1u32 << depth) - 1) as NumColorRatio;
+        let mut img = Image::new(width, height);
+        for (y, row) in pixels.chunks_exact(stride).enumerate() {
+            for x in 0..width as usize {
+                let s =
+                    |c: usize| sample(row, x * channels + c, depth) as NumColorRatio / max_value;
+                let (r, g, b) = match color_type {
+                    COLOR_TYPE_GRAY | COLOR_TYPE_GRAY_ALPHA => (s(0), s(0), s(0)),
+                    COLOR_TYPE_PALETTE => {
+                        let i = sample(row, x, depth) as usize * 3;
+                        let rgb = palette
+                            .get(i..i + 3)
+                            .ok_or(InvalidData("palette index out of range"))?;
+                        let c = |v: u8| v as NumColorRatio / 255.0;
+                        (c(rgb[0]), c(rgb[1]), c(rgb[2]))
+                    }
+                    _ => (s(0), s(1), s(2)),
+                };
+                img.set_pixel(
+                    x as ImageSize,
+                    y as ImageSize,
+                    T::from_rgb_normalized(r, g, b),
+                );
+            }
+        }
+        Ok(img)
+    }
+
+    /// Save the image as a PNG file.
+    pub fn save_png(&self, path: &Path, options: &PngOptions) -> Result<(), Error> {
+        let mut writer = create_file(path)?;
+        self.encode_png(&mut writer, options)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Write the image in PNG format to `w`.
+    pub fn encode_png<W: Write>(&self, w: &mut W, options: &PngOptions) -> io::Result<()> {
+        let (color_type, channels) = if options.alpha {
+            (COLOR_TYPE_RGBA, 4)
+        } else {
+            (COLOR_TYPE_RGB, 3)
+        };
+        let (bit_depth, sample_size) = match options.depth {
+            BitDepth::Eight => (8u8, 1),
+            BitDepth::Sixteen => (16u8, 2),
+        };
+        let max_value = options.depth.max_value();
+        w.write_all(&SIGNATURE)?;
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&self.get_width().to_be_bytes());
+        header.extend_from_slice(&self.get_height().to_be_bytes());
+        // bit depth, color type, compression, filter, interlace
+        header.extend_from_slice(&[bit_depth, color_type, 0, 0, 0]);
+        write_chunk(w, b"IHDR", &header)?;
+
+        let bpp = channels * sample_size;
+        let stride = self.get_width() as usize * bpp;
+        let mut raw = Vec::with_capacity((stride + 1) * self.get_height() as usize);
+        let mut row = Vec::with_capacity(stride);
+        let mut prev = vec![0u8; stride];
+        for (x, _, pix) in self.iter() {
+            let samples = [
+                quantize(pix.red(), max_value),
+                quantize(pix.green(), max_value),
+                quantize(pix.blue(), max_value),
+                max_value,
+            ];
+            for v in samples.iter().take(channels) {
+                match options.depth {
+                    BitDepth::Eight => row.push(*v as u8),
+                    BitDepth::Sixteen => row.extend_from_slice(&v.to_be_bytes()),
+                }
+            }
+            if x + 1 == self.get_width() {
+                filter_row(&row, &prev, bpp, &mut raw);
+                std::mem::swap(&mut row, &mut prev);
+                row.clear();
+            }
+        }
+
+        for chunk in zlib::compress(&raw).chunks(MAX_IDAT_SIZE) {
+            write_chunk(w, b"IDAT", chunk)?;
+        }
+        write_chunk(w, b"IEND", &[])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::png::PngOptions;
+    use crate::ppm::{BitDepth, Error, Image};
+    use crate::types::{Pixel, PixelF64};
+    use crate::zlib;
+
+    /// Split a PNG file into (type, data) chunks, checking every CRC.
+    fn chunks(data: &[u8]) -> Vec<([u8; 4], Vec<u8>)> {
+        assert_eq!(&data[..8], &super::SIGNATURE);
+        let mut pos = 8;
+        let mut ret = Vec::new();
+        while pos < data.len() {
+            let len = u32::from_be_bytes(data[pos..pos + 4].try_into().unwrap()) as usize;
+            let kind: [u8; 4] = data[pos + 4..pos + 8].try_into().unwrap();
+            let body = &data[pos + 8..pos + 8 + len];
+            let crc = u32::from_be_bytes(data[pos + 8 + len..pos + 12 + len].try_into().unwrap());
+            assert_eq!(
+                crc,
+                zlib::crc32(&data[pos + 4..pos + 8 + len]),
+                "bad chunk CRC"
+            );
+            ret.push((kind, body.to_vec()));
+            pos += 12 + len;
+        }
+        ret
+    }
+
+    #[test]
+    fn test_encode_png() {
+        let (width, height) = (17u32, 9u32);
+        let mut img: Image<PixelF64> = Image::new(width, height);
+        for (x, y, pix) in img.iter_mut() {
+            *pix = PixelF64::new(x as f64 / 16.0, y as f64 / 8.0, ((x + y) % 2) as f64);
+        }
+        for (depth, alpha) in [
+            (BitDepth::Eight, false),
+            (BitDepth::Sixteen, false),
+            (BitDepth::Eight, true),
+        ] {
+            let mut data = Vec::new();
+            img.encode_png(&mut data, &PngOptions { depth, alpha })
+                .expect("encode png");
+            let chunks = chunks(&data);
+            assert_eq!(&chunks[0].0, b"IHDR");
+            assert_eq!(&chunks.last().unwrap().0, b"IEND");
+            let header = &chunks[0].1;
+            assert_eq!(&header[..8], &[0, 0, 0, 17, 0, 0, 0, 9]);
+            let idat: Vec<u8> = chunks
+                .iter()
+                .filter(|(kind, _)| kind == b"IDAT")
+                .flat_map(|(_, body)| body.clone())
+                .collect();
+            let channels = if alpha { 4 } else { 3 };
+            let sample_size = if depth == BitDepth::Sixteen { 2 } else { 1 };
+            let bpp = channels * sample_size;
+            let stride = width as usize * bpp;
+            let raw = zlib::decompress(&idat).expect("decompress IDAT");
+            assert_eq!(raw.len(), (stride + 1) * height as usize);
+            let pixels = super::unfilter(&raw, stride, bpp).expect("unfilter");
+            let max_value = depth.max_value() as f64;
+            for (x, y, pix) in img.iter() {
+                let offset = y as usize * stride + x as usize * bpp;
+                let sample = |c: usize| -> f64 {
+                    let i = offset + c * sample_size;
+                    if sample_size == 2 {
+                        u16::from_be_bytes([pixels[i], pixels[i + 1]]) as f64
+                    } else {
+                        pixels[i] as f64
+                    }
+                };
+                assert_eq!(sample(0), (pix.red() * max_value).round());
+                assert_eq!(sample(1), (pix.green() * max_value).round());
+                assert_eq!(sample(2), (pix.blue() * max_value).round());
+                if alpha {
+                    assert_eq!(sample(3), max_value);
+                }
+            }
+        }
+    }
+
+    /// Build a PNG file from the IHDR fields and unfiltered rows.
+    fn png_file(width: u32, height: u32, depth: u8, color_type: u8, rows: &[&[u8]]) -> Vec<u8> {
+        png_file_with(width, height, depth, color_type, 0, &[], rows)
+    }
+
+    fn png_file_with(
+        width: u32,
+        height: u32,
+        depth: u8,
+        color_type: u8,
+        interlace: u8,
+        palette: &[u8],
+        rows: &[&[u8]],
+    ) -> Vec<u8> {
+        let mut header = Vec::new();
+        header.extend_from_slice(&width.to_be_bytes());
+        header.extend_from_slice(&height.to_be_bytes());
+        header.extend_from_slice(&[depth, color_type, 0, 0, interlace]);
+        let raw: Vec<u8> = rows.iter().flat_map(|r| [&[0u8][..], r].concat()).collect();
+        let mut data = super::SIGNATURE.to_vec();
+        super::write_chunk(&mut data, b"IHDR", &header).unwrap();
+        if !palette.is_empty() {
+            super::write_chunk(&mut data, b"PLTE", palette).unwrap();
+        }
+        super::write_chunk(&mut data, b"IDAT", &zlib::compress(&raw)).unwrap();
+        super::write_chunk(&mut data, b"IEND", &[]).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_decode_png() {
+        let (width, height) = (13u32, 6u32);
+        let mut img: Image<PixelF64> = Image::new(width, height);
+        for (x, y, pix) in img.iter_mut() {
+            *pix = PixelF64::new(x as f64 / 12.0, y as f64 / 5.0, ((x + y) % 2) as f64);
+        }
+        for (depth, alpha) in [(BitDepth::Eight, true), (BitDepth::Sixteen, false)] {
+            let mut data = Vec::new();
+            img.encode_png(&mut data, &PngOptions { depth, alpha })
+                .expect("encode png");
+            let decoded: Image<PixelF64> = Image::decode_png(&data).expect("decode png");
+            let tolerance = 0.5 / depth.max_value() as f64 + 1e-12;
+            for (x, y, pix) in img.iter() {
+                let d = decoded.get_pixel(x, y);
+                assert!((d.red() - pix.red()).abs() <= tolerance);
+                assert!((d.green() - pix.green()).abs() <= tolerance);
+                assert!((d.blue() - pix.blue()).abs() <= tolerance);
+            }
+        }
+
+        // 2-bit grayscale, 5 pixels packed into two bytes per row
+        let data = png_file(
+            5,
+            1,
+            2,
+            super::COLOR_TYPE_GRAY,
+            &[&[0b00011011, 0b11000000]],
+        );
+        let gray: Image<PixelF64> = Image::decode_png(&data).expect("decode gray png");
+        let values: Vec<f64> = gray.iter().map(|(_, _, p)| p.green()).collect();
+        assert_eq!(values, [0.0, 1.0 / 3.0, 2.0 / 3.0, 1.0, 1.0]);
+
+        // 1-bit palette
+        let palette = [255, 0, 0, 0, 0, 255];
+        let data = png_file_with(
+            3,
+            1,
+            1,
+            super::COLOR_TYPE_PALETTE,
+            0,
+            &palette,
+            &[&[0b01000000]],
+        );
+        let indexed: Image<PixelF64> = Image::decode_png(&data).expect("decode palette png");
+        assert_eq!(indexed.get_pixel(0, 0), PixelF64::new(1.0, 0.0, 0.0));
+        assert_eq!(indexed.get_pixel(1, 0), PixelF64::new(0.0, 0.0, 1.0));
+        assert_eq!(indexed.get_pixel(2, 0), PixelF64::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_decode_png_invalid() {
+        let decode = |data: &[u8]| Image::<PixelF64>::decode_png(data).map(|_| ());
+        let valid = png_file(2, 2, 8, super::COLOR_TYPE_GRAY, &[&[1, 2], &[3, 4]]);
+        assert!(decode(&valid).is_ok());
+        assert!(matches!(decode(&valid[..40]), Err(Error::TruncatedData)));
+        let mut corrupted = valid.clone();
+        corrupted[20] ^= 1;
+        assert!(matches!(decode(&corrupted), Err(Error::InvalidData(_))));
+        let interlaced =
+            png_file_with(2, 2, 8, super::COLOR_TYPE_GRAY, 1, &[], &[&[1, 2], &[3, 4]]);
+        assert!(matches!(
+            decode(&interlaced),
+            Err(Error::UnsupportedFormat(_))
+        ));
+        let short = png_file(2, 3, 8, super::COLOR_TYPE_GRAY, &[&[1, 2], &[3, 4]]);
+        assert!(matches!(decode(&short), Err(Error::TruncatedData)));
+        let missing_palette = png_file(1, 1, 8, super::COLOR_TYPE_PALETTE, &[&[0]]);
+        assert!(matches!(
+            decode(&missing_palette),
+            Err(Error::InvalidData(_))
+        ));
+    }
+}
@@ -0,0 +1,191 @@
+//! Real roots of polynomials up to degree four.
+//!
+//! Roots are returned in ascending order, and repeated roots may be returned once or
+//! several times. Roots of cubics and quartics are refined with Newton iterations on the
+//! original polynomial, which keeps them accurate when closed-form solutions cancel badly.
+
+/// Roots of `a x^2 + b x + c`, or of the linear equation if `a` is zero.
+pub fn solve_quadratic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    if a == 0.0 {
+        return if b == 0.0 { vec![] } else { vec![-c / b] };
+    }
+    let delta = b * b - 4.0 * a * c;
+    if delta < 0.0 {
+        return vec![];
+    }
+    // avoid subtracting numbers of similar size
+    let q = -0.5 * (b + delta.sqrt().copysign(b));
+    let mut roots = if q == 0.0 {
+        // b and c are both zero
+        vec![0.0, 0.0]
+    } else {
+        vec![q / a, c / q]
+    };
+    roots.sort_by(f64::total_cmp);
+    roots
+}
+
+/// Evaluate a polynomial with coefficients from the highest degree, and its derivative.
+fn evaluate(coefficients: &[f64], x: f64) -> (f64, f64) {
+    coefficients
+        .iter()
+        .fold((0.0, 0.0), |(p, dp), &c| (p * x + c, dp * x + p))
+}
+
+/// Polish roots with a few Newton iterations, keeping a step only if it improves the root.
+fn refine(coefficients: &[f64], roots: &mut [f64]) {
+    for root in roots.iter_mut() {
+        for _ in 0..4 {
+            let (p, dp) = evaluate(coefficients, *root);
+            if p == 0.0 || dp == 0.0 {
+                break;
+            }
+            let next = *root - p / dp;
+            if evaluate(coefficients, next).0.abs() >= p.abs() {
+                break;
+            }
+            *root = next;
+        }
+    }
+    roots.sort_by(f64::total_cmp);
+}
+
+/// Roots of `a x^3 + b x^2 + c x + d`, falling back to lower degrees if `a` is zero.
+pub fn solve_cubic(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    if a == 0.0 {
+        return solve_quadratic(b, c, d);
+    }
+    let (b, c, d) = (b / a, c / a, d / a);
+    // depressed cubic y^3 + p y + q with x = y - b / 3
+    let shift = b / 3.0;
+    let p = c - b * shift;
+    let q = d - c * shift + 2.0 * shift * shift * shift;
+    let mut roots = if p == 0.0 {
+        vec![(-q).cbrt()]
+    } else {
+        let delta = (q / 2.0).powi(2) + (p / 3.0).powi(3);
+        if delta > 0.0 {
+            // one real root
+            let u = (-q / 2.0 + delta.sqrt().copysign(-q)).cbrt();
+            vec![u - p / (3.0 * u)]
+        } else {
+            // three real roots, by the trigonometric method
+            let m = 2.0 * (-p / 3.0).sqrt();
+            let theta = (3.0 * q / (p * m)).clamp(-1.0, 1.0).acos() / 3.0;
+            (0..3)
+                .map(|k| m * (theta - 2.0 * std::f64::consts::PI * k as f64 / 3.0).cos())
+                .collect()
+        }
+    };
+    for root in &mut roots {
+        *root -= shift;
+    }
+    refine(&[1.0, b, c, d], &mut roots);
+    roots
+}
+
+/// Roots of `a x^4 + b x^3 + c x^2 + d x + e` by Ferrari's method,
+/// falling back to lower degrees if `a` is zero.
+pub fn solve_quartic(a: f64, b: f64, c: f64, d: f64, e: f64) -> Vec<f64> {
+    if a == 0.0 {
+        return solve_cubic(b, c, d, e);
+    }
+    let (b, c, d, e) = (b / a, c / a, d / a, e / a);
+    // depressed quartic y^4 + p y^2 + q y + r with x = y - b / 4
+    let shift = b / 4.0;
+    let p = c - 6.0 * shift * shift;
+    let q = d - 2.0 * c * shift + 8.0 * shift.powi(3);
+    let r = e - d * shift + c * shift * shift - 3.0 * shift.powi(4);
+    let scale = 1.0 + p.abs() + r.abs().sqrt();
+    let mut roots = Vec::with_capacity(4);
+    if q.abs() <= 1e-12 * scale * scale * scale.sqrt() {
+        // biquadratic in y^2
+        for y2 in solve_quadratic(1.0, p, r) {
+            if y2 >= 0.0 {
+                roots.extend([-y2.sqrt(), y2.sqrt()]);
+            }
+        }
+    } else {
+        // a positive root m of the resolvent cubic factors the quartic into two quadratics
+        // (y^2 + s y + t1)(y^2 - s y + t2) with s = sqrt(m)
+        let m = solve_cubic(1.0, 2.0 * p, p * p - 4.0 * r, -q * q)
+            .into_iter()
+            .fold(0.0, f64::max);
+        if m > 0.0 {
+            let s = m.sqrt();
+            let t = (p + m) / 2.0;
+            let u = q / (2.0 * s);
+            roots.extend(solve_quadratic(1.0, s, t - u));
+            roots.extend(solve_quadratic(1.0, -s, t + u));
+        }
+    }
+    for root in &mut roots {
+        *root -= shift;
+    }
+    refine(&[1.0, b, c, d, e], &mut roots);
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::polynomial::{solve_cubic, solve_quadratic, solve_quartic};
+
+    fn assert_roots(actual: Vec<f64>, expected: &[f64]) {
+        assert_eq!(actual.len(), expected.len(), "{actual:?} != {expected:?}");
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-9, "{actual:?} != {expected:?}");
+        }
+    }
+
+    #[test]
+    fn test_quadratic_and_cubic() {
+        assert_roots(solve_quadratic(1.0, -3.0, 2.0), &[1.0, 2.0]);
+        assert_roots(solve_quadratic(1.0, 0.0, 1.0), &[]);
+        assert_roots(solve_quadratic(0.0, 2.0, -1.0), &[0.5]);
+        // roots of very different magnitude
+        assert_roots(solve_quadratic(1.0, -1e9, 1.0), &[1e-9, 1e9]);
+        // (x + 1)(x - 2)(x - 5)
+        assert_roots(solve_cubic(2.0, -12.0, 6.0, 20.0), &[-1.0, 2.0, 5.0]);
+        // (x - 1)(x^2 + 1)
+        assert_roots(solve_cubic(1.0, -1.0, 1.0, -1.0), &[1.0]);
+        assert_roots(solve_cubic(1.0, 0.0, 0.0, -8.0), &[2.0]);
+    }
+
+    #[test]
+    fn test_quartic() {
+        // (x - 1)(x - 2)(x - 3)(x - 4)
+        assert_roots(
+            solve_quartic(1.0, -10.0, 35.0, -50.0, 24.0),
+            &[1.0, 2.0, 3.0, 4.0],
+        );
+        // (x^2 - 1)(x^2 - 4), biquadratic
+        assert_roots(
+            solve_quartic(1.0, 0.0, -5.0, 0.0, 4.0),
+            &[-2.0, -1.0, 1.0, 2.0],
+        );
+        // (x - 0.5)(x + 3)(x^2 + 1)
+        assert_roots(solve_quartic(1.0, 2.5, -0.5, 2.5, -1.5), &[-3.0, 0.5]);
+        assert_roots(solve_quartic(1.0, 0.0, 0.0, 0.0, 1.0), &[]);
+        // roots far from the origin, like a ray from far away hitting a torus
+        let roots: Vec<f64> = [1000.0, 1000.5, 1001.5, 1002.0].to_vec();
+        let [a, b, c, d] = [roots[0], roots[1], roots[2], roots[3]];
+        let coefficients = [
+            1.0,
+            -(a + b + c + d),
+            a * b + a * c + a * d + b * c + b * d + c * d,
+            -(a * b * c + a * b * d + a * c * d + b * c * d),
+            a * b * c * d,
+        ];
+        let solved = solve_quartic(
+            coefficients[0],
+            coefficients[1],
+            coefficients[2],
+            coefficients[3],
+            coefficients[4],
+        );
+        assert_eq!(solved.len(), 4);
+        for (s, r) in solved.iter().zip(&roots) {
+            assert!((s - r).abs() < 1e-3, "{solved:?}");
+        }
+    }
+}
@@ -1,15 +1,130 @@
-use crate::ppm::Error::IOError;
-use crate::types::Pixel;
-use std::fs::OpenOptions;
-use std::io::Write;
+#[cfg(feature = "exr")]
+use crate::exr::{encode_exr, ExrChannels, ExrCompression, ExrLayer};
+#[cfg(feature = "png")]
+use crate::png::PngOptions;
+use crate::ppm::Error::{
+    IOError, InvalidHeader, InvalidMagic, InvalidMaxValue, InvalidSample, SampleOutOfRange,
+    TruncatedData, UnsupportedFormat,
+};
+use crate::types::{NumColorRatio, Pixel};
+use std::fmt::{Display, Formatter};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
 use std::path::Path;
-use std::{io, ops, slice};
+use std::{fmt, fs, io, ops, slice};
 
 pub type ImageSize = u32;
 
 pub type ColorChannel = u8;
 
-const PIXEL_DEPTH: usize = 255;
+const MAX_PIXEL_DEPTH: u32 = 65535;
+
+/// How pixel samples are stored in a PPM file.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum PpmEncoding {
+    /// plain text samples (P3)
+    #[default]
+    Ascii,
+    /// raw big-endian samples (P6)
+    Binary,
+}
+
+/// Bits per color channel in the output file.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum BitDepth {
+    #[default]
+    Eight,
+    Sixteen,
+}
+
+impl BitDepth {
+    pub fn max_value(&self) -> u16 {
+        match self {
+            BitDepth::Eight => 255,
+            BitDepth::Sixteen => 65535,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SaveOptions {
+    pub encoding: PpmEncoding,
+    pub depth: BitDepth,
+}
+
+/// Convert a normalized color ratio to an integer sample in range `0..=max_value`.
+/// Values out of range [0, 1] are clamped.
+pub fn quantize(v: NumColorRatio, max_value: u16) -> u16 {
+    (v.clamp(0.0, 1.0) * max_value as NumColorRatio).round() as u16
+}
+
+/// Image file formats supported by [`Image::export`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    Ppm,
+    #[cfg(feature = "png")]
+    Png,
+    #[cfg(feature = "hdr")]
+    Pfm,
+    #[cfg(feature = "hdr")]
+    Hdr,
+    #[cfg(feature = "exr")]
+    Exr,
+}
+
+impl ImageFormat {
+    /// Guess the image format from file extension, case-insensitively.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        Self::from_extension(path.extension()?.to_str()?)
+    }
+
+    /// Look up the image format by its file extension without the leading dot, case-insensitively.
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext.to_ascii_lowercase().as_str() {
+            "ppm" => Some(ImageFormat::Ppm),
+            #[cfg(feature = "png")]
+            "png" => Some(ImageFormat::Png),
+            #[cfg(feature = "hdr")]
+            "pfm" => Some(ImageFormat::Pfm),
+            #[cfg(feature = "hdr")]
+            "hdr" => Some(ImageFormat::Hdr),
+            #[cfg(feature = "exr")]
+            "exr" => Some(ImageFormat::Exr),
+            _ => None,
+        }
+    }
+
+    /// Whether the format stores linear values without clamping them to range [0, 1].
+    pub fn is_high_dynamic_range(&self) -> bool {
+        match self {
+            ImageFormat::Ppm => false,
+            #[cfg(feature = "png")]
+            ImageFormat::Png => false,
+            #[cfg(feature = "hdr")]
+            ImageFormat::Pfm | ImageFormat::Hdr => true,
+            #[cfg(feature = "exr")]
+            ImageFormat::Exr => true,
+        }
+    }
+}
+
+/// Largest number of pixels of rendered images, 16384 x 16384.
+pub const MAX_PIXELS: usize = 1 << 28;
+
+/// Check that an image of `width` x `height` pixels can be rendered, returning its pixel count.
+pub fn check_size(width: ImageSize, height: ImageSize) -> Result<usize, Error> {
+    if width == 0 || height == 0 {
+        return Err(Error::InvalidArgument(
+            "image width and height must be positive",
+        ));
+    }
+    match (width as usize).checked_mul(height as usize) {
+        Some(n) if n <= MAX_PIXELS => Ok(n),
+        _ => Err(Error::InvalidArgument(
+            "image has more than 16384 x 16384 pixels",
+        )),
+    }
+}
 
 pub struct Image<T: Pixel> {
     width: ImageSize,
@@ -46,7 +161,7 @@ impl<'a, T: Pixel> Iterator for MutableImageIterator<'a, T> {
 pub struct ImageIterator<'a, T: Pixel> {
     x: ImageSize,
     y: ImageSize,
-    n: ImageSize,
+    n: usize,
     img: &'a Image<T>,
 }
 
@@ -62,7 +177,7 @@ impl<'a, T: Pixel> Iterator for ImageIterator<'a, T> {
             self.y,
             self.img
                 .data
-                .get(self.n as usize)
+                .get(self.n)
                 .expect("expected pixel while iterating through image pixels"),
         );
         self.x += 1;
@@ -87,20 +202,20 @@ impl<T: Pixel> Image<T> {
         Image {
             width,
             height,
-            data: vec![T::black(); (width * height) as usize],
+            data: vec![T::black(); width as usize * height as usize],
         }
     }
 
-    pub fn iter(&self) -> ImageIterator<T> {
+    pub fn iter(&self) -> ImageIterator<'_, T> {
         ImageIterator {
             x: 0,
             y: 0,
             n: 0,
-            img: &self,
+            img: self,
         }
     }
 
-    pub fn iter_mut(&mut self) -> MutableImageIterator<T> {
+    pub fn iter_mut(&mut self) -> MutableImageIterator<'_, T> {
         MutableImageIterator {
             iter_mut: self.data.iter_mut(),
             width: self.width,
@@ -109,23 +224,197 @@ impl<T: Pixel> Image<T> {
         }
     }
 
+    /// Save the image as an ASCII 8-bit PPM file.
     pub fn save(&self, path: &Path) -> Result<(), Error> {
-        // TODO generalize this function to allow generate images with different color depth
-        let mut file = OpenOptions::new().write(true).create(true).open(path)?;
+        self.save_with(path, &SaveOptions::default())
+    }
 
-        // write file header
-        file.write(format!("P3\n{} {}\n{}\n", self.width, self.height, PIXEL_DEPTH).as_bytes())?;
+    /// Save the image as a PPM file with given encoding and color depth.
+    pub fn save_with(&self, path: &Path, options: &SaveOptions) -> Result<(), Error> {
+        let mut writer = create_file(path)?;
+        self.encode_ppm(&mut writer, options)?;
+        writer.flush()?;
+        Ok(())
+    }
 
-        // write pixels
+    /// Write the image in PPM format to `w`.
+    pub fn encode_ppm<W: Write>(&self, w: &mut W, options: &SaveOptions) -> io::Result<()> {
+        let magic = match options.encoding {
+            PpmEncoding::Ascii => "P3",
+            PpmEncoding::Binary => "P6",
+        };
+        let max_value = options.depth.max_value();
+        write!(
+            w,
+            "{}\n{} {}\n{}\n",
+            magic, self.width, self.height, max_value
+        )?;
         for (_, _, pix) in self.iter() {
-            file.write(format!("{} {} {}\n", pix.red8(), pix.green8(), pix.blue8()).as_bytes())?;
+            let rgb = [
+                quantize(pix.red(), max_value),
+                quantize(pix.green(), max_value),
+                quantize(pix.blue(), max_value),
+            ];
+            match (options.encoding, options.depth) {
+                (PpmEncoding::Ascii, _) => writeln!(w, "{} {} {}", rgb[0], rgb[1], rgb[2])?,
+                (PpmEncoding::Binary, BitDepth::Eight) => {
+                    w.write_all(&rgb.map(|v| v as u8))?;
+                }
+                (PpmEncoding::Binary, BitDepth::Sixteen) => {
+                    for v in rgb {
+                        w.write_all(&v.to_be_bytes())?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Save the image in the format implied by file extension of `path`,
+    /// using default options of that format.
+    pub fn export(&self, path: &Path) -> Result<(), Error> {
+        match ImageFormat::from_path(path) {
+            Some(format) => self.save_as(path, format),
+            None => Err(UnsupportedFormat(path.to_string_lossy().into_owned())),
         }
+    }
+
+    /// Save the image in given format, using default options of that format.
+    pub fn save_as(&self, path: &Path, format: ImageFormat) -> Result<(), Error> {
+        let mut writer = create_file(path)?;
+        self.encode_as(&mut writer, format)?;
+        writer.flush()?;
+        Ok(())
+    }
 
+    /// Write the image in given format to `w`, using default options of that format.
+    pub fn encode_as<W: Write>(&self, w: &mut W, format: ImageFormat) -> Result<(), Error> {
+        match format {
+            ImageFormat::Ppm => self.encode_ppm(w, &SaveOptions::default())?,
+            #[cfg(feature = "png")]
+            ImageFormat::Png => self.encode_png(w, &PngOptions::default())?,
+            #[cfg(feature = "hdr")]
+            ImageFormat::Pfm => self.encode_pfm(w)?,
+            #[cfg(feature = "hdr")]
+            ImageFormat::Hdr => self.encode_hdr(w)?,
+            #[cfg(feature = "exr")]
+            ImageFormat::Exr => {
+                let layer = ExrLayer {
+                    name: "",
+                    image: self,
+                    channels: ExrChannels::Rgb,
+                };
+                encode_exr(w, &[layer], ExrCompression::default())?
+            }
+        }
         Ok(())
     }
 
+    /// Load an image file in the format implied by file extension of `path`.
+    pub fn import(path: &Path) -> Result<Self, Error> {
+        match ImageFormat::from_path(path) {
+            Some(ImageFormat::Ppm) => Self::load(path),
+            #[cfg(feature = "png")]
+            Some(ImageFormat::Png) => Self::load_png(path),
+            #[cfg(feature = "hdr")]
+            Some(ImageFormat::Pfm) => Self::load_pfm(path),
+            #[cfg(feature = "hdr")]
+            Some(ImageFormat::Hdr) => Self::load_hdr(path),
+            #[cfg(feature = "exr")]
+            Some(ImageFormat::Exr) => Self::load_exr(path),
+            _ => Err(UnsupportedFormat(path.to_string_lossy().into_owned())),
+        }
+    }
+
+    /// Load a PPM image file. Both ASCII (P3) and binary (P6) variants are supported,
+    /// with any max value in range 1..=65535.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let data = fs::read(path)?;
+        Self::decode_ppm(&data)
+    }
+
+    /// Decode a PPM image from its file content.
+    pub fn decode_ppm(data: &[u8]) -> Result<Self, Error> {
+        let mut reader = PpmReader { data, pos: 0 };
+        let binary = match reader.magic()? {
+            [b'P', b'3'] => false,
+            [b'P', b'6'] => true,
+            magic => return Err(InvalidMagic(magic)),
+        };
+        let width = reader.header_value()?;
+        let height = reader.header_value()?;
+        let max_value = reader.header_value()?;
+        if width == 0 || height == 0 {
+            return Err(InvalidHeader("image size must be positive"));
+        }
+        if max_value == 0 || max_value > MAX_PIXEL_DEPTH {
+            return Err(InvalidMaxValue(max_value));
+        }
+        let n = (width as usize)
+            .checked_mul(height as usize)
+            .ok_or(InvalidHeader("image size is too large"))?;
+        if binary {
+            // exactly one whitespace character separates the header and the raster
+            match reader.next_byte() {
+                Some(c) if c.is_ascii_whitespace() => {}
+                Some(_) => return Err(InvalidHeader("expected whitespace after max value")),
+                None => return Err(TruncatedData),
+            }
+            let sample_size = if max_value > 255 { 2 } else { 1 };
+            // reject impossible sizes before allocating the pixel buffer
+            if reader.remaining() / 3 / sample_size < n {
+                return Err(TruncatedData);
+            }
+        } else if reader.remaining() / 2 / 3 < n {
+            // every sample needs at least a separator and a digit
+            return Err(TruncatedData);
+        }
+        let mut data = Vec::with_capacity(n);
+        for _ in 0..n {
+            let mut rgb = [0u32; 3];
+            for v in rgb.iter_mut() {
+                *v = if binary {
+                    reader.binary_sample(max_value)?
+                } else {
+                    reader.ascii_sample()?
+                };
+                if *v > max_value {
+                    return Err(SampleOutOfRange {
+                        value: *v,
+                        max_value,
+                    });
+                }
+            }
+            let [r, g, b] = rgb;
+            data.push(if max_value == 255 {
+                T::from_rgb8(r as ColorChannel, g as ColorChannel, b as ColorChannel)
+            } else {
+                let m = max_value as NumColorRatio;
+                T::from_rgb_normalized(
+                    r as NumColorRatio / m,
+                    g as NumColorRatio / m,
+                    b as NumColorRatio / m,
+                )
+            });
+        }
+        Ok(Image {
+            width,
+            height,
+            data,
+        })
+    }
+
     fn index(&self, x: ImageSize, y: ImageSize) -> usize {
-        (x + y * self.width) as usize
+        x as usize + y as usize * self.width as usize
+    }
+
+    /// Create a new image of the same size by applying `f` to every pixel.
+    pub fn map<U: Pixel, F: Fn(&T) -> U>(&self, f: F) -> Image<U> {
+        Image {
+            width: self.width,
+            height: self.height,
+            data: self.data.iter().map(f).collect(),
+        }
     }
 
     pub fn set_pixel(&mut self, x: ImageSize, y: ImageSize, pixel: T) {
@@ -133,7 +422,7 @@ impl<T: Pixel> Image<T> {
         self.data[i] = pixel;
     }
 
-    pub fn get_pixel(&mut self, x: ImageSize, y: ImageSize) -> T {
+    pub fn get_pixel(&self, x: ImageSize, y: ImageSize) -> T {
         let i = self.index(x, y);
         self.data[i]
     }
@@ -159,11 +448,156 @@ impl<T: Pixel> ops::AddAssign<Self> for Image<T> {
     }
 }
 
+/// Create or truncate a file for writing an image.
+pub(crate) fn create_file(path: &Path) -> io::Result<BufWriter<File>> {
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)?;
+    Ok(BufWriter::new(file))
+}
+
+/// Cursor over the content of a PPM file.
+struct PpmReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> PpmReader<'a> {
+    fn next_byte(&mut self) -> Option<u8> {
+        let c = *self.data.get(self.pos)?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    fn magic(&mut self) -> Result<[u8; 2], Error> {
+        match (self.next_byte(), self.next_byte()) {
+            (Some(a), Some(b)) => Ok([a, b]),
+            _ => Err(TruncatedData),
+        }
+    }
+
+    /// Skip whitespaces and comments. A comment starts with `#` and ends at the end of line.
+    fn skip_whitespace(&mut self) {
+        while let Some(&c) = self.data.get(self.pos) {
+            if c == b'#' {
+                while let Some(c) = self.next_byte() {
+                    if c == b'\n' || c == b'\r' {
+                        break;
+                    }
+                }
+            } else if c.is_ascii_whitespace() {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Read a decimal integer, returning `None` if there is no digit at current position.
+    fn decimal(&mut self) -> Result<Option<u32>, Error> {
+        self.skip_whitespace();
+        let start = self.pos;
+        let mut value: u32 = 0;
+        while let Some(&c) = self.data.get(self.pos) {
+            if !c.is_ascii_digit() {
+                break;
+            }
+            value = value
+                .checked_mul(10)
+                .and_then(|v| v.checked_add((c - b'0') as u32))
+                .ok_or(InvalidHeader("integer overflow"))?;
+            self.pos += 1;
+        }
+        if self.pos == start {
+            return Ok(None);
+        }
+        Ok(Some(value))
+    }
+
+    fn header_value(&mut self) -> Result<u32, Error> {
+        match self.decimal()? {
+            Some(v) => Ok(v),
+            None if self.pos >= self.data.len() => Err(TruncatedData),
+            None => Err(InvalidHeader("expected a decimal integer")),
+        }
+    }
+
+    fn ascii_sample(&mut self) -> Result<u32, Error> {
+        match self.decimal()? {
+            Some(v) => Ok(v),
+            None if self.pos >= self.data.len() => Err(TruncatedData),
+            None => Err(InvalidSample),
+        }
+    }
+
+    fn binary_sample(&mut self, max_value: u32) -> Result<u32, Error> {
+        let hi = self.next_byte().ok_or(TruncatedData)? as u32;
+        if max_value <= 255 {
+            return Ok(hi);
+        }
+        // 16-bit samples are stored in big endian
+        let lo = self.next_byte().ok_or(TruncatedData)? as u32;
+        Ok((hi << 8) | lo)
+    }
+}
+
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum Error {
     IOError(io::Error),
+    /// the file does not start with a supported magic number
+    InvalidMagic([u8; 2]),
+    /// malformed file header
+    InvalidHeader(&'static str),
+    /// max value is not in range 1..=65535
+    InvalidMaxValue(u32),
+    /// the file ends before all pixels are read
+    TruncatedData,
+    /// a non-numeric token was found in pixel data
+    InvalidSample,
+    /// malformed or corrupted pixel data
+    InvalidData(&'static str),
+    /// a sample value is greater than max value declared in the header
+    SampleOutOfRange {
+        value: u32,
+        max_value: u32,
+    },
+    /// the file name does not have a known image file extension
+    UnsupportedFormat(String),
+    /// images that are expected to have the same size differ in size
+    SizeMismatch,
+    /// the arguments can not be encoded as an image file
+    InvalidArgument(&'static str),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            IOError(e) => write!(f, "I/O error: {e}"),
+            Error::InvalidMagic(magic) => write!(f, "invalid magic number {magic:?}"),
+            Error::InvalidHeader(msg) => write!(f, "invalid header: {msg}"),
+            Error::InvalidMaxValue(v) => write!(f, "invalid max value {v}"),
+            Error::TruncatedData => write!(f, "truncated pixel data"),
+            Error::InvalidSample => write!(f, "invalid sample in pixel data"),
+            Error::InvalidData(msg) => write!(f, "invalid pixel data: {msg}"),
+            Error::SampleOutOfRange { value, max_value } => {
+                write!(f, "sample {value} exceeds max value {max_value}")
+            }
+            Error::UnsupportedFormat(name) => write!(f, "unsupported image format: {name}"),
+            Error::SizeMismatch => write!(f, "image sizes do not match"),
+            Error::InvalidArgument(msg) => write!(f, "invalid argument: {msg}"),
+        }
+    }
 }
 
+impl std::error::Error for Error {}
+
 impl From<io::Error> for Error {
     fn from(value: io::Error) -> Self {
         IOError(value)
@@ -173,7 +607,7 @@ impl From<io::Error> for Error {
 #[cfg(test)]
 mod tests {
     use crate::ppm::{ImageSize, Pixel};
-    use crate::types::PixelU8;
+    use crate::types::{PixelF64, PixelU8};
     use crate::{ppm, testing};
     use std::fs;
     use std::path::Path;
@@ -217,4 +651,121 @@ mod tests {
     fn test_ppm_1_300x200() {
         do_test_ppm_1(300, 200);
     }
+
+    #[test]
+    fn test_save_p6_roundtrip() {
+        let mut img: ppm::Image<PixelF64> = ppm::Image::new(3, 2);
+        for (x, y, pix) in img.iter_mut() {
+            *pix = PixelF64::new(x as f64 / 2.0, y as f64, 0.25);
+        }
+        for depth in [ppm::BitDepth::Eight, ppm::BitDepth::Sixteen] {
+            let options = ppm::SaveOptions {
+                encoding: ppm::PpmEncoding::Binary,
+                depth,
+            };
+            let mut data = Vec::new();
+            img.encode_ppm(&mut data, &options).expect("encode image");
+            let max_value = depth.max_value();
+            let header = format!("P6\n3 2\n{max_value}\n");
+            let bytes_per_sample = if max_value > 255 { 2 } else { 1 };
+            assert!(data.starts_with(header.as_bytes()));
+            assert_eq!(data.len(), header.len() + 3 * 2 * 3 * bytes_per_sample);
+            let loaded: ppm::Image<PixelF64> = ppm::Image::decode_ppm(&data).expect("decode");
+            let tolerance = 0.5 / max_value as f64;
+            for ((_, _, a), (_, _, b)) in img.iter().zip(loaded.iter()) {
+                assert!((a.red() - b.red()).abs() <= tolerance);
+                assert!((a.green() - b.green()).abs() <= tolerance);
+                assert!((a.blue() - b.blue()).abs() <= tolerance);
+            }
+        }
+    }
+
+    #[test]
+    fn test_load_p3() {
+        let img: ppm::Image<PixelU8> =
+            ppm::Image::load(&testing::path("1.300x200.ppm")).expect("load image");
+        assert_eq!(img.get_width(), 300);
+        assert_eq!(img.get_height(), 200);
+        for (x, y, pix) in img.iter() {
+            let expected = PixelU8::from_rgb_normalized(x as f64 / 300.0, y as f64 / 200.0, 0.0);
+            assert_eq!(pix.red8(), expected.red8());
+            assert_eq!(pix.green8(), expected.green8());
+            assert_eq!(pix.blue8(), expected.blue8());
+        }
+    }
+
+    #[test]
+    fn test_load_p6_16bit_with_comments() {
+        let mut data = b"P6 # binary\n2\t1\r\n# max value\n65535\n".to_vec();
+        data.extend_from_slice(&[0xff, 0xff, 0x80, 0x00, 0x00, 0x00]);
+        data.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0xff, 0xff]);
+        let img: ppm::Image<PixelF64> = ppm::Image::decode_ppm(&data).expect("decode image");
+        assert_eq!((img.get_width(), img.get_height()), (2, 1));
+        let p = img.get_pixel(0, 0);
+        assert_eq!(p.red(), 1.0);
+        assert!((p.green() - 0x8000 as f64 / 65535.0).abs() < 1e-12);
+        assert_eq!(p.blue(), 0.0);
+        assert_eq!(img.get_pixel(1, 0).blue(), 1.0);
+    }
+
+    #[test]
+    fn test_load_errors() {
+        type Img = ppm::Image<PixelU8>;
+        assert!(matches!(
+            Img::decode_ppm(b"P5\n1 1\n255\n\0"),
+            Err(ppm::Error::InvalidMagic(_))
+        ));
+        assert!(matches!(
+            Img::decode_ppm(b"P3\n2 1\n255\n1 2 3 4 5"),
+            Err(ppm::Error::TruncatedData)
+        ));
+        assert!(matches!(
+            Img::decode_ppm(b"P6\n2 1\n255\n\x01\x02\x03"),
+            Err(ppm::Error::TruncatedData)
+        ));
+        assert!(matches!(
+            Img::decode_ppm(b"P3\n1 1\n15\n1 16 3"),
+            Err(ppm::Error::SampleOutOfRange {
+                value: 16,
+                max_value: 15
+            })
+        ));
+        assert!(matches!(
+            Img::decode_ppm(b"P3\n1 1\n65536\n1 1 1"),
+            Err(ppm::Error::InvalidMaxValue(65536))
+        ));
+        assert!(matches!(
+            Img::decode_ppm(b"P3\n1 x\n255\n1 1 1"),
+            Err(ppm::Error::InvalidHeader(_))
+        ));
+    }
+
+    #[test]
+    fn test_ppm_invalid_size() {
+        type Img = ppm::Image<PixelU8>;
+        // impossible sizes are rejected before allocating pixels
+        for header in ["100000 100000", "4000000000 4000000000"] {
+            for magic in ["P3", "P6"] {
+                let data = format!("{magic}\n{header}\n255\n1 2 3");
+                assert!(matches!(
+                    Img::decode_ppm(data.as_bytes()),
+                    Err(ppm::Error::TruncatedData)
+                ));
+            }
+        }
+        let img = Img::decode_ppm(b"P3\n1 1\n255\n1 2 3").expect("decode");
+        assert_eq!(img.get_pixel(0, 0).blue(), 3.0 / 255.0);
+    }
+
+    #[test]
+    fn test_check_size() {
+        assert_eq!(ppm::check_size(640, 480).unwrap(), 640 * 480);
+        assert_eq!(ppm::check_size(16384, 16384).unwrap(), ppm::MAX_PIXELS);
+        for (width, height) in [(0, 1), (1, 0), (16385, 16384), (100000, 100000)] {
+            assert!(matches!(
+                ppm::check_size(width, height),
+                Err(ppm::Error::InvalidArgument(_))
+            ));
+        }
+    }
 }
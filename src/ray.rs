@@ -1,5 +1,6 @@
 use crate::types::{PositionVec, Time};
 
+#[derive(Copy, Clone, Debug)]
 pub struct Ray {
     pub origin: PositionVec,
     pub direction: PositionVec,
@@ -1,14 +1,55 @@
+use crate::framebuffer::AccumulationBuffer;
+use crate::ppm;
 use crate::ppm::Image;
 use crate::scene::{
     AbsoluteSphereScene, Camera, DemoSkyScene, Hittable, NormVectorVisualizedSphereScene, Scene,
     SkiedWorld,
 };
-use crate::types::{NumPosition, Pixel, PositionVec};
-use rand::Rng;
-use std::path::Path;
+use crate::tonemap::DisplayTransform;
+use crate::types::{NumPosition, Pixel, PixelF64, PositionVec};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
 use std::sync::mpsc::{channel, Sender};
+#[cfg(feature = "parallel")]
 use std::thread;
-use tracing::{debug, info};
+use tracing::debug;
+#[cfg(feature = "parallel")]
+use tracing::info;
+
+#[derive(Debug)]
+pub enum RenderError {
+    /// no sample was taken, e.g. the requested sample count is zero
+    NoSamples,
+    /// failed to save the rendered image
+    Image(ppm::Error),
+    /// the output file exists and overwriting is not allowed
+    OutputExists(PathBuf),
+}
+
+impl Display for RenderError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RenderError::NoSamples => write!(f, "no sample was taken"),
+            RenderError::Image(e) => write!(f, "{e}"),
+            RenderError::OutputExists(path) => {
+                write!(f, "output file {} already exists", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for RenderError {}
+
+impl From<ppm::Error> for RenderError {
+    fn from(value: ppm::Error) -> Self {
+        RenderError::Image(value)
+    }
+}
+
+/// Sensor width of the default camera, giving 1/256 units wide pixels at 640x480.
+pub const DEFAULT_SENSOR_WIDTH: NumPosition = 2.5;
 
 pub struct Renderer<T>
 where
@@ -16,79 +57,114 @@ where
 {
     camera: Camera,
     scene: T,
+    /// applied when converting linear radiance to display values
+    display: DisplayTransform,
+    /// number of worker threads, one per CPU core if not set
+    threads: Option<usize>,
+    /// seed of the random sampling pattern, random on every render if not set
+    seed: Option<u64>,
 }
 
 impl<T: Scene> Renderer<T> {
-    pub fn render(&self, samples: usize) {
-        let (sender, receiver) = channel::<Image<T::T>>();
+    pub fn new(camera: Camera, scene: T) -> Self {
+        Renderer {
+            camera,
+            scene,
+            display: DisplayTransform::default(),
+            threads: None,
+            seed: None,
+        }
+    }
+
+    /// Set the number of worker threads. Ignored without the `parallel` feature.
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = Some(threads.max(1));
+    }
+
+    /// Make the sampling pattern reproducible. Images rendered with the same seed
+    /// and thread count are identical.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
+    }
+
+    pub fn get_camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    pub fn set_display_transform(&mut self, display: DisplayTransform) {
+        self.display = display;
+    }
+
+    pub fn get_display_transform(&self) -> &DisplayTransform {
+        &self.display
+    }
+
+    /// Render the scene with `samples` samples per pixel,
+    /// returning the averaged linear radiance of each pixel.
+    pub fn render_linear(&self, samples: usize) -> Result<Image<PixelF64>, RenderError> {
+        let (sender, receiver) = channel::<AccumulationBuffer>();
+        #[cfg(feature = "parallel")]
         thread::scope(move |s| {
-            {
-                let mut samples = samples;
-                let thread_cnt = num_cpus::get();
-                info!("Worker threads: {thread_cnt}");
-                let samples_per_thread = samples / thread_cnt;
-                for i in 0..thread_cnt {
-                    let worker = Worker {
-                        id: i,
-                        renderer: &self,
-                        ch: sender.clone(),
-                        iter_count: if i == thread_cnt - 1 {
-                            samples_per_thread + samples % thread_cnt
-                        } else {
-                            samples_per_thread
-                        },
-                    };
-                    s.spawn(move || worker.run());
-                    samples -= samples_per_thread;
-                }
+            let mut samples = samples;
+            let thread_cnt = self.threads.unwrap_or_else(num_cpus::get);
+            info!("Worker threads: {thread_cnt}");
+            let samples_per_thread = samples / thread_cnt;
+            for i in 0..thread_cnt {
+                let worker = Worker {
+                    id: i,
+                    renderer: self,
+                    ch: sender.clone(),
+                    iter_count: if i == thread_cnt - 1 {
+                        samples_per_thread + samples % thread_cnt
+                    } else {
+                        samples_per_thread
+                    },
+                };
+                s.spawn(move || worker.run());
+                samples -= samples_per_thread;
             }
-            s.spawn(move || {
-                // TODO extract SSAA logic and bypass this code when SSAA is not enabled
-                let sample_factor = 1.0 / samples as f64;
-                let mut sum_image: Image<T::T> = Image::new(self.camera.width, self.camera.height);
-                let mut has_image = false;
-                for mut image in receiver {
-                    image *= sample_factor;
-                    sum_image += image;
-                    has_image = true;
-                }
-                if !has_image {
-                    panic!("no image generated");
-                }
-                sum_image
-                    .save(Path::new("result.ppm"))
-                    .expect("failed to save image file");
-            });
         });
+        #[cfg(not(feature = "parallel"))]
+        Worker {
+            id: 0,
+            renderer: self,
+            ch: sender,
+            iter_count: samples,
+        }
+        .run();
+        let mut buffer = AccumulationBuffer::new(self.camera.width, self.camera.height);
+        for worker_buffer in receiver {
+            buffer.merge(&worker_buffer)?;
+        }
+        if buffer.total_samples() == 0 {
+            return Err(RenderError::NoSamples);
+        }
+        Ok(buffer.resolve())
+    }
+
+    /// Render the scene with `samples` samples per pixel,
+    /// returning the image after applying the display transform.
+    pub fn render(&self, samples: usize) -> Result<Image<T::T>, RenderError> {
+        Ok(self.display.apply(&self.render_linear(samples)?))
     }
 }
 
 pub fn new_demo_renderer<T: Pixel>() -> Renderer<DemoSkyScene<T>> {
     Renderer {
-        camera: Camera {
-            pos: PositionVec::zeros(),
-            // wh_ratio: 0.0,
-            width: 640,
-            height: 480,
-            pixel_width: 0.125,
-            pixel_height: 0.125,
-            focus_length: 1 as NumPosition,
-        },
+        camera: Camera::new(640, 480, 80.0, 1.0),
+        display: DisplayTransform::default(),
+        threads: None,
+        seed: None,
         scene: DemoSkyScene::new(),
     }
 }
 
 pub fn new_sphere_renderer<T: Pixel>() -> Renderer<AbsoluteSphereScene<T>> {
     Renderer {
-        camera: Camera {
-            pos: PositionVec::zeros(),
-            // wh_ratio: 0.0,
-            width: 640,
-            height: 480,
-            pixel_width: 1.0 / 256.0,
-            pixel_height: 1.0 / 256.0,
-            focus_length: 1 as NumPosition,
-        },
+        camera: Camera::new(640, 480, DEFAULT_SENSOR_WIDTH, 1.0),
+        display: DisplayTransform::default(),
+        threads: None,
+        seed: None,
         scene: AbsoluteSphereScene::new(PositionVec::new(0.0, 0.0, -1.0), 0.5, T::black()),
     }
 }
@@ -96,40 +172,40 @@ pub fn new_sphere_renderer<T: Pixel>() -> Renderer<AbsoluteSphereScene<T>> {
 pub fn new_norm_visualized_sphere_renderer<T: Pixel>(
 ) -> Renderer<NormVectorVisualizedSphereScene<T>> {
     Renderer {
-        camera: Camera {
-            pos: PositionVec::zeros(),
-            // wh_ratio: 0.0,
-            width: 640,
-            height: 480,
-            pixel_width: 1.0 / 256.0,
-            pixel_height: 1.0 / 256.0,
-            focus_length: 1 as NumPosition,
-        },
+        camera: Camera::new(640, 480, DEFAULT_SENSOR_WIDTH, 1.0),
+        display: DisplayTransform::default(),
+        threads: None,
+        seed: None,
         scene: NormVectorVisualizedSphereScene::new(PositionVec::new(0.0, 0.0, -1.0), 0.5),
     }
 }
 
 pub fn new_skied_world<'a, T: Pixel>(
-    objects: Vec<&'a dyn Hittable<T>>,
+    objects: Vec<&'a dyn Hittable>,
 ) -> Renderer<SkiedWorld<'a, T>> {
-    Renderer {
-        camera: Camera {
-            pos: PositionVec::zeros(),
-            // wh_ratio: 0.0,
-            width: 640,
-            height: 480,
-            pixel_width: 1.0 / 256.0,
-            pixel_height: 1.0 / 256.0,
-            focus_length: 1 as NumPosition,
-        },
-        scene: SkiedWorld { objects },
-    }
+    Renderer::new(
+        Camera::new(640, 480, DEFAULT_SENSOR_WIDTH, 1.0),
+        SkiedWorld::new(objects),
+    )
+}
+
+/// One step of the SplitMix64 generator, mapping nearby inputs to unrelated outputs.
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Seed of the random stream of worker `id`, unrelated to those of other workers and seeds.
+fn worker_seed(seed: u64, id: usize) -> u64 {
+    splitmix64(splitmix64(seed).wrapping_add(id as u64))
 }
 
 struct Worker<'a, T: Scene + Send + Sync> {
     id: usize,
     renderer: &'a Renderer<T>,
-    ch: Sender<Image<T::T>>,
+    ch: Sender<AccumulationBuffer>,
     iter_count: usize,
 }
 
@@ -139,17 +215,181 @@ impl<'a, T: Scene> Worker<'a, T> {
             "Worker started (id: {}), iter_count: {}",
             self.id, self.iter_count
         );
-        // TODO make SSAA image generation deterministic
-        let mut rng = rand::thread_rng();
+        let mut rng = match self.renderer.seed {
+            // every worker draws from its own stream
+            Some(seed) => StdRng::seed_from_u64(worker_seed(seed, self.id)),
+            None => StdRng::from_entropy(),
+        };
+        let scene = &self.renderer.scene;
+        let camera = &self.renderer.camera;
+        let mut buffer = AccumulationBuffer::new(camera.width, camera.height);
         for _ in 0..self.iter_count {
-            let scene = &self.renderer.scene;
-            let camera = &self.renderer.camera;
             let rnd_x: f64 = rng.gen();
             let rnd_y: f64 = rng.gen();
-            let image = camera.get_image(scene, rnd_x, rnd_y);
-            self.ch
-                .send(image)
-                .expect("failed to write worker result image to channel");
+            let image = camera.get_image(scene, rnd_x, rnd_y, &mut rng);
+            buffer
+                .add_image(&image)
+                .expect("sampled image has the same size as the camera");
         }
+        self.ch
+            .send(buffer)
+            .expect("failed to write worker result buffer to channel");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::light::Light;
+    use crate::material::{Diffuse, Glass};
+    use crate::objects::plane::Plane;
+    use crate::objects::shaded::Shaded;
+    use crate::objects::sphere::Sphere;
+    use crate::renderer::{new_demo_renderer, worker_seed, RenderError, Renderer};
+    use crate::scene::{Background, Camera, Hittable, SkiedWorld};
+    use crate::types::{Pixel, PixelF64, PixelU8, PositionVec};
+    use std::f64::consts::PI;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_render_returns_image() {
+        let renderer = new_demo_renderer::<PixelU8>();
+        let image = renderer.render(3).expect("render");
+        assert_eq!((image.get_width(), image.get_height()), (640, 480));
+        // the sky is fully saturated in blue channel
+        assert_eq!(image.get_pixel(0, 0).blue8(), 255);
+        let renderer = new_demo_renderer::<PixelF64>();
+        assert!(matches!(renderer.render(0), Err(RenderError::NoSamples)));
+    }
+
+    #[test]
+    fn test_seeded_render_is_reproducible() {
+        let sphere = Sphere {
+            center: PositionVec::new(0.0, 0.0, -1.0),
+            radius: 0.5,
+        };
+        let render = |seed| {
+            let objects: Vec<&dyn Hittable> = vec![&sphere];
+            let mut renderer = Renderer::new(
+                Camera::new(16, 12, 2.5, 1.0),
+                SkiedWorld::<PixelF64>::new(objects),
+            );
+            renderer.set_threads(2);
+            renderer.set_seed(seed);
+            let image = renderer.render_linear(4).expect("render");
+            image.iter().map(|(_, _, p)| p.red()).collect::<Vec<_>>()
+        };
+        assert_eq!(render(42), render(42));
+        assert_ne!(render(42), render(43));
+    }
+
+    #[test]
+    fn test_worker_seeds_do_not_overlap() {
+        let mut seeds: Vec<u64> = (0..16)
+            .flat_map(|seed| (0..16).map(move |id| worker_seed(seed, id)))
+            .collect();
+        seeds.sort_unstable();
+        seeds.dedup();
+        assert_eq!(seeds.len(), 16 * 16);
+    }
+
+    #[test]
+    fn test_path_tracing() {
+        let sphere = Sphere {
+            center: PositionVec::new(0.0, 0.0, -2.0),
+            radius: 0.5,
+        };
+        let render = |object: &dyn Hittable| {
+            let mut world = SkiedWorld::<PixelF64>::new(vec![object]);
+            world.set_background(Background::Color(PixelF64::new(1.0, 1.0, 1.0)));
+            let mut renderer = Renderer::new(Camera::new(8, 8, 1.0, 1.0), world);
+            renderer.set_threads(1);
+            renderer.set_seed(5);
+            renderer.render_linear(4).expect("render").get_pixel(4, 4)
+        };
+        // every ray scattered off a convex diffuse object reaches the uniform background
+        let diffuse = Shaded {
+            object: &sphere,
+            material: Arc::new(Diffuse {
+                albedo: PixelF64::new(0.5, 0.25, 1.0),
+            }),
+        };
+        assert_eq!(render(&diffuse), PixelF64::new(0.5, 0.25, 1.0));
+        // and a glass sphere neither absorbs nor emits light
+        let glass = Shaded {
+            object: &sphere,
+            material: Arc::new(Glass { ior: 1.5 }),
+        };
+        let color = render(&glass);
+        assert!((color.red() - 1.0).abs() < 1e-9, "{color:?}");
+    }
+
+    #[test]
+    fn test_direct_light() {
+        let ground = Shaded {
+            object: Plane {
+                point: PositionVec::new(0.0, 0.0, -2.0),
+                normal: PositionVec::z(),
+            },
+            material: Arc::new(Diffuse {
+                albedo: PixelF64::new(0.5, 0.5, 0.5),
+            }),
+        };
+        // at 45 degrees, behind the camera on its left
+        let direction = PositionVec::new(1.0, 0.0, -1.0).normalize();
+        let radiance = PI * 2f64.sqrt();
+        let render = |objects| {
+            let mut world = SkiedWorld::<PixelF64>::new(objects);
+            world.set_background(Background::Color(PixelF64::black()));
+            world.set_lights(vec![Light::Distant {
+                direction,
+                radiance: PixelF64::new(radiance, radiance, radiance),
+            }]);
+            let mut renderer = Renderer::new(Camera::new(8, 8, 1.0, 1.0), world);
+            renderer.set_threads(1);
+            renderer.set_seed(7);
+            renderer.render_linear(4).expect("render").get_pixel(4, 4)
+        };
+        let lit = render(vec![&ground]);
+        assert!((lit.red() - 0.5).abs() < 1e-12, "{lit:?}");
+        // in the shadow of a black sphere between the ground and the light
+        let sphere = Shaded {
+            object: Sphere {
+                center: PositionVec::new(0.0, 0.0, -2.0) - direction * 1.5,
+                radius: 0.3,
+            },
+            material: Arc::new(Diffuse {
+                albedo: PixelF64::black(),
+            }),
+        };
+        assert_eq!(render(vec![&ground, &sphere]), PixelF64::black());
+    }
+
+    #[test]
+    fn test_8bit_render_keeps_precision() {
+        let render = |objects, background| {
+            let mut world = SkiedWorld::<PixelU8>::new(objects);
+            world.set_background(Background::Color(background));
+            let mut renderer = Renderer::new(Camera::new(8, 8, 1.0, 1.0), world);
+            renderer.set_threads(1);
+            renderer.set_seed(3);
+            renderer
+        };
+        // radiance below one 8-bit step survives averaging and the display transform
+        let dark = render(vec![], PixelF64::new(0.003, 0.003, 0.003));
+        assert!(dark.render(4).expect("render").get_pixel(0, 0).red8() > 0);
+        // light brighter than white is not clipped before reflecting off a diffuse surface
+        let sphere = Sphere {
+            center: PositionVec::new(0.0, 0.0, -2.0),
+            radius: 0.5,
+        };
+        let diffuse = Shaded {
+            object: &sphere,
+            material: Arc::new(Diffuse {
+                albedo: PixelF64::new(0.3, 0.3, 0.3),
+            }),
+        };
+        let bright = render(vec![&diffuse], PixelF64::new(2.0, 2.0, 2.0));
+        let color = bright.render_linear(4).expect("render").get_pixel(4, 4);
+        assert!((color.red() - 0.6).abs() < 1e-12, "{color:?}");
     }
 }
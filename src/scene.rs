@@ -1,14 +1,25 @@
+use crate::aabb::Aabb;
+use crate::bvh::Bvh;
+use crate::light::Light;
+use crate::material::Material;
+use crate::objects::normal_color;
 use crate::ppm::{Image, ImageSize};
 use crate::ray::Ray;
-use crate::types::{NumPosition, Pixel, PositionVec, Time};
+use crate::types::{NumPosition, Pixel, PixelF64, PositionVec, Time};
+use nalgebra::Matrix3;
 use num_traits::float::FloatCore;
+use rand::RngCore;
 use std::marker::PhantomData;
+use std::sync::Arc;
 
 /// Storing viewer's parameter.
+#[derive(Clone, Debug)]
 pub struct Camera {
-    /// position of the sensor center.
-    /// The position origin is focus point.
+    /// position of the focus point in world space
     pub pos: PositionVec,
+    /// rotation from camera space to world space,
+    /// in camera space the camera looks at -z with +y being up
+    pub orientation: Matrix3<NumPosition>,
     // /// width/height, must be positive
     // wh_ratio: NumPosition,
     /// image width in pixels, even number
@@ -23,35 +34,83 @@ pub struct Camera {
 
 /// Scene describes how objects in the world is organized.
 pub trait Scene: Send + Sync {
+    /// pixel type of rendered images
     type T: Pixel;
-    fn get_color(&self, ray: Ray) -> Self::T;
+    /// Radiance arriving along `ray`, drawing random numbers for scattering from `rng`.
+    /// Samples stay in double precision until they are averaged, and are converted to
+    /// [`Scene::T`] only after that.
+    fn get_color(&self, ray: Ray, rng: &mut dyn RngCore) -> PixelF64;
 }
 
 impl Camera {
+    /// Camera at the origin with square pixels, looking at -z.
+    /// `sensor_width` is the width of the whole image on the sensor plane,
+    /// which is `focus_length` away from the focus point.
+    pub fn new(
+        width: ImageSize,
+        height: ImageSize,
+        sensor_width: NumPosition,
+        focus_length: NumPosition,
+    ) -> Self {
+        let pixel_size = sensor_width / width as NumPosition;
+        Camera {
+            pos: PositionVec::zeros(),
+            orientation: Matrix3::identity(),
+            width,
+            height,
+            pixel_width: pixel_size,
+            pixel_height: pixel_size,
+            focus_length,
+        }
+    }
+
+    /// Rotate the camera to look at `target`, keeping `up` pointing upwards in the image.
+    /// The orientation is left unchanged if `target` is at the camera position or
+    /// `up` is parallel to the viewing direction.
+    pub fn look_at(&mut self, target: PositionVec, up: PositionVec) {
+        let Some(w) = (self.pos - target).try_normalize(NumPosition::EPSILON) else {
+            return;
+        };
+        let Some(u) = up.cross(&w).try_normalize(NumPosition::EPSILON) else {
+            return;
+        };
+        let v = w.cross(&u);
+        self.orientation = Matrix3::from_columns(&[u, v, w]);
+    }
+
     /// Deterministic method (random source is provided via arguments)
     /// to render a single-sampled image with viewer parameters for given scene.
     /// Say you want a 100-times-sampled image, you have to run get_image for
     /// 100 times and average them pixel by pixel to get the final image.
     /// rnd_x, rnd_y: 0 <= x < 1, random parameters for SSAA.
-    pub fn get_image<T: Scene>(&self, scene: &T, rnd_x: f64, rnd_y: f64) -> Image<T::T> {
+    /// rng: random source of scattered rays.
+    pub fn get_image<T: Scene>(
+        &self,
+        scene: &T,
+        rnd_x: f64,
+        rnd_y: f64,
+        rng: &mut dyn RngCore,
+    ) -> Image<PixelF64> {
         let mut image = Image::new(self.width, self.height);
         for (x, y, pixel) in image.iter_mut() {
             // get a sample of those rays whose destination is current pixel
             let pos_pixel = self.get_pixel_pos(x, y);
-            let origin = PositionVec::zeros();
             let bias = PositionVec::new(
                 rnd_x * self.pixel_width,
                 -rnd_y * self.pixel_height,
                 0 as NumPosition,
             );
-            let direction = (pos_pixel - origin + bias).normalize() as PositionVec;
-            let ray = Ray { origin, direction };
-            *pixel = scene.get_color(ray);
+            let direction = (self.orientation * (pos_pixel + bias)).normalize() as PositionVec;
+            let ray = Ray {
+                origin: self.pos,
+                direction,
+            };
+            *pixel = scene.get_color(ray, rng);
         }
         image
     }
 
-    /// Convert image pixel position (x, y) to 3D position in camera position system.
+    /// Convert image pixel position (x, y) to 3D position in camera space.
     /// Returns the position of the pixel's upper-left corner.
     fn get_pixel_pos(&self, x: ImageSize, y: ImageSize) -> PositionVec {
         let pos_sensor_center =
@@ -62,13 +121,12 @@ impl Camera {
                 (self.height as NumPosition) * self.pixel_height / 2.0,
                 0 as NumPosition,
             );
-        let pixel_pos = pos_left_upper_pixel
+        pos_left_upper_pixel
             + PositionVec::new(
                 self.pixel_width * (x as NumPosition),
                 -(self.pixel_height * (y as NumPosition)),
                 0 as NumPosition,
-            );
-        pixel_pos
+            )
     }
 }
 
@@ -80,20 +138,31 @@ pub struct DemoSkyScene<T: Pixel> {
 impl<T: Pixel> DemoSkyScene<T> {
     pub fn new() -> Self {
         DemoSkyScene {
-            _marker: PhantomData::default(),
+            _marker: PhantomData,
         }
     }
 }
 
+impl<T: Pixel> Default for DemoSkyScene<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T: Pixel> Scene for DemoSkyScene<T> {
     type T = T;
 
-    fn get_color(&self, ray: Ray) -> Self::T {
-        let a = 0.5 * (ray.direction.y as f64 + 1.0);
-        T::from_rgb_normalized(1.0 - 0.5 * a, 1.0 - 0.3 * a, 1.0)
+    fn get_color(&self, ray: Ray, _rng: &mut dyn RngCore) -> PixelF64 {
+        sky_color(&ray)
     }
 }
 
+/// Vertical gradient from white at the horizon to light blue above.
+fn sky_color(ray: &Ray) -> PixelF64 {
+    let a = 0.5 * (ray.direction.y + 1.0);
+    PixelF64::new(1.0 - 0.5 * a, 1.0 - 0.3 * a, 1.0)
+}
+
 pub struct AbsoluteSphereScene<T: Pixel> {
     sphere_center: PositionVec,
     sphere_radius: NumPosition,
@@ -112,15 +181,15 @@ impl<T: Pixel> AbsoluteSphereScene<T> {
 
 impl<T: Pixel> Scene for AbsoluteSphereScene<T> {
     type T = T;
-    fn get_color(&self, ray: Ray) -> T {
+    fn get_color(&self, ray: Ray, _rng: &mut dyn RngCore) -> PixelF64 {
         let oc = ray.origin - self.sphere_center;
         let a = ray.direction.norm_squared();
         let b = 2.0 * oc.dot(&ray.direction);
         let c = oc.norm_squared() - self.sphere_radius * self.sphere_radius;
         if b * b > 4.0 * a * c {
-            return self.sphere_color.clone();
+            return Pixel::from(&self.sphere_color);
         }
-        return DemoSkyScene::new().get_color(ray);
+        sky_color(&ray)
     }
 }
 
@@ -142,7 +211,7 @@ impl<T: Pixel> NormVectorVisualizedSphereScene<T> {
 
 impl<T: Pixel> Scene for NormVectorVisualizedSphereScene<T> {
     type T = T;
-    fn get_color(&self, ray: Ray) -> T {
+    fn get_color(&self, ray: Ray, _rng: &mut dyn RngCore) -> PixelF64 {
         let oc = ray.origin - self.sphere_center;
         let a = ray.direction.norm_squared();
         let b = 2.0 * oc.dot(&ray.direction);
@@ -150,55 +219,231 @@ impl<T: Pixel> Scene for NormVectorVisualizedSphereScene<T> {
         let delta = b * b - 4.0 * a * c;
         if delta < 0.0 {
             // does not hit the sphere
-            return DemoSkyScene::new().get_color(ray);
+            return sky_color(&ray);
         }
         // hit time, the smaller root
         let t = (-b - delta.sqrt()) / (2.0 * a);
         let surface_normal = (ray.at(t) - self.sphere_center).normalize();
-        let color = 0.5 * (surface_normal + PositionVec::new(1.0, 1.0, 1.0));
-        T::from_rgb_normalized(color.x, color.y, color.z)
+        normal_color(&surface_normal)
     }
 }
 
 /// the result of a hit
-pub struct HitEvent<T: Pixel> {
+pub struct HitEvent {
     /// hit point position
     pub hit_pos: PositionVec,
     /// hit surface normal vector, pointing to outer surface
     pub surface_nv: PositionVec,
+    /// normal vector for shading, interpolated from vertex normals on meshes,
+    /// the same as `surface_nv` on surfaces without
+    pub shading_nv: PositionVec,
+    /// surface coordinates of the hit point, like texture coordinates on meshes
+    pub uv: [NumPosition; 2],
     /// hit time
     pub t: Time,
-    /// color of the hit point
-    pub color: T,
+    /// color interpolated from vertex colors of meshes, tinting the material
+    pub color: Option<PixelF64>,
+    /// what the surface is made of, set by wrapping objects in [`Shaded`];
+    /// surfaces without are shown in their vertex color or visualized normal, without lighting
+    ///
+    /// [`Shaded`]: crate::objects::shaded::Shaded
+    pub material: Option<Arc<dyn Material>>,
+}
+
+/// Objects with a known extent, all a bounding volume hierarchy needs to know of them.
+pub trait Bounded {
+    /// axis-aligned box containing the whole object, `None` if it is unbounded like a plane
+    fn bounding_box(&self) -> Option<Aabb> {
+        None
+    }
 }
 
-pub trait Hittable<T: Pixel>: Send + Sync {
+pub trait Hittable: Bounded + Send + Sync {
     /// test whether the given ray will hit this object in time range `t1` <= t < `t2`,
     /// returning the smallest `t` that hits the object and satisfy the range constraint
-    fn try_hit(&self, ray: &Ray, t1: Time, t2: Time) -> Option<HitEvent<T>>;
+    fn try_hit(&self, ray: &Ray, t1: Time, t2: Time) -> Option<HitEvent>;
+}
+
+impl<H: Hittable + ?Sized> Hittable for &H {
+    fn try_hit(&self, ray: &Ray, t1: Time, t2: Time) -> Option<HitEvent> {
+        (**self).try_hit(ray, t1, t2)
+    }
+}
+
+impl<H: Bounded + ?Sized> Bounded for &H {
+    fn bounding_box(&self) -> Option<Aabb> {
+        (**self).bounding_box()
+    }
+}
+
+impl<H: Hittable + ?Sized> Hittable for Box<H> {
+    fn try_hit(&self, ray: &Ray, t1: Time, t2: Time) -> Option<HitEvent> {
+        (**self).try_hit(ray, t1, t2)
+    }
+}
+
+impl<H: Bounded + ?Sized> Bounded for Box<H> {
+    fn bounding_box(&self) -> Option<Aabb> {
+        (**self).bounding_box()
+    }
+}
+
+impl<H: Hittable + ?Sized> Hittable for Arc<H> {
+    fn try_hit(&self, ray: &Ray, t1: Time, t2: Time) -> Option<HitEvent> {
+        (**self).try_hit(ray, t1, t2)
+    }
+}
+
+impl<H: Bounded + ?Sized> Bounded for Arc<H> {
+    fn bounding_box(&self) -> Option<Aabb> {
+        (**self).bounding_box()
+    }
+}
+
+/// How the color of a ray hitting an object is computed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+#[cfg_attr(
+    feature = "scene-file",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Integrator {
+    /// path tracing, following rays scattered by materials until they reach a light or
+    /// the background
+    #[default]
+    Path,
+    /// surface normal mapped to RGB
+    Normal,
+    /// distance to the hit point, brighter is nearer
+    Depth,
+}
+
+/// Number of times a path may scatter before it is cut off.
+pub const MAX_BOUNCES: usize = 16;
+
+/// Scattered rays start this late, so they do not hit the surface they leave again
+/// due to rounding errors of the hit position.
+const SCATTER_EPSILON: Time = 1e-6;
+
+/// What rays hitting nothing see.
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub enum Background {
+    /// vertical gradient of [`DemoSkyScene`]
+    #[default]
+    Sky,
+    /// uniform color
+    Color(PixelF64),
+}
+
+impl Background {
+    pub fn get_color(&self, ray: Ray) -> PixelF64 {
+        match self {
+            Background::Sky => sky_color(&ray),
+            Background::Color(color) => *color,
+        }
+    }
 }
 
 pub struct SkiedWorld<'a, T: Pixel> {
-    pub(crate) objects: Vec<&'a dyn Hittable<T>>,
+    pub(crate) objects: Bvh<&'a dyn Hittable>,
+    pub(crate) integrator: Integrator,
+    pub(crate) background: Background,
+    pub(crate) lights: Vec<Light>,
+    _marker: PhantomData<T>,
+}
+
+impl<'a, T: Pixel> SkiedWorld<'a, T> {
+    pub fn new(objects: Vec<&'a dyn Hittable>) -> Self {
+        SkiedWorld {
+            objects: Bvh::new(objects),
+            integrator: Integrator::default(),
+            background: Background::default(),
+            lights: Vec::new(),
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn set_background(&mut self, background: Background) {
+        self.background = background;
+    }
+
+    pub fn set_integrator(&mut self, integrator: Integrator) {
+        self.integrator = integrator;
+    }
+
+    /// Set the lights without a surface, which light the objects in addition to
+    /// the background and emitting objects.
+    pub fn set_lights(&mut self, lights: Vec<Light>) {
+        self.lights = lights;
+    }
+
+    /// Light from lights without a surface reflected at `hit` along `ray`,
+    /// unless something is in between.
+    fn direct_light(&self, ray: &Ray, hit: &HitEvent, material: &dyn Material) -> PixelF64 {
+        let mut radiance = PixelF64::black();
+        for sample in self.lights.iter().filter_map(|l| l.sample(&hit.hit_pos)) {
+            let Some(reflected) = material.reflected(ray, hit, &sample.direction) else {
+                continue;
+            };
+            let shadow = Ray {
+                origin: hit.hit_pos,
+                direction: sample.direction,
+            };
+            if self
+                .objects
+                .try_hit(&shadow, SCATTER_EPSILON, sample.distance)
+                .is_none()
+            {
+                radiance += reflected.tinted(&sample.radiance);
+            }
+        }
+        radiance
+    }
+
+    /// Radiance arriving along `ray`, summing the light reaching it over scattering surfaces.
+    fn trace(&self, mut ray: Ray, rng: &mut dyn RngCore) -> PixelF64 {
+        let mut radiance = PixelF64::black();
+        // fraction of the light at the current bounce reaching the camera
+        let mut throughput = PixelF64::new(1.0, 1.0, 1.0);
+        let mut t1 = 0.0;
+        for _ in 0..=MAX_BOUNCES {
+            let Some(hit) = self.objects.try_hit(&ray, t1, Time::infinity()) else {
+                return radiance + throughput.tinted(&self.background.get_color(ray));
+            };
+            let Some(material) = &hit.material else {
+                let color = hit.color.unwrap_or_else(|| normal_color(&hit.shading_nv));
+                return radiance + throughput.tinted(&color);
+            };
+            radiance += throughput.tinted(&material.emitted(&ray, &hit));
+            radiance += throughput.tinted(&self.direct_light(&ray, &hit, material.as_ref()));
+            let Some(scatter) = material.scatter(&ray, &hit, rng) else {
+                return radiance;
+            };
+            throughput = throughput.tinted(&scatter.attenuation);
+            ray = scatter.ray;
+            t1 = SCATTER_EPSILON;
+        }
+        radiance
+    }
 }
 
 impl<'a, T: Pixel> Scene for SkiedWorld<'a, T> {
     type T = T;
 
-    fn get_color(&self, ray: Ray) -> T {
-        let mut last_hit: Option<HitEvent<T>> = None;
-        let mut t_max = Time::infinity();
-        for obj in &self.objects {
-            if let Some(hit) = obj.try_hit(&ray, 0.0, t_max) {
-                if hit.t < t_max {
-                    t_max = hit.t;
-                    last_hit = Some(hit);
+    fn get_color(&self, ray: Ray, rng: &mut dyn RngCore) -> PixelF64 {
+        match self.integrator {
+            Integrator::Path => self.trace(ray, rng),
+            Integrator::Normal => match self.objects.try_hit(&ray, 0.0, Time::infinity()) {
+                None => self.background.get_color(ray),
+                Some(hit) => normal_color(&hit.shading_nv),
+            },
+            Integrator::Depth => match self.objects.try_hit(&ray, 0.0, Time::infinity()) {
+                None => self.background.get_color(ray),
+                Some(hit) => {
+                    let v = 1.0 / (1.0 + hit.t);
+                    PixelF64::new(v, v, v)
                 }
-            }
-        }
-        match last_hit {
-            None => DemoSkyScene::new().get_color(ray),
-            Some(hit) => hit.color,
+            },
         }
     }
 }
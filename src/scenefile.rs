@@ -0,0 +1,1348 @@
+//! Scene description files in TOML format.
+//!
+//! [`SceneDescription`] is both the content of a scene file and an editable in-memory
+//! scene, which can be saved back to a file.
+//!
+//! A scene file describes render settings, the camera, the background, named materials
+//! and objects referring to them:
+//!
+//! ```toml
+//! [render]
+//! width = 320
+//! height = 240
+//! samples = 16
+//!
+//! [camera]
+//! position = [0.0, 0.0, 1.0]
+//! look_at = [0.0, 0.0, -1.0]
+//! fov = 90.0
+//!
+//! [background]
+//! type = "color"
+//! color = [0.1, 0.1, 0.1]
+//!
+//! [materials.red]
+//! type = "diffuse"
+//! color = [1.0, 0.0, 0.0]
+//!
+//! [materials.glass]
+//! type = "glass"
+//! ior = 1.5
+//!
+//! [[objects]]
+//! type = "sphere"
+//! center = [0.0, 0.0, -1.0]
+//! radius = 0.5
+//! material = "red"
+//!
+//! [[objects]]
+//! type = "sphere"
+//! center = [1.0, 0.0, -1.5]
+//! radius = 0.5
+//! material = "glass"
+//!
+//! [[objects]]
+//! type = "mesh"
+//! file = "teapot.obj"
+//! ```
+
+use crate::bvh::Bvh;
+use crate::import;
+#[cfg(feature = "gltf")]
+use crate::import::gltf::GltfScene;
+use crate::import::obj::{MtlMaterial, ObjModel};
+use crate::import::{ply, stl};
+use crate::light::Light;
+use crate::material::{Diffuse, Emissive, Glass, Material, Metal, NormalColor};
+use crate::objects::aabox::AxisAlignedBox;
+use crate::objects::cylinder::{Cone, Cylinder};
+use crate::objects::mesh::TriangleMesh;
+use crate::objects::plane::{Disk, Plane, Quad};
+use crate::objects::shaded::Shaded;
+use crate::objects::sphere::Sphere;
+use crate::objects::torus::Torus;
+use crate::ppm;
+use crate::renderer::{Renderer, DEFAULT_SENSOR_WIDTH};
+use crate::scene::{Background, Camera, Hittable, Integrator, SkiedWorld};
+use crate::tonemap::{DisplayTransform, ToneMapping};
+use crate::transform::{axis_angle, Instance, Rotation, Transform};
+use crate::types::{NumColorRatio, NumPosition, PixelF64, PositionVec};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::{fmt, fs, io};
+
+type Vec3 = [f64; 3];
+
+/// A material shared by all objects made of it.
+type SharedMaterial = Arc<dyn Material>;
+
+#[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
+pub enum Error {
+    IOError(io::Error),
+    /// malformed TOML, or a field is missing or has a wrong type
+    ParseError(toml::de::Error),
+    /// the scene can not be written as TOML
+    SerializeError(toml::ser::Error),
+    /// a field has an invalid value
+    InvalidField {
+        field: String,
+        message: String,
+    },
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::IOError(e) => write!(f, "I/O error: {e}"),
+            // the message tells the line and column
+            Error::ParseError(e) => write!(f, "{e}"),
+            Error::SerializeError(e) => write!(f, "can not serialize scene: {e}"),
+            Error::InvalidField { field, message } => write!(f, "invalid `{field}`: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(value: io::Error) -> Self {
+        Error::IOError(value)
+    }
+}
+
+impl From<toml::de::Error> for Error {
+    fn from(value: toml::de::Error) -> Self {
+        Error::ParseError(value)
+    }
+}
+
+impl From<toml::ser::Error> for Error {
+    fn from(value: toml::ser::Error) -> Self {
+        Error::SerializeError(value)
+    }
+}
+
+fn invalid(field: impl Into<String>, message: impl Into<String>) -> Error {
+    Error::InvalidField {
+        field: field.into(),
+        message: message.into(),
+    }
+}
+
+fn vector(v: &Vec3) -> PositionVec {
+    PositionVec::new(v[0], v[1], v[2])
+}
+
+/// Normalize a direction, which must not be zero.
+fn unit(field: String, v: &Vec3) -> Result<PositionVec, Error> {
+    vector(v)
+        .try_normalize(0.0)
+        .ok_or_else(|| invalid(field, "must not be zero"))
+}
+
+/// Check that a length is positive and finite.
+fn positive(field: String, v: NumPosition) -> Result<NumPosition, Error> {
+    if !v.is_finite() || v <= 0.0 {
+        return Err(invalid(field, "must be positive"));
+    }
+    Ok(v)
+}
+
+fn color(v: &Vec3) -> PixelF64 {
+    PixelF64::new(v[0], v[1], v[2])
+}
+
+/// A scene as written in a scene file.
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneDescription {
+    #[serde(default)]
+    pub render: RenderSettings,
+    #[serde(default)]
+    pub camera: CameraDescription,
+    #[serde(default)]
+    pub background: BackgroundDescription,
+    #[serde(default)]
+    pub materials: BTreeMap<String, MaterialDescription>,
+    #[serde(default)]
+    pub objects: Vec<ObjectDescription>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RenderSettings {
+    pub width: u32,
+    pub height: u32,
+    /// samples per pixel
+    pub samples: usize,
+    /// number of worker threads, one per CPU core if not set
+    pub threads: Option<usize>,
+    /// seed of the random sampling pattern, random on every render if not set
+    pub seed: Option<u64>,
+    pub integrator: Integrator,
+    /// exposure adjustment in stops
+    pub exposure: NumColorRatio,
+    pub tone_mapping: ToneMapping,
+    pub srgb: bool,
+    /// where to save the image
+    pub output: Option<PathBuf>,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings {
+            width: 640,
+            height: 480,
+            samples: 100,
+            threads: None,
+            seed: None,
+            integrator: Integrator::default(),
+            exposure: 0.0,
+            tone_mapping: ToneMapping::default(),
+            srgb: true,
+            output: None,
+        }
+    }
+}
+
+impl RenderSettings {
+    pub fn get_display_transform(&self) -> DisplayTransform {
+        DisplayTransform {
+            exposure: self.exposure,
+            tone_mapping: self.tone_mapping,
+            srgb: self.srgb,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CameraDescription {
+    pub position: Vec3,
+    pub look_at: Vec3,
+    pub up: Vec3,
+    /// horizontal field of view in degrees
+    pub fov: NumPosition,
+}
+
+impl Default for CameraDescription {
+    fn default() -> Self {
+        CameraDescription {
+            position: [0.0, 0.0, 0.0],
+            look_at: [0.0, 0.0, -1.0],
+            up: [0.0, 1.0, 0.0],
+            fov: (DEFAULT_SENSOR_WIDTH / 2.0).atan().to_degrees() * 2.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum BackgroundDescription {
+    #[default]
+    Sky,
+    Color {
+        color: Vec3,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum MaterialDescription {
+    /// surface normal mapped to RGB, without lighting
+    Normal,
+    /// uniform color, emitted like a light so it looks the same under any lighting
+    Color { color: Vec3 },
+    /// matte surface scattering light evenly
+    Diffuse { color: Vec3 },
+    /// reflective surface
+    Metal {
+        color: Vec3,
+        /// blur of reflections, from 0 for a mirror to 1
+        #[serde(default)]
+        fuzz: NumPosition,
+    },
+    /// clear surface refracting light
+    Glass {
+        /// index of refraction
+        ior: NumColorRatio,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ObjectDescription {
+    Sphere {
+        center: Vec3,
+        radius: NumPosition,
+        /// name of the material, surface normal is visualized if not set
+        material: Option<String>,
+    },
+    /// infinite plane facing where the normal points to
+    Plane {
+        point: Vec3,
+        normal: Vec3,
+        material: Option<String>,
+    },
+    /// parallelogram with edges `u` and `v` from a corner, facing the side `u` turns
+    /// counter-clockwise to `v`
+    Quad {
+        corner: Vec3,
+        u: Vec3,
+        v: Vec3,
+        material: Option<String>,
+    },
+    Disk {
+        center: Vec3,
+        normal: Vec3,
+        radius: NumPosition,
+        material: Option<String>,
+    },
+    /// axis-aligned box between two corners
+    Box {
+        min: Vec3,
+        max: Vec3,
+        material: Option<String>,
+    },
+    /// finite cylinder along `axis` from the center of its bottom end
+    Cylinder {
+        base: Vec3,
+        axis: Vec3,
+        radius: NumPosition,
+        /// closed by disks at both ends
+        #[serde(default)]
+        capped: bool,
+        material: Option<String>,
+    },
+    /// finite cone along `axis` from the center of its base to its apex
+    Cone {
+        base: Vec3,
+        axis: Vec3,
+        radius: NumPosition,
+        /// closed by a disk at the base
+        #[serde(default)]
+        capped: bool,
+        material: Option<String>,
+    },
+    /// ring around `axis` through its center
+    Torus {
+        center: Vec3,
+        axis: Vec3,
+        major_radius: NumPosition,
+        minor_radius: NumPosition,
+        material: Option<String>,
+    },
+    /// another object moved into place, scaled first, then rotated, then translated
+    Instance {
+        object: Box<ObjectDescription>,
+        translate: Option<Vec3>,
+        /// angle in degrees, followed by the axis to rotate around
+        rotate: Option<[NumPosition; 4]>,
+        scale: Option<Vec3>,
+    },
+    /// triangles loaded from a Wavefront OBJ, glTF, PLY or STL file
+    Mesh {
+        file: PathBuf,
+        /// name of the material replacing materials of the file
+        material: Option<String>,
+    },
+}
+
+impl ObjectDescription {
+    /// Make relative paths of mesh files, resolved against `from`, relative to `to`.
+    /// Both directories are absolute.
+    fn rebase(&mut self, from: &Path, to: &Path) {
+        match self {
+            ObjectDescription::Mesh { file, .. } if file.is_relative() => {
+                *file = relative_path(&from.join(&*file), to);
+            }
+            ObjectDescription::Instance { object, .. } => object.rebase(from, to),
+            _ => {}
+        }
+    }
+}
+
+/// Remove `.` and `..` components from an absolute path without accessing the file system.
+fn normalize(path: &Path) -> Vec<Component<'_>> {
+    let mut components = Vec::new();
+    for c in path.components() {
+        match c {
+            Component::CurDir => {}
+            Component::ParentDir if matches!(components.last(), Some(Component::Normal(_))) => {
+                components.pop();
+            }
+            Component::ParentDir => {}
+            c => components.push(c),
+        }
+    }
+    components
+}
+
+/// The absolute path `path` relative to the absolute directory `dir`,
+/// or `path` itself if they have no common root.
+fn relative_path(path: &Path, dir: &Path) -> PathBuf {
+    let (path_components, dir_components) = (normalize(path), normalize(dir));
+    if path_components.first() != dir_components.first() {
+        return path.to_path_buf();
+    }
+    let common = path_components
+        .iter()
+        .zip(&dir_components)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut relative: PathBuf = dir_components[common..]
+        .iter()
+        .map(|_| Component::ParentDir)
+        .collect();
+    relative.extend(&path_components[common..]);
+    relative
+}
+
+/// Triangles of a mesh file with their materials, or without material if the colors
+/// come from the mesh itself, and the lights placed in the file.
+struct MeshFile {
+    meshes: Vec<(TriangleMesh, Option<SharedMaterial>)>,
+    lights: Vec<Light>,
+}
+
+/// Objects and settings built from a [`SceneDescription`], ready for rendering.
+pub struct LoadedScene {
+    pub settings: RenderSettings,
+    pub camera: Camera,
+    pub background: Background,
+    /// lights without a surface, lighting the objects in addition to emitting ones
+    pub lights: Vec<Light>,
+    pub objects: Vec<Box<dyn Hittable>>,
+}
+
+impl LoadedScene {
+    /// Build a renderer for the scene with all render settings applied.
+    pub fn renderer(&self) -> Renderer<SkiedWorld<'_, PixelF64>> {
+        let mut world = SkiedWorld::new(self.objects.iter().map(|o| o.as_ref()).collect());
+        world.set_integrator(self.settings.integrator);
+        world.set_background(self.background);
+        world.set_lights(self.lights.clone());
+        let mut renderer = Renderer::new(self.camera.clone(), world);
+        renderer.set_display_transform(self.settings.get_display_transform());
+        if let Some(threads) = self.settings.threads {
+            renderer.set_threads(threads);
+        }
+        if let Some(seed) = self.settings.seed {
+            renderer.set_seed(seed);
+        }
+        renderer
+    }
+}
+
+impl SceneDescription {
+    /// A normal-visualized sphere in front of the camera under the sky.
+    pub fn demo() -> Self {
+        SceneDescription {
+            objects: vec![ObjectDescription::Sphere {
+                center: [0.0, 0.0, -1.0],
+                radius: 0.5,
+                material: None,
+            }],
+            ..SceneDescription::default()
+        }
+    }
+
+    /// Create a scene rendering `gltf`, loaded from the file at `path`, from its first camera,
+    /// or from the default camera if the file has no camera.
+    /// The mesh object refers to the file by its name, relative to the directory of the file.
+    #[cfg(feature = "gltf")]
+    pub fn from_gltf(path: &Path, gltf: &GltfScene) -> Self {
+        let file = path.file_name().map(PathBuf::from).unwrap_or_default();
+        let mut desc = SceneDescription {
+            objects: vec![ObjectDescription::Mesh {
+                file,
+                material: None,
+            }],
+            ..SceneDescription::default()
+        };
+        if let Some(camera) = gltf.cameras.first() {
+            let render = &mut desc.render;
+            let aspect_ratio = match camera.aspect_ratio {
+                Some(aspect_ratio) if aspect_ratio > 0.0 => {
+                    render.height =
+                        ((render.width as NumPosition / aspect_ratio).round() as u32).max(1);
+                    aspect_ratio
+                }
+                _ => render.width as NumPosition / render.height as NumPosition,
+            };
+            let up = camera.orientation.column(1).into_owned();
+            let forward = -camera.orientation.column(2);
+            desc.camera = CameraDescription {
+                position: camera.position.into(),
+                look_at: (camera.position + forward).into(),
+                up: up.into(),
+                fov: ((camera.yfov / 2.0).tan() * aspect_ratio)
+                    .atan()
+                    .to_degrees()
+                    * 2.0,
+            };
+        }
+        desc
+    }
+
+    pub fn parse(s: &str) -> Result<Self, Error> {
+        Ok(toml::from_str(s)?)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Serialize the scene in the scene file format.
+    pub fn to_toml(&self) -> Result<String, Error> {
+        Ok(toml::to_string(self)?)
+    }
+
+    /// Save the scene file at `path`. Relative paths of mesh files are resolved against the
+    /// working directory, and saved relative to the directory of the scene file.
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        self.save_relative_to(path, Path::new(""))
+    }
+
+    /// Like [`SceneDescription::save`], resolving relative paths of mesh files against `dir`,
+    /// which is usually the directory of the scene file the description was loaded from.
+    pub fn save_relative_to(&self, path: &Path, dir: &Path) -> Result<(), Error> {
+        // an empty path is the working directory
+        let absolute = |p: &Path| std::path::absolute(Path::new(".").join(p));
+        let from = absolute(dir)?;
+        let to = absolute(path.parent().unwrap_or(Path::new("")))?;
+        let mut desc = self.clone();
+        for object in &mut desc.objects {
+            object.rebase(&from, &to);
+        }
+        Ok(fs::write(path, desc.to_toml()?)?)
+    }
+
+    fn build_camera(&self) -> Result<Camera, Error> {
+        let (render, desc) = (&self.render, &self.camera);
+        if render.width == 0 {
+            return Err(invalid("render.width", "must be positive"));
+        }
+        if render.height == 0 {
+            return Err(invalid("render.height", "must be positive"));
+        }
+        ppm::check_size(render.width, render.height)
+            .map_err(|_| invalid("render", format!("more than {} pixels", ppm::MAX_PIXELS)))?;
+        if !(desc.fov > 0.0 && desc.fov < 180.0) {
+            return Err(invalid("camera.fov", "must be in range (0, 180)"));
+        }
+        let sensor_width = 2.0 * (desc.fov.to_radians() / 2.0).tan();
+        let mut camera = Camera::new(render.width, render.height, sensor_width, 1.0);
+        camera.pos = vector(&desc.position);
+        let direction = vector(&desc.look_at) - camera.pos;
+        if direction.norm() == 0.0 {
+            return Err(invalid("camera.look_at", "is at the camera position"));
+        }
+        if vector(&desc.up).cross(&direction).norm() == 0.0 {
+            return Err(invalid("camera.up", "is parallel to the viewing direction"));
+        }
+        camera.look_at(vector(&desc.look_at), vector(&desc.up));
+        Ok(camera)
+    }
+
+    /// The material named at `field`, showing surface normals if no name is given.
+    fn build_material(
+        &self,
+        field: String,
+        material: &Option<String>,
+    ) -> Result<SharedMaterial, Error> {
+        let Some(name) = material else {
+            return Ok(Arc::new(NormalColor));
+        };
+        let Some(desc) = self.materials.get(name) else {
+            return Err(invalid(field, format!("unknown material `{name}`")));
+        };
+        Ok(match desc {
+            MaterialDescription::Normal => Arc::new(NormalColor),
+            MaterialDescription::Color { color: c } => Arc::new(Emissive { radiance: color(c) }),
+            MaterialDescription::Diffuse { color: c } => Arc::new(Diffuse { albedo: color(c) }),
+            MaterialDescription::Metal { color: c, fuzz } => {
+                if !(0.0..=1.0).contains(fuzz) {
+                    return Err(invalid(
+                        format!("materials.{name}.fuzz"),
+                        "must be in range [0, 1]",
+                    ));
+                }
+                Arc::new(Metal {
+                    albedo: color(c),
+                    fuzz: *fuzz,
+                })
+            }
+            MaterialDescription::Glass { ior } => Arc::new(Glass {
+                ior: positive(format!("materials.{name}.ior"), *ior)?,
+            }),
+        })
+    }
+
+    /// Check the description and create the objects it describes.
+    /// Relative paths of mesh files are resolved against the working directory.
+    pub fn build(&self) -> Result<LoadedScene, Error> {
+        self.build_relative_to(Path::new(""))
+    }
+
+    /// Like [`SceneDescription::build`] for a description created by
+    /// [`SceneDescription::from_gltf`], taking meshes and lights from the already loaded
+    /// `gltf` instead of loading the file again.
+    #[cfg(feature = "gltf")]
+    pub fn build_gltf(&self, gltf: GltfScene) -> Result<LoadedScene, Error> {
+        self.build_with(|objects, lights| {
+            *lights = gltf.lights();
+            *objects = gltf.into_objects();
+            Ok(())
+        })
+    }
+
+    /// Load a mesh file.
+    fn load_mesh(&self, path: &Path) -> Result<MeshFile, import::Error> {
+        let extension = path
+            .extension()
+            .and_then(|s| s.to_str())
+            .map(|s| s.to_ascii_lowercase());
+        let meshes = match extension.as_deref() {
+            Some("obj") => {
+                let model = ObjModel::load(path)?;
+                model
+                    .meshes
+                    .into_iter()
+                    .map(|m| {
+                        let material = m
+                            .material
+                            .as_ref()
+                            .and_then(|name| model.materials.get(name))
+                            .map_or_else(|| Arc::new(NormalColor) as _, MtlMaterial::material);
+                        (m.mesh, Some(material))
+                    })
+                    .collect()
+            }
+            Some("ply") => vec![(ply::load(path)?, None)],
+            Some("stl") => vec![(stl::load(path)?, None)],
+            #[cfg(feature = "gltf")]
+            Some("gltf" | "glb") => {
+                let scene = GltfScene::load(path)?;
+                return Ok(MeshFile {
+                    lights: scene.lights(),
+                    meshes: scene
+                        .into_meshes()
+                        .into_iter()
+                        .map(|(mesh, material)| (mesh, Some(material)))
+                        .collect(),
+                });
+            }
+            _ => {
+                return Err(import::Error::Unsupported(format!(
+                    "unknown mesh file type of {}",
+                    path.display()
+                )))
+            }
+        };
+        Ok(MeshFile {
+            meshes,
+            lights: Vec::new(),
+        })
+    }
+
+    /// Build the object described at `field`, adding it to `objects`.
+    /// Meshes from files add one object per material, and the lights of the file to `lights`.
+    fn build_object(
+        &self,
+        field: &str,
+        object: &ObjectDescription,
+        dir: &Path,
+        objects: &mut Vec<Box<dyn Hittable>>,
+        lights: &mut Vec<Light>,
+    ) -> Result<(), Error> {
+        match object {
+            ObjectDescription::Sphere {
+                center,
+                radius,
+                material,
+            } => {
+                let material = self.build_material(format!("{field}.material"), material)?;
+                objects.push(Box::new(Shaded {
+                    object: Sphere {
+                        center: vector(center),
+                        radius: positive(format!("{field}.radius"), *radius)?,
+                    },
+                    material,
+                }));
+            }
+            ObjectDescription::Plane {
+                point,
+                normal,
+                material,
+            } => {
+                let material = self.build_material(format!("{field}.material"), material)?;
+                objects.push(Box::new(Shaded {
+                    object: Plane {
+                        point: vector(point),
+                        normal: unit(format!("{field}.normal"), normal)?,
+                    },
+                    material,
+                }));
+            }
+            ObjectDescription::Quad {
+                corner,
+                u,
+                v,
+                material,
+            } => {
+                if vector(u).cross(&vector(v)).norm() == 0.0 {
+                    return Err(invalid(format!("{field}.v"), "is parallel to u"));
+                }
+                let material = self.build_material(format!("{field}.material"), material)?;
+                objects.push(Box::new(Shaded {
+                    object: Quad {
+                        corner: vector(corner),
+                        u: vector(u),
+                        v: vector(v),
+                    },
+                    material,
+                }));
+            }
+            ObjectDescription::Disk {
+                center,
+                normal,
+                radius,
+                material,
+            } => {
+                let material = self.build_material(format!("{field}.material"), material)?;
+                objects.push(Box::new(Shaded {
+                    object: Disk {
+                        center: vector(center),
+                        normal: unit(format!("{field}.normal"), normal)?,
+                        radius: positive(format!("{field}.radius"), *radius)?,
+                    },
+                    material,
+                }));
+            }
+            ObjectDescription::Box { min, max, material } => {
+                if !(0..3).all(|k| min[k] < max[k]) {
+                    return Err(invalid(
+                        format!("{field}.max"),
+                        "must be larger than min on every axis",
+                    ));
+                }
+                let material = self.build_material(format!("{field}.material"), material)?;
+                objects.push(Box::new(Shaded {
+                    object: AxisAlignedBox {
+                        min: vector(min),
+                        max: vector(max),
+                    },
+                    material,
+                }));
+            }
+            ObjectDescription::Cylinder {
+                base,
+                axis,
+                radius,
+                capped,
+                material,
+            } => {
+                unit(format!("{field}.axis"), axis)?;
+                let material = self.build_material(format!("{field}.material"), material)?;
+                objects.push(Box::new(Shaded {
+                    object: Cylinder {
+                        base: vector(base),
+                        axis: vector(axis),
+                        radius: positive(format!("{field}.radius"), *radius)?,
+                        capped: *capped,
+                    },
+                    material,
+                }));
+            }
+            ObjectDescription::Cone {
+                base,
+                axis,
+                radius,
+                capped,
+                material,
+            } => {
+                unit(format!("{field}.axis"), axis)?;
+                let material = self.build_material(format!("{field}.material"), material)?;
+                objects.push(Box::new(Shaded {
+                    object: Cone {
+                        base: vector(base),
+                        axis: vector(axis),
+                        radius: positive(format!("{field}.radius"), *radius)?,
+                        capped: *capped,
+                    },
+                    material,
+                }));
+            }
+            ObjectDescription::Torus {
+                center,
+                axis,
+                major_radius,
+                minor_radius,
+                material,
+            } => {
+                let material = self.build_material(format!("{field}.material"), material)?;
+                objects.push(Box::new(Shaded {
+                    object: Torus {
+                        center: vector(center),
+                        axis: unit(format!("{field}.axis"), axis)?,
+                        major_radius: positive(format!("{field}.major_radius"), *major_radius)?,
+                        minor_radius: positive(format!("{field}.minor_radius"), *minor_radius)?,
+                    },
+                    material,
+                }));
+            }
+            ObjectDescription::Mesh { file, material } => {
+                let path = dir.join(file);
+                let file = self.load_mesh(&path).map_err(|e| {
+                    invalid(
+                        format!("{field}.file"),
+                        format!("can not load {}: {e}", path.display()),
+                    )
+                })?;
+                let replaced = match material {
+                    None => None,
+                    Some(_) => Some(self.build_material(format!("{field}.material"), material)?),
+                };
+                lights.extend(file.lights);
+                for (mesh, own) in file.meshes {
+                    match replaced.clone().or(own) {
+                        Some(material) => objects.push(Box::new(Shaded {
+                            object: mesh,
+                            material,
+                        })),
+                        None => objects.push(Box::new(mesh)),
+                    }
+                }
+            }
+            ObjectDescription::Instance {
+                object,
+                translate,
+                rotate,
+                scale,
+            } => {
+                let translation = translate.as_ref().map_or(PositionVec::zeros(), vector);
+                let rotation = rotate.map_or(Rotation::identity(), |[angle, x, y, z]| {
+                    axis_angle(&PositionVec::new(x, y, z), angle.to_radians())
+                });
+                let scale = scale.as_ref().map_or(PositionVec::repeat(1.0), vector);
+                if !scale.iter().all(|f| f.is_finite() && *f != 0.0) {
+                    return Err(invalid(format!("{field}.scale"), "must not be zero"));
+                }
+                let transform = Transform::from_trs(&translation, &rotation, &scale);
+                let (mut inner, mut inner_lights) = (Vec::new(), Vec::new());
+                self.build_object(
+                    &format!("{field}.object"),
+                    object,
+                    dir,
+                    &mut inner,
+                    &mut inner_lights,
+                )?;
+                lights.extend(inner_lights.iter().map(|l| l.transformed(&transform)));
+                let object: Box<dyn Hittable> = if inner.len() == 1 {
+                    inner.remove(0)
+                } else {
+                    // meshes from files with several materials
+                    Box::new(Bvh::new(inner))
+                };
+                objects.push(Box::new(Instance::new(object, transform)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Like [`SceneDescription::build`], resolving relative paths of mesh files against `dir`,
+    /// which is usually the directory of the scene file.
+    pub fn build_relative_to(&self, dir: &Path) -> Result<LoadedScene, Error> {
+        self.build_with(|objects, lights| {
+            for (i, object) in self.objects.iter().enumerate() {
+                self.build_object(&format!("objects[{i}]"), object, dir, objects, lights)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Check the settings and build the scene of the objects and lights added by `add`.
+    fn build_with(
+        &self,
+        add: impl FnOnce(&mut Vec<Box<dyn Hittable>>, &mut Vec<Light>) -> Result<(), Error>,
+    ) -> Result<LoadedScene, Error> {
+        if self.render.samples == 0 {
+            return Err(invalid("render.samples", "must be positive"));
+        }
+        if self.render.threads == Some(0) {
+            return Err(invalid("render.threads", "must be positive"));
+        }
+        let camera = self.build_camera()?;
+        let background = match &self.background {
+            BackgroundDescription::Sky => Background::Sky,
+            BackgroundDescription::Color { color: c } => Background::Color(color(c)),
+        };
+        let (mut objects, mut lights) = (Vec::new(), Vec::new());
+        add(&mut objects, &mut lights)?;
+        Ok(LoadedScene {
+            settings: self.render.clone(),
+            camera,
+            background,
+            lights,
+            objects,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "gltf")]
+    use crate::import::gltf::GltfScene;
+    use crate::ray::Ray;
+    use crate::scenefile::{
+        BackgroundDescription, Error, MaterialDescription, ObjectDescription, SceneDescription,
+    };
+    use crate::testing;
+    use crate::tonemap::ToneMapping;
+    use crate::types::{Pixel, PixelF64, PositionVec};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    const SCENE: &str = r#"
+[render]
+width = 32
+height = 24
+samples = 2
+seed = 7
+tone_mapping = { extended_reinhard = { white = 4.0 } }
+
+[camera]
+position = [0.0, 0.0, 1.0]
+look_at = [0.0, 0.0, -1.0]
+fov = 60.0
+
+[background]
+type = "color"
+color = [0.0, 0.0, 0.25]
+
+[materials.red]
+type = "color"
+color = [1.0, 0.0, 0.0]
+
+[[objects]]
+type = "sphere"
+center = [0.0, 0.0, -1.0]
+radius = 0.5
+material = "red"
+"#;
+
+    #[test]
+    fn test_load_scene() {
+        let desc = SceneDescription::parse(SCENE).expect("parse");
+        assert_eq!(desc.render.width, 32);
+        assert_eq!(
+            desc.background,
+            BackgroundDescription::Color {
+                color: [0.0, 0.0, 0.25]
+            }
+        );
+        assert_eq!(
+            desc.materials["red"],
+            MaterialDescription::Color {
+                color: [1.0, 0.0, 0.0]
+            }
+        );
+        assert!(matches!(
+            desc.objects[0],
+            ObjectDescription::Sphere { radius: 0.5, .. }
+        ));
+        let scene = desc.build().expect("build");
+        let image = scene.renderer().render_linear(2).expect("render");
+        assert_eq!(image.get_pixel(16, 12), PixelF64::new(1.0, 0.0, 0.0));
+        assert_eq!(image.get_pixel(0, 0).blue(), 0.25);
+    }
+
+    #[test]
+    fn test_parse_error_has_line() {
+        let err = SceneDescription::parse("[render]\nwidth = 32\nheigth = 24\n").unwrap_err();
+        assert!(matches!(err, Error::ParseError(_)));
+        let message = err.to_string();
+        assert!(message.contains("line 3"), "{message}");
+        assert!(message.contains("heigth"), "{message}");
+
+        let err = SceneDescription::parse("[camera]\n\nfov = \"wide\"\n").unwrap_err();
+        assert!(err.to_string().contains("line 3"), "{err}");
+        // objects are reported at the line their table starts
+        let err = SceneDescription::parse("\n[[objects]]\ntype = \"sphere\"\ncenter = [0.0]\n")
+            .unwrap_err();
+        assert!(err.to_string().contains("line 2"), "{err}");
+    }
+
+    fn render_red(desc: &SceneDescription) -> Vec<f64> {
+        let scene = desc.build().expect("build");
+        let image = scene.renderer().render_linear(2).expect("render");
+        image.iter().map(|(_, _, p)| p.red()).collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let desc = SceneDescription::parse(SCENE).expect("parse");
+        let saved = desc.to_toml().expect("serialize");
+        let loaded = SceneDescription::parse(&saved).expect("parse saved scene");
+        assert_eq!(loaded, desc);
+        assert_eq!(render_red(&loaded), render_red(&desc));
+    }
+
+    #[test]
+    fn test_save_generated_scene() {
+        let mut desc = SceneDescription::demo();
+        desc.render.width = 24;
+        desc.render.height = 16;
+        desc.render.seed = Some(3);
+        desc.render.tone_mapping = ToneMapping::AgX;
+        desc.render.output = Some(PathBuf::from("out.png"));
+        desc.background = BackgroundDescription::Color {
+            color: [0.1, 0.2, 0.3],
+        };
+        desc.materials.insert(
+            "gray".to_string(),
+            MaterialDescription::Color {
+                color: [0.5, 0.5, 0.5],
+            },
+        );
+        desc.materials
+            .insert("normal".to_string(), MaterialDescription::Normal);
+        desc.materials.insert(
+            "gold".to_string(),
+            MaterialDescription::Metal {
+                color: [0.9, 0.7, 0.3],
+                fuzz: 0.2,
+            },
+        );
+        desc.materials
+            .insert("glass".to_string(), MaterialDescription::Glass { ior: 1.5 });
+        desc.materials.insert(
+            "chalk".to_string(),
+            MaterialDescription::Diffuse {
+                color: [0.9, 0.9, 0.9],
+            },
+        );
+        let names = ["gray", "normal", "gold", "glass", "chalk"];
+        for (i, name) in names.iter().enumerate() {
+            desc.objects.push(ObjectDescription::Sphere {
+                center: [i as f64 * 0.2 - 0.4, -0.2, -1.5],
+                radius: 0.1 / 3.0,
+                material: Some(name.to_string()),
+            });
+        }
+        desc.camera.position = [0.1, 0.2, 0.7];
+        desc.camera.fov = 55.5;
+
+        let path = std::env::temp_dir().join("rrt_ut_test_save_generated_scene.toml");
+        desc.save(&path).expect("save");
+        let loaded = SceneDescription::load(&path).expect("load");
+        assert_eq!(loaded, desc);
+        assert_eq!(render_red(&loaded), render_red(&desc));
+        std::fs::remove_file(&path).expect("remove temp file");
+    }
+
+    #[test]
+    fn test_save_to_other_directory() {
+        let mut desc = SceneDescription::parse(SCENE).expect("parse");
+        desc.objects = vec![
+            ObjectDescription::Mesh {
+                file: PathBuf::from("cube.obj"),
+                material: None,
+            },
+            ObjectDescription::Instance {
+                object: Box::new(ObjectDescription::Mesh {
+                    file: PathBuf::from("../test/square.ply"),
+                    material: None,
+                }),
+                translate: Some([1.0, 0.0, 0.0]),
+                rotate: None,
+                scale: None,
+            },
+        ];
+        let dir = std::env::temp_dir().join("rrt_ut_test_save_to_other_directory");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("scenes")).expect("create temp dir");
+        let path = dir.join("scenes").join("scene.toml");
+        desc.save_relative_to(&path, &testing::path(""))
+            .expect("save");
+
+        let loaded = SceneDescription::load(&path).expect("load");
+        let ObjectDescription::Mesh { file, .. } = &loaded.objects[0] else {
+            panic!("mesh expected");
+        };
+        assert!(file.is_relative());
+        assert_eq!(
+            path.parent().unwrap().join(file).canonicalize().unwrap(),
+            testing::path("cube.obj").canonicalize().unwrap()
+        );
+        let scene = loaded
+            .build_relative_to(path.parent().unwrap())
+            .expect("build");
+        assert_eq!(scene.objects.len(), 3);
+        fs::remove_dir_all(&dir).expect("remove temp dir");
+
+        let root = Path::new("/a/b");
+        assert_eq!(
+            super::relative_path(Path::new("/a/b/c/../d.obj"), root),
+            Path::new("d.obj")
+        );
+        assert_eq!(
+            super::relative_path(Path::new("/a/x/d.obj"), root),
+            Path::new("../x/d.obj")
+        );
+    }
+
+    #[test]
+    fn test_load_mesh() {
+        let mut desc = SceneDescription::parse(SCENE).expect("parse");
+        desc.camera.position = [0.0, 0.0, 5.0];
+        desc.objects = vec![ObjectDescription::Mesh {
+            file: PathBuf::from("cube.obj"),
+            material: None,
+        }];
+        // lit evenly, diffuse surfaces of the convex cube show their albedo
+        desc.background = BackgroundDescription::Color {
+            color: [1.0, 1.0, 1.0],
+        };
+        let scene = desc.build_relative_to(&testing::path("")).expect("build");
+        assert_eq!(scene.objects.len(), 2);
+        let image = scene.renderer().render_linear(1).expect("render");
+        // the front face of the cube is red
+        assert_eq!(image.get_pixel(16, 12), PixelF64::new(0.8, 0.1, 0.1));
+        assert!(matches!(
+            desc.build(),
+            Err(Error::InvalidField { field, .. }) if field == "objects[0].file"
+        ));
+    }
+
+    #[test]
+    fn test_load_colored_mesh() {
+        let mut desc = SceneDescription::parse(SCENE).expect("parse");
+        desc.camera.position = [0.5, 0.5, 1.0];
+        desc.camera.look_at = [0.5, 0.5, 0.0];
+        desc.objects = vec![ObjectDescription::Mesh {
+            file: PathBuf::from("square.ply"),
+            material: None,
+        }];
+        let scene = desc.build_relative_to(&testing::path("")).expect("build");
+        let image = scene.renderer().render_linear(1).expect("render");
+        // vertex colors are kept without a material, the bottom left corner is red
+        let (w, h) = (desc.render.width, desc.render.height);
+        let center = image.get_pixel(w / 2, h / 2);
+        let corner = image.get_pixel(w / 2 - w / 8, h / 2 + w / 8);
+        assert!(corner.red() > center.red());
+        assert!(corner.green() < center.green());
+    }
+
+    #[cfg(feature = "gltf")]
+    #[test]
+    fn test_from_gltf() {
+        let path = testing::path("triangle.gltf");
+        let gltf = GltfScene::load(&path).expect("load");
+        let mut desc = SceneDescription::from_gltf(&path, &gltf);
+        // the camera has an aspect ratio of 2
+        assert_eq!((desc.render.width, desc.render.height), (640, 320));
+        assert_eq!(desc.camera.position, [0.0, 0.0, 1.0]);
+        assert_eq!(desc.camera.look_at, [0.0, 0.0, 0.0]);
+        desc.render.width = 64;
+        desc.render.height = 32;
+        desc.background = BackgroundDescription::Color {
+            color: [1.0, 1.0, 1.0],
+        };
+        let scene = desc.build_relative_to(&testing::path("")).expect("build");
+        assert_eq!(scene.objects.len(), 1);
+        // the sun shines along the triangle
+        assert_eq!(scene.lights.len(), 1);
+        let image = scene.renderer().render_linear(1).expect("render");
+        assert_eq!(image.get_pixel(40, 10), PixelF64::new(0.1, 0.8, 0.2));
+        assert_ne!(image.get_pixel(20, 10), PixelF64::new(0.1, 0.8, 0.2));
+
+        // the same scene without loading the file again
+        let loaded = desc.build_gltf(gltf).expect("build");
+        assert_eq!(loaded.objects.len(), 1);
+        assert_eq!(loaded.lights, scene.lights);
+        let again = loaded.renderer().render_linear(1).expect("render");
+        assert_eq!(again.get_pixel(40, 10), image.get_pixel(40, 10));
+    }
+
+    #[test]
+    fn test_build_instance() {
+        let desc = SceneDescription::parse(
+            r#"
+[[objects]]
+type = "instance"
+translate = [0.0, 0.0, -5.0]
+rotate = [90.0, 0.0, 1.0, 0.0]
+scale = [3.0, 1.0, 1.0]
+
+[objects.object]
+type = "sphere"
+center = [0.0, 0.0, 0.0]
+radius = 1.0
+"#,
+        )
+        .expect("parse");
+        let scene = desc.build().expect("build");
+        // stretched along x, then turned to lie along z
+        let ray = Ray {
+            origin: PositionVec::zeros(),
+            direction: PositionVec::new(0.0, 0.0, -1.0),
+        };
+        let hit = scene.objects[0]
+            .try_hit(&ray, 0.0, f64::INFINITY)
+            .expect("hit");
+        assert!((hit.t - 2.0).abs() < 1e-12);
+        assert!((hit.surface_nv - PositionVec::new(0.0, 0.0, 1.0)).norm() < 1e-12);
+        let saved = SceneDescription::parse(&desc.to_toml().expect("save")).expect("parse");
+        assert_eq!(saved, desc);
+
+        let mut invalid = desc.clone();
+        let ObjectDescription::Instance { scale, .. } = &mut invalid.objects[0] else {
+            panic!("expected an instance");
+        };
+        *scale = Some([1.0, 0.0, 1.0]);
+        assert!(matches!(
+            invalid.build(),
+            Err(Error::InvalidField { field, .. }) if field == "objects[0].scale"
+        ));
+        let mut invalid = desc.clone();
+        let ObjectDescription::Instance { object, .. } = &mut invalid.objects[0] else {
+            panic!("expected an instance");
+        };
+        let ObjectDescription::Sphere { radius, .. } = object.as_mut() else {
+            panic!("expected a sphere");
+        };
+        *radius = -1.0;
+        assert!(matches!(
+            invalid.build(),
+            Err(Error::InvalidField { field, .. }) if field == "objects[0].object.radius"
+        ));
+    }
+
+    #[test]
+    fn test_build_shapes() {
+        let desc = SceneDescription::parse(
+            r#"
+[[objects]]
+type = "plane"
+point = [0.0, -1.0, 0.0]
+normal = [0.0, 2.0, 0.0]
+
+[[objects]]
+type = "quad"
+corner = [-1.0, -1.0, -3.0]
+u = [2.0, 0.0, 0.0]
+v = [0.0, 2.0, 0.0]
+
+[[objects]]
+type = "disk"
+center = [0.0, 0.0, -2.0]
+normal = [0.0, 0.0, 1.0]
+radius = 0.5
+
+[[objects]]
+type = "box"
+min = [-0.2, -0.2, -1.6]
+max = [0.2, 0.2, -1.2]
+
+[[objects]]
+type = "cylinder"
+base = [1.0, -1.0, -2.0]
+axis = [0.0, 1.0, 0.0]
+radius = 0.2
+capped = true
+
+[[objects]]
+type = "cone"
+base = [-1.0, -1.0, -2.0]
+axis = [0.0, 1.0, 0.0]
+radius = 0.3
+
+[[objects]]
+type = "torus"
+center = [0.0, 0.5, -2.0]
+axis = [0.0, 0.0, 1.0]
+major_radius = 0.4
+minor_radius = 0.1
+"#,
+        )
+        .expect("parse");
+        assert_eq!(desc.build().expect("build").objects.len(), 7);
+
+        let mut invalid = desc.clone();
+        let ObjectDescription::Plane { normal, .. } = &mut invalid.objects[0] else {
+            panic!("expected a plane");
+        };
+        *normal = [0.0; 3];
+        assert!(matches!(
+            invalid.build(),
+            Err(Error::InvalidField { field, .. }) if field == "objects[0].normal"
+        ));
+        let mut invalid = desc.clone();
+        let ObjectDescription::Box { max, .. } = &mut invalid.objects[3] else {
+            panic!("expected a box");
+        };
+        max[1] = -0.2;
+        assert!(matches!(
+            invalid.build(),
+            Err(Error::InvalidField { field, .. }) if field == "objects[3].max"
+        ));
+        let mut invalid = desc.clone();
+        let ObjectDescription::Torus { minor_radius, .. } = &mut invalid.objects[6] else {
+            panic!("expected a torus");
+        };
+        *minor_radius = 0.0;
+        assert!(matches!(
+            invalid.build(),
+            Err(Error::InvalidField { field, .. }) if field == "objects[6].minor_radius"
+        ));
+    }
+
+    #[test]
+    fn test_invalid_field() {
+        let mut desc = SceneDescription::parse(SCENE).expect("parse");
+        desc.objects.push(ObjectDescription::Sphere {
+            center: [0.0; 3],
+            radius: 1.0,
+            material: Some("blue".to_string()),
+        });
+        assert!(matches!(
+            desc.build(),
+            Err(Error::InvalidField { field, .. }) if field == "objects[1].material"
+        ));
+        desc.materials.insert(
+            "blue".to_string(),
+            MaterialDescription::Metal {
+                color: [0.0, 0.0, 1.0],
+                fuzz: 2.0,
+            },
+        );
+        assert!(matches!(
+            desc.build(),
+            Err(Error::InvalidField { field, .. }) if field == "materials.blue.fuzz"
+        ));
+        desc.materials
+            .insert("blue".to_string(), MaterialDescription::Glass { ior: 0.0 });
+        assert!(matches!(
+            desc.build(),
+            Err(Error::InvalidField { field, .. }) if field == "materials.blue.ior"
+        ));
+        desc.objects.truncate(1);
+        desc.camera.look_at = desc.camera.position;
+        assert!(matches!(
+            desc.build(),
+            Err(Error::InvalidField { field, .. }) if field == "camera.look_at"
+        ));
+        desc.render.width = 100000;
+        desc.render.height = 100000;
+        assert!(matches!(
+            desc.build(),
+            Err(Error::InvalidField { field, .. }) if field == "render"
+        ));
+    }
+}
@@ -0,0 +1,161 @@
+//! Top-level acceleration structures, placing many instances of shared geometry.
+//!
+//! Each [`Instance`] refers to a shared bottom-level structure, like a [`TriangleMesh`] with
+//! its own hierarchy or a [`Bvh`] of objects, and a transform into the world. A [`Tlas`] is a
+//! [`Bvh`] over the instances, so memory grows with the unique geometry, and moving
+//! instances only rebuilds the hierarchy over the instances.
+//!
+//! [`TriangleMesh`]: crate::objects::mesh::TriangleMesh
+
+use crate::bvh::Bvh;
+use crate::transform::Instance;
+use std::sync::Arc;
+
+/// A hierarchy over instances of shared bottom-level structures,
+/// rebuilt by [`Bvh::update`] after moving them.
+pub type Tlas<B> = Bvh<Instance<Arc<B>>>;
+
+#[cfg(test)]
+mod tests {
+    use crate::objects::mesh::TriangleMesh;
+    use crate::ray::Ray;
+    use crate::scene::{HitEvent, Hittable};
+    use crate::tlas::Tlas;
+    use crate::transform::{axis_angle, Instance, Transform};
+    use crate::types::PositionVec;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use std::sync::Arc;
+
+    /// A unit cube around the origin with outward facing triangles.
+    fn cube() -> TriangleMesh {
+        let mut mesh = TriangleMesh::default();
+        for axis in 0..3 {
+            for side in [-0.5, 0.5] {
+                let mut n = PositionVec::zeros();
+                n[axis] = side;
+                let mut u = PositionVec::zeros();
+                u[(axis + 1) % 3] = 0.5;
+                let mut v = PositionVec::zeros();
+                v[(axis + 2) % 3] = 0.5;
+                if side < 0.0 {
+                    std::mem::swap(&mut u, &mut v);
+                }
+                let base = mesh.positions().len() as u32;
+                mesh.positions_mut()
+                    .extend([n - u - v, n + u - v, n + u + v, n - u + v]);
+                mesh.indices_mut()
+                    .extend([[base, base + 1, base + 2], [base, base + 2, base + 3]]);
+            }
+        }
+        mesh
+    }
+
+    fn random_transform(rng: &mut StdRng) -> Transform {
+        let translation = PositionVec::new(
+            rng.gen_range(-20.0..20.0),
+            rng.gen_range(-20.0..20.0),
+            rng.gen_range(-20.0..20.0),
+        );
+        let axis = PositionVec::new(
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+            rng.gen_range(-1.0..1.0),
+        );
+        let rotation = axis_angle(&axis, rng.gen_range(0.0..6.0));
+        let scale = PositionVec::new(
+            rng.gen_range(0.5..2.0),
+            rng.gen_range(0.5..2.0),
+            rng.gen_range(0.5..2.0),
+        );
+        Transform::from_trs(&translation, &rotation, &scale)
+    }
+
+    /// The mesh with the transform applied to its vertices.
+    fn baked(mesh: &TriangleMesh, transform: &Transform) -> TriangleMesh {
+        TriangleMesh::new(
+            mesh.positions()
+                .iter()
+                .map(|p| transform.point(p))
+                .collect(),
+            mesh.indices().to_vec(),
+        )
+    }
+
+    #[test]
+    fn test_same_hits_as_copies() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let blas = Arc::new(cube());
+        let transforms: Vec<_> = (0..200).map(|_| random_transform(&mut rng)).collect();
+        let tlas = Tlas::new(
+            transforms
+                .iter()
+                .map(|m| Instance::new(blas.clone(), *m))
+                .collect(),
+        );
+        // all instances share one mesh
+        assert_eq!(Arc::strong_count(&blas), 201);
+        let copies: Vec<TriangleMesh> = transforms.iter().map(|m| baked(&blas, m)).collect();
+        let mut hits = 0;
+        for _ in 0..2000 {
+            let ray = Ray {
+                origin: PositionVec::new(0.0, 0.0, 40.0),
+                direction: PositionVec::new(
+                    rng.gen_range(-0.6..0.6),
+                    rng.gen_range(-0.6..0.6),
+                    -1.0,
+                ),
+            };
+            let mut expected: Option<HitEvent> = None;
+            for copy in &copies {
+                let t_max = expected.as_ref().map_or(f64::INFINITY, |hit| hit.t);
+                if let Some(hit) = copy.try_hit(&ray, 0.0, t_max) {
+                    expected = Some(hit);
+                }
+            }
+            let hit = tlas.try_hit(&ray, 0.0, f64::INFINITY);
+            assert_eq!(hit.is_some(), expected.is_some(), "{ray:?}");
+            if let (Some(hit), Some(expected)) = (hit, expected) {
+                assert!((hit.t - expected.t).abs() < 1e-9);
+                assert!((hit.hit_pos - expected.hit_pos).norm() < 1e-9);
+                assert!((hit.surface_nv - expected.surface_nv).norm() < 1e-9);
+                hits += 1;
+            }
+        }
+        assert!(hits > 200, "only {hits} rays hit");
+    }
+
+    #[test]
+    fn test_move_instances() {
+        let blas = Arc::new(cube());
+        let at = |x: f64| Transform::translation(&PositionVec::new(x, 0.0, 0.0));
+        let mut tlas = Tlas::new(vec![
+            Instance::new(blas.clone(), at(-2.0)),
+            Instance::new(blas.clone(), at(2.0)),
+        ]);
+        let down = |x: f64| Ray {
+            origin: PositionVec::new(x, 5.0, 0.0),
+            direction: PositionVec::new(0.0, -1.0, 0.0),
+        };
+        let hit = |tlas: &Tlas<TriangleMesh>, x: f64| -> Option<HitEvent> {
+            tlas.try_hit(&down(x), 0.0, f64::INFINITY)
+        };
+        assert!(hit(&tlas, -2.0).is_some() && hit(&tlas, 2.0).is_some());
+        assert!(hit(&tlas, 6.0).is_none());
+
+        tlas.update(|instances| {
+            instances[1].transform = at(6.0) * Transform::scaling(&PositionVec::repeat(2.0));
+        });
+        assert!(hit(&tlas, 2.0).is_none());
+        let moved = hit(&tlas, 6.0).expect("hit");
+        assert!((moved.t - 4.0).abs() < 1e-12);
+        assert!((moved.surface_nv - PositionVec::new(0.0, 1.0, 0.0)).norm() < 1e-12);
+
+        // removed instances are not hit anymore
+        tlas.update(|instances| {
+            instances.remove(0);
+        });
+        assert!(hit(&tlas, -2.0).is_none());
+        assert_eq!(tlas.len(), 1);
+    }
+}
@@ -0,0 +1,227 @@
+//! Display transform turning linear scene radiance into display-referred values,
+//! applied between the accumulated image and low dynamic range image files.
+
+use crate::ppm::Image;
+use crate::types::{NumColorRatio, Pixel};
+use nalgebra::{Matrix3, Vector3};
+
+type Color = Vector3<NumColorRatio>;
+
+/// Operators compressing unbounded radiance into range [0, 1].
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+#[cfg_attr(
+    feature = "scene-file",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum ToneMapping {
+    /// values out of range are clipped
+    #[default]
+    Clamp,
+    /// `L / (1 + L)` on luminance, preserving hue
+    Reinhard,
+    /// Reinhard with luminance `white` mapped to 1.0
+    ExtendedReinhard { white: NumColorRatio },
+    /// Stephen Hill's fit of the ACES reference rendering and output transforms
+    AcesFilmic,
+    /// minimal approximation of Troy Sobotka's AgX, which desaturates bright colors gracefully
+    #[cfg_attr(feature = "scene-file", serde(rename = "agx"))]
+    AgX,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DisplayTransform {
+    /// exposure adjustment in stops, applied before tone mapping
+    pub exposure: NumColorRatio,
+    pub tone_mapping: ToneMapping,
+    /// encode the result with sRGB transfer function, otherwise keep it linear
+    pub srgb: bool,
+}
+
+impl Default for DisplayTransform {
+    fn default() -> Self {
+        DisplayTransform {
+            exposure: 0.0,
+            tone_mapping: ToneMapping::default(),
+            srgb: true,
+        }
+    }
+}
+
+fn luminance(c: &Color) -> NumColorRatio {
+    0.2126 * c.x + 0.7152 * c.y + 0.0722 * c.z
+}
+
+/// Scale color so that its luminance becomes `f(luminance)`.
+fn map_luminance(c: Color, f: impl Fn(NumColorRatio) -> NumColorRatio) -> Color {
+    let l = luminance(&c);
+    if l <= 0.0 {
+        return Color::zeros();
+    }
+    c * (f(l) / l)
+}
+
+fn aces_filmic(c: Color) -> Color {
+    let input = Matrix3::new(
+        0.59719, 0.35458, 0.04823, //
+        0.07600, 0.90834, 0.01566, //
+        0.02840, 0.13383, 0.83777,
+    );
+    let output = Matrix3::new(
+        1.60475, -0.53108, -0.07367, //
+        -0.10208, 1.10813, -0.00605, //
+        -0.00327, -0.07276, 1.07602,
+    );
+    let v = input * c;
+    let v = v
+        .map(|v| (v * (v + 0.0245786) - 0.000090537) / (v * (0.983729 * v + 0.4329510) + 0.238081));
+    output * v
+}
+
+fn agx(c: Color) -> Color {
+    const MIN_EV: NumColorRatio = -12.47393;
+    const MAX_EV: NumColorRatio = 4.026069;
+    let inset = Matrix3::from_column_slice(&[
+        0.842479062253094,
+        0.0423282422610123,
+        0.0423756549057051,
+        0.0784335999999992,
+        0.878468636469772,
+        0.0784336,
+        0.0792237451477643,
+        0.0791661274605434,
+        0.879142973793104,
+    ]);
+    let outset = Matrix3::from_column_slice(&[
+        1.19687900512017,
+        -0.0528968517574562,
+        -0.0529716355144438,
+        -0.0980208811401368,
+        1.15190312990417,
+        -0.0980434501171241,
+        -0.0990297440797205,
+        -0.0989611768448433,
+        1.15107367264116,
+    ]);
+    let v = (inset * c).map(|v| {
+        let x = (v.max(1e-10).log2().clamp(MIN_EV, MAX_EV) - MIN_EV) / (MAX_EV - MIN_EV);
+        // polynomial approximation of the default contrast curve
+        let x2 = x * x;
+        let x4 = x2 * x2;
+        15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x
+            - 0.00232
+    });
+    // the curve output is display encoded with a 2.2 power, convert it back to linear
+    (outset * v).map(|v| v.max(0.0).powf(2.2))
+}
+
+/// sRGB opto-electronic transfer function (IEC 61966-2-1).
+pub fn srgb_oetf(v: NumColorRatio) -> NumColorRatio {
+    if v <= 0.0031308 {
+        12.92 * v
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// sRGB electro-optical transfer function, the inverse of [`srgb_oetf`].
+pub fn srgb_eotf(v: NumColorRatio) -> NumColorRatio {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+impl ToneMapping {
+    pub fn apply(&self, c: Color) -> Color {
+        match *self {
+            ToneMapping::Clamp => c,
+            ToneMapping::Reinhard => map_luminance(c, |l| l / (1.0 + l)),
+            ToneMapping::ExtendedReinhard { white } => {
+                map_luminance(c, |l| l * (1.0 + l / (white * white)) / (1.0 + l))
+            }
+            ToneMapping::AcesFilmic => aces_filmic(c),
+            ToneMapping::AgX => agx(c),
+        }
+    }
+}
+
+impl DisplayTransform {
+    /// Transform a linear color into display values in range [0, 1].
+    pub fn apply_rgb(
+        &self,
+        r: NumColorRatio,
+        g: NumColorRatio,
+        b: NumColorRatio,
+    ) -> (NumColorRatio, NumColorRatio, NumColorRatio) {
+        // NaN and negative radiance are not displayable
+        let sanitize = |v: NumColorRatio| if v > 0.0 { v } else { 0.0 };
+        let c = Color::new(sanitize(r), sanitize(g), sanitize(b)) * self.exposure.exp2();
+        let c = self.tone_mapping.apply(c).map(|v| v.clamp(0.0, 1.0));
+        let c = if self.srgb { c.map(srgb_oetf) } else { c };
+        (c.x, c.y, c.z)
+    }
+
+    pub fn apply<T: Pixel, U: Pixel>(&self, image: &Image<T>) -> Image<U> {
+        image.map(|p| {
+            let (r, g, b) = self.apply_rgb(p.red(), p.green(), p.blue());
+            U::from_rgb_normalized(r, g, b)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tonemap::{srgb_eotf, srgb_oetf, DisplayTransform, ToneMapping};
+
+    #[test]
+    fn test_srgb_oetf() {
+        assert_eq!(srgb_oetf(0.0), 0.0);
+        assert!((srgb_oetf(1.0) - 1.0).abs() < 1e-12);
+        assert!((srgb_oetf(0.18) - 0.46135).abs() < 1e-4);
+        for v in [0.0, 0.001, 0.18, 0.5, 1.0] {
+            assert!((srgb_eotf(srgb_oetf(v)) - v).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_operators_are_monotonic_and_bounded() {
+        for tone_mapping in [
+            ToneMapping::Clamp,
+            ToneMapping::Reinhard,
+            ToneMapping::ExtendedReinhard { white: 4.0 },
+            ToneMapping::AcesFilmic,
+            ToneMapping::AgX,
+        ] {
+            let t = DisplayTransform {
+                exposure: 0.0,
+                tone_mapping,
+                srgb: true,
+            };
+            let mut last = -1.0;
+            for i in 0..200 {
+                let v = i as f64 * 0.1;
+                let (r, g, b) = t.apply_rgb(v, v, v);
+                assert!((0.0..=1.0).contains(&r), "{tone_mapping:?} out of range");
+                assert!((r - g).abs() < 1e-3 && (g - b).abs() < 1e-3);
+                assert!(r >= last - 1e-9, "{tone_mapping:?} is not monotonic");
+                last = r;
+            }
+            let (r, _, _) = t.apply_rgb(f64::NAN, -1.0, 0.0);
+            assert!(r < 0.01);
+        }
+    }
+
+    #[test]
+    fn test_extended_reinhard_white_point() {
+        let t = DisplayTransform {
+            exposure: 1.0,
+            tone_mapping: ToneMapping::ExtendedReinhard { white: 8.0 },
+            srgb: false,
+        };
+        // exposure of one stop maps 4.0 to the white point
+        let (r, g, b) = t.apply_rgb(4.0, 4.0, 4.0);
+        assert!((r - 1.0).abs() < 1e-12 && (g - 1.0).abs() < 1e-12 && (b - 1.0).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,258 @@
+//! Affine transforms to move, rotate and scale objects.
+//!
+//! A [`Transform`] keeps a 4x4 matrix together with its inverse, so both directions are
+//! available without inverting matrices while rendering. Rotations are unit quaternions.
+//! [`Instance`] places any object into the world by a transform.
+
+use crate::aabb::Aabb;
+use crate::ray::Ray;
+use crate::scene::{Bounded, HitEvent, Hittable};
+use crate::types::{NumPosition, PositionVec, Time};
+use nalgebra::{Matrix4, Point3, UnitQuaternion, Vector3};
+use std::ops::Mul;
+
+/// A rotation as a unit quaternion.
+pub type Rotation = UnitQuaternion<NumPosition>;
+
+/// Rotation by `angle` radians around `axis`, counter-clockwise when the axis points at the
+/// viewer. The identity if the axis is zero.
+pub fn axis_angle(axis: &PositionVec, angle: NumPosition) -> Rotation {
+    match axis.try_normalize(0.0) {
+        Some(axis) => Rotation::from_scaled_axis(axis * angle),
+        None => Rotation::identity(),
+    }
+}
+
+/// An invertible affine transform. `a * b` applies `b` first, then `a`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transform {
+    matrix: Matrix4<NumPosition>,
+    inverse: Matrix4<NumPosition>,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::identity()
+    }
+}
+
+impl Transform {
+    pub fn identity() -> Self {
+        Transform {
+            matrix: Matrix4::identity(),
+            inverse: Matrix4::identity(),
+        }
+    }
+
+    /// The transform of an affine matrix, `None` if it is not invertible.
+    pub fn from_matrix(matrix: Matrix4<NumPosition>) -> Option<Self> {
+        Some(Transform {
+            matrix,
+            inverse: matrix.try_inverse()?,
+        })
+    }
+
+    pub fn translation(offset: &PositionVec) -> Self {
+        Transform {
+            matrix: Matrix4::new_translation(offset),
+            inverse: Matrix4::new_translation(&-offset),
+        }
+    }
+
+    /// Scaling along the axes by nonzero factors, mirroring along axes with negative ones.
+    pub fn scaling(factors: &PositionVec) -> Self {
+        Transform {
+            matrix: Matrix4::new_nonuniform_scaling(factors),
+            inverse: Matrix4::new_nonuniform_scaling(&factors.map(|f| 1.0 / f)),
+        }
+    }
+
+    pub fn rotation(rotation: &Rotation) -> Self {
+        Transform {
+            matrix: rotation.to_homogeneous(),
+            inverse: rotation.inverse().to_homogeneous(),
+        }
+    }
+
+    /// Scale, then rotate, then translate, the usual order of placing an object.
+    pub fn from_trs(translation: &PositionVec, rotation: &Rotation, scale: &PositionVec) -> Self {
+        Transform::translation(translation)
+            * Transform::rotation(rotation)
+            * Transform::scaling(scale)
+    }
+
+    /// Matrix from object space to world space.
+    pub fn matrix(&self) -> &Matrix4<NumPosition> {
+        &self.matrix
+    }
+
+    /// Transform from world space back to object space.
+    pub fn inverse(&self) -> Transform {
+        Transform {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    pub fn point(&self, p: &PositionVec) -> PositionVec {
+        self.matrix.transform_point(&Point3::from(*p)).coords
+    }
+
+    /// Transform a direction, which is not affected by translations.
+    pub fn vector(&self, v: &PositionVec) -> PositionVec {
+        self.matrix.transform_vector(v)
+    }
+
+    /// Transform a unit normal vector by the inverse transpose,
+    /// which keeps it perpendicular to transformed surfaces.
+    pub fn normal(&self, nv: &PositionVec) -> PositionVec {
+        let n: Vector3<NumPosition> = self.inverse.fixed_view::<3, 3>(0, 0).tr_mul(nv);
+        n.normalize()
+    }
+
+    /// Transform a ray without normalizing its direction, so hit times stay the same.
+    pub fn ray(&self, ray: &Ray) -> Ray {
+        Ray {
+            origin: self.point(&ray.origin),
+            direction: self.vector(&ray.direction),
+        }
+    }
+
+    /// Bounds of the transformed box.
+    pub fn aabb(&self, bounds: &Aabb) -> Aabb {
+        if bounds.is_empty() {
+            return *bounds;
+        }
+        Aabb::from_points(&bounds.corners().map(|c| self.point(&c)))
+    }
+}
+
+impl Mul for Transform {
+    type Output = Transform;
+
+    fn mul(self, rhs: Transform) -> Transform {
+        Transform {
+            matrix: self.matrix * rhs.matrix,
+            inverse: rhs.inverse * self.inverse,
+        }
+    }
+}
+
+/// Wraps an object, given in its own object space, to place it into the world.
+pub struct Instance<H> {
+    pub object: H,
+    /// object space to world space
+    pub transform: Transform,
+}
+
+impl<H> Instance<H> {
+    pub fn new(object: H, transform: Transform) -> Self {
+        Instance { object, transform }
+    }
+}
+
+impl<H: Hittable> Hittable for Instance<H> {
+    fn try_hit(&self, ray: &Ray, t1: Time, t2: Time) -> Option<HitEvent> {
+        let local = self.transform.inverse().ray(ray);
+        let mut hit = self.object.try_hit(&local, t1, t2)?;
+        hit.hit_pos = self.transform.point(&hit.hit_pos);
+        hit.surface_nv = self.transform.normal(&hit.surface_nv);
+        hit.shading_nv = self.transform.normal(&hit.shading_nv);
+        Some(hit)
+    }
+}
+
+impl<H: Bounded> Bounded for Instance<H> {
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.transform.aabb(&self.object.bounding_box()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aabb::Aabb;
+    use crate::objects::sphere::Sphere;
+    use crate::ray::Ray;
+    use crate::scene::{Bounded, HitEvent, Hittable};
+    use crate::transform::{axis_angle, Instance, Transform};
+    use crate::types::PositionVec;
+    use nalgebra::Matrix4;
+    use std::f64::consts::FRAC_PI_2;
+
+    fn assert_close(actual: PositionVec, expected: PositionVec) {
+        assert!((actual - expected).norm() < 1e-12, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn test_compose() {
+        let x = PositionVec::new(1.0, 0.0, 0.0);
+        let quarter = Transform::rotation(&axis_angle(&PositionVec::z(), FRAC_PI_2));
+        assert_close(quarter.point(&x), PositionVec::new(0.0, 1.0, 0.0));
+        // scaled first, then rotated, then moved
+        let m = Transform::from_trs(
+            &PositionVec::new(0.0, 0.0, 5.0),
+            &axis_angle(&PositionVec::z(), FRAC_PI_2),
+            &PositionVec::new(2.0, 1.0, 1.0),
+        );
+        assert_close(m.point(&x), PositionVec::new(0.0, 2.0, 5.0));
+        assert_close(m.vector(&x), PositionVec::new(0.0, 2.0, 0.0));
+        assert_close(m.inverse().point(&m.point(&x)), x);
+        let product = m.matrix() * m.inverse().matrix();
+        assert!((product - Matrix4::identity()).norm() < 1e-12);
+        let inverted = Transform::from_matrix(*m.matrix()).expect("invertible");
+        assert_close(inverted.inverse().point(&x), m.inverse().point(&x));
+        assert!(Transform::from_matrix(Matrix4::new_scaling(0.0)).is_none());
+
+        // normals stay perpendicular to a sheared surface
+        let shear = Transform::from_matrix(Matrix4::new(
+            1.0, 1.0, 0.0, 0.0, //
+            0.0, 1.0, 0.0, 0.0, //
+            0.0, 0.0, 1.0, 0.0, //
+            0.0, 0.0, 0.0, 1.0,
+        ))
+        .expect("invertible");
+        let tangent = shear.vector(&x);
+        let n = shear.normal(&PositionVec::new(0.0, 1.0, 0.0));
+        assert!(n.dot(&tangent).abs() < 1e-12);
+        assert!((n.norm() - 1.0).abs() < 1e-12);
+
+        let unit = Aabb::from_points(&[PositionVec::zeros(), PositionVec::new(1.0, 1.0, 1.0)]);
+        let rotated = quarter.aabb(&unit);
+        assert_close(rotated.min, PositionVec::new(-1.0, 0.0, 0.0));
+        assert_close(rotated.max, PositionVec::new(0.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_instance() {
+        // a unit sphere stretched into an ellipsoid, lying along x at z = -5
+        let ellipsoid = Instance::new(
+            Sphere {
+                center: PositionVec::zeros(),
+                radius: 1.0,
+            },
+            Transform::translation(&PositionVec::new(0.0, 0.0, -5.0))
+                * Transform::scaling(&PositionVec::new(3.0, 1.0, 1.0)),
+        );
+        let hit = |origin: PositionVec, direction: PositionVec| -> Option<HitEvent> {
+            ellipsoid.try_hit(&Ray { origin, direction }, 0.0, f64::INFINITY)
+        };
+        let front = hit(PositionVec::new(2.0, 0.0, 0.0), -PositionVec::z()).expect("hit");
+        let z = (1.0f64 - (2.0 / 3.0) * (2.0 / 3.0)).sqrt();
+        assert!((front.t - (5.0 - z)).abs() < 1e-12);
+        assert_close(front.hit_pos, PositionVec::new(2.0, 0.0, -5.0 + z));
+        // the normal of the ellipsoid x^2 / 9 + y^2 + z^2 = 1 is its gradient
+        let gradient = PositionVec::new(2.0 / 9.0, 0.0, z);
+        assert_close(front.surface_nv, gradient.normalize());
+        assert!(hit(PositionVec::new(3.5, 0.0, 0.0), -PositionVec::z()).is_none());
+        // hit times do not depend on the length of the direction
+        let side = hit(
+            PositionVec::new(10.0, 0.0, -5.0),
+            PositionVec::new(-2.0, 0.0, 0.0),
+        );
+        assert!((side.expect("hit").t - 3.5).abs() < 1e-12);
+
+        let bounds = ellipsoid.bounding_box().expect("bounded");
+        assert_close(bounds.min, PositionVec::new(-3.0, -1.0, -6.0));
+        assert_close(bounds.max, PositionVec::new(3.0, 1.0, -4.0));
+    }
+}
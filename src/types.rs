@@ -15,7 +15,7 @@ pub type PositionVec = Vector3<NumPosition>;
 // TODO this is a quick abstraction for 8bit image rendering.
 // Generalize the color depth in the future.
 pub trait Pixel:
-    Send + Sync + Copy + MulAssign<NumColorRatio> + Add<Self> + AddAssign<Self>
+    Send + Sync + Copy + 'static + MulAssign<NumColorRatio> + Add<Self> + AddAssign<Self>
 {
     fn red(&self) -> NumColorRatio;
     fn green(&self) -> NumColorRatio;
@@ -27,9 +27,18 @@ pub trait Pixel:
     fn from_rgb8(r: ColorChannel, g: ColorChannel, b: ColorChannel) -> Self;
     fn black() -> Self;
     fn from<T: Pixel>(value: &T) -> Self;
+
+    /// Multiply each channel by the channel of `filter`, like light passing a colored filter.
+    fn tinted(&self, filter: &Self) -> Self {
+        Self::from_rgb_normalized(
+            self.red() * filter.red(),
+            self.green() * filter.green(),
+            self.blue() * filter.blue(),
+        )
+    }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct PixelU8 {
     rgb: Vector3<NumColor>,
 }
@@ -49,9 +58,9 @@ impl AsRef<Vector3<NumColor>> for PixelU8 {
     }
 }
 
-impl Into<Vector3<NumColor>> for PixelU8 {
-    fn into(self) -> Vector3<NumColor> {
-        self.rgb
+impl From<PixelU8> for Vector3<NumColor> {
+    fn from(value: PixelU8) -> Self {
+        value.rgb
     }
 }
 
@@ -133,7 +142,7 @@ impl Pixel for PixelU8 {
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct PixelF64 {
     rgb: Vector3<NumColorRatio>,
 }
@@ -0,0 +1,465 @@
+//! Minimal zlib (RFC 1950) / DEFLATE (RFC 1951) implementation used by image encoders.
+//! Compression emits a single block with fixed Huffman codes and LZ77 back references,
+//! which is far from optimal but small, fast and understood by every decoder.
+
+const WINDOW_SIZE: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const MAX_CHAIN: usize = 64;
+const HASH_BITS: u32 = 15;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    /// the stream ends before the final block is complete
+    UnexpectedEof,
+    /// invalid zlib header or unsupported compression method
+    InvalidHeader,
+    /// malformed DEFLATE data
+    InvalidData(&'static str),
+    /// Adler-32 checksum of the decompressed data does not match
+    ChecksumMismatch,
+}
+
+/// Compute Adler-32 checksum of `data`.
+pub fn adler32(data: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+    // 5552 is the largest n such that the sums do not overflow u32
+    for chunk in data.chunks(5552) {
+        for &c in chunk {
+            a += c as u32;
+            b += a;
+        }
+        a %= MOD;
+        b %= MOD;
+    }
+    (b << 16) | a
+}
+
+/// Compute CRC-32 (ISO 3309, as used by PNG and gzip) of `data`.
+pub fn crc32(data: &[u8]) -> u32 {
+    crc32_update(0xffff_ffff, data) ^ 0xffff_ffff
+}
+
+/// Feed `data` into a running CRC-32 register. The register should start with `0xffffffff`
+/// and be inverted after the last update.
+pub fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    for &c in data {
+        crc ^= c as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+struct BitWriter {
+    out: Vec<u8>,
+    acc: u64,
+    n: u32,
+}
+
+impl BitWriter {
+    /// Write the lowest `n` bits of `v`, least significant bit first.
+    fn bits(&mut self, v: u32, n: u32) {
+        self.acc |= (v as u64) << self.n;
+        self.n += n;
+        while self.n >= 8 {
+            self.out.push(self.acc as u8);
+            self.acc >>= 8;
+            self.n -= 8;
+        }
+    }
+
+    /// Write a Huffman code, which is packed starting from its most significant bit.
+    fn code(&mut self, code: u32, len: u32) {
+        self.bits(code.reverse_bits() >> (32 - len), len);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.n > 0 {
+            self.out.push(self.acc as u8);
+        }
+        self.out
+    }
+}
+
+fn write_literal(w: &mut BitWriter, lit: u16) {
+    match lit {
+        0..=143 => w.code(0x30 + lit as u32, 8),
+        144..=255 => w.code(0x190 + (lit as u32 - 144), 9),
+        256..=279 => w.code(lit as u32 - 256, 7),
+        _ => w.code(0xc0 + (lit as u32 - 280), 8),
+    }
+}
+
+fn write_match(w: &mut BitWriter, len: usize, dist: usize) {
+    let i = LENGTH_BASE.partition_point(|&b| b as usize <= len) - 1;
+    write_literal(w, 257 + i as u16);
+    w.bits(
+        (len - LENGTH_BASE[i] as usize) as u32,
+        LENGTH_EXTRA[i] as u32,
+    );
+    let j = DIST_BASE.partition_point(|&b| b as usize <= dist) - 1;
+    w.code(j as u32, 5);
+    w.bits((dist - DIST_BASE[j] as usize) as u32, DIST_EXTRA[j] as u32);
+}
+
+fn hash(data: &[u8], i: usize) -> usize {
+    let v = (data[i] as u32) << 16 | (data[i + 1] as u32) << 8 | data[i + 2] as u32;
+    (v.wrapping_mul(0x9e37_79b1) >> (32 - HASH_BITS)) as usize
+}
+
+/// Compress `data` into a raw DEFLATE stream.
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut w = BitWriter {
+        out: Vec::with_capacity(data.len() / 2 + 16),
+        acc: 0,
+        n: 0,
+    };
+    // BFINAL = 1, BTYPE = 01 (fixed Huffman codes)
+    w.bits(1, 1);
+    w.bits(1, 2);
+    // head[h]: latest position + 1 with hash h, prev[i % WINDOW_SIZE]: previous position + 1
+    let mut head = vec![0usize; 1 << HASH_BITS];
+    let mut prev = vec![0usize; WINDOW_SIZE];
+    let insert = |head: &mut [usize], prev: &mut [usize], i: usize| {
+        if i + MIN_MATCH <= data.len() {
+            let h = hash(data, i);
+            prev[i % WINDOW_SIZE] = head[h];
+            head[h] = i + 1;
+        }
+    };
+    let mut i = 0;
+    while i < data.len() {
+        let mut best_len = 0;
+        let mut best_dist = 0;
+        if i + MIN_MATCH <= data.len() {
+            let max_len = MAX_MATCH.min(data.len() - i);
+            let mut candidate = head[hash(data, i)];
+            let mut chain = 0;
+            while candidate > 0 && chain < MAX_CHAIN {
+                let j = candidate - 1;
+                if i - j > WINDOW_SIZE {
+                    break;
+                }
+                let len = data[j..]
+                    .iter()
+                    .zip(&data[i..i + max_len])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if len > best_len {
+                    best_len = len;
+                    best_dist = i - j;
+                    if len == max_len {
+                        break;
+                    }
+                }
+                let next = prev[j % WINDOW_SIZE];
+                if next >= candidate {
+                    // the slot was overwritten by a newer position
+                    break;
+                }
+                candidate = next;
+                chain += 1;
+            }
+        }
+        if best_len >= MIN_MATCH {
+            write_match(&mut w, best_len, best_dist);
+            for k in i..i + best_len {
+                insert(&mut head, &mut prev, k);
+            }
+            i += best_len;
+        } else {
+            write_literal(&mut w, data[i] as u16);
+            insert(&mut head, &mut prev, i);
+            i += 1;
+        }
+    }
+    write_literal(&mut w, 256);
+    w.finish()
+}
+
+/// Compress `data` into a zlib stream.
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    // CM = 8 (deflate), CINFO = 7 (32K window), no preset dictionary, default level;
+    // 0x789c is a multiple of 31 as required by FCHECK
+    let mut out = vec![0x78, 0x9c];
+    out.extend(deflate(data));
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    acc: u64,
+    n: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn bits(&mut self, n: u32) -> Result<u32, Error> {
+        while self.n < n {
+            let c = *self.data.get(self.pos).ok_or(Error::UnexpectedEof)?;
+            self.pos += 1;
+            self.acc |= (c as u64) << self.n;
+            self.n += 8;
+        }
+        let v = (self.acc & ((1u64 << n) - 1)) as u32;
+        self.acc >>= n;
+        self.n -= n;
+        Ok(v)
+    }
+
+    fn align_to_byte(&mut self) {
+        let r = self.n % 8;
+        self.acc >>= r;
+        self.n -= r;
+    }
+}
+
+/// Canonical Huffman decoding table.
+struct Huffman {
+    /// number of codes of each length
+    counts: [u16; 16],
+    /// symbols ordered by code
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; 16];
+        for &l in lengths {
+            counts[l as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0u16; 16];
+        for i in 1..16 {
+            offsets[i] = offsets[i - 1] + counts[i - 1];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (s, &l) in lengths.iter().enumerate() {
+            if l != 0 {
+                symbols[offsets[l as usize] as usize] = s as u16;
+                offsets[l as usize] += 1;
+            }
+        }
+        Huffman { counts, symbols }
+    }
+
+    fn decode(&self, r: &mut BitReader) -> Result<u16, Error> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..16 {
+            code |= r.bits(1)? as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(Error::InvalidData("invalid Huffman code"))
+    }
+}
+
+fn fixed_tables() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+fn dynamic_tables(r: &mut BitReader) -> Result<(Huffman, Huffman), Error> {
+    const ORDER: [usize; 19] = [
+        16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+    ];
+    let n_lit = r.bits(5)? as usize + 257;
+    let n_dist = r.bits(5)? as usize + 1;
+    let n_code = r.bits(4)? as usize + 4;
+    let mut code_lengths = [0u8; 19];
+    for &i in ORDER.iter().take(n_code) {
+        code_lengths[i] = r.bits(3)? as u8;
+    }
+    let code_table = Huffman::new(&code_lengths);
+    let mut lengths = vec![0u8; n_lit + n_dist];
+    let mut i = 0;
+    while i < lengths.len() {
+        let sym = code_table.decode(r)?;
+        let (value, repeat) = match sym {
+            0..=15 => (sym as u8, 1),
+            16 => {
+                if i == 0 {
+                    return Err(Error::InvalidData("repeat without previous length"));
+                }
+                (lengths[i - 1], 3 + r.bits(2)? as usize)
+            }
+            17 => (0, 3 + r.bits(3)? as usize),
+            _ => (0, 11 + r.bits(7)? as usize),
+        };
+        if i + repeat > lengths.len() {
+            return Err(Error::InvalidData("too many code lengths"));
+        }
+        lengths[i..i + repeat].fill(value);
+        i += repeat;
+    }
+    Ok((
+        Huffman::new(&lengths[..n_lit]),
+        Huffman::new(&lengths[n_lit..]),
+    ))
+}
+
+/// Decompress a raw DEFLATE stream.
+pub fn inflate(data: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut r = BitReader {
+        data,
+        pos: 0,
+        acc: 0,
+        n: 0,
+    };
+    let mut out = Vec::new();
+    loop {
+        let last = r.bits(1)? == 1;
+        match r.bits(2)? {
+            0 => {
+                r.align_to_byte();
+                let len = r.bits(16)?;
+                let nlen = r.bits(16)?;
+                if len != !nlen & 0xffff {
+                    return Err(Error::InvalidData("stored block length mismatch"));
+                }
+                for _ in 0..len {
+                    out.push(r.bits(8)? as u8);
+                }
+            }
+            t @ (1 | 2) => {
+                let (lit, dist) = if t == 1 {
+                    fixed_tables()
+                } else {
+                    dynamic_tables(&mut r)?
+                };
+                loop {
+                    let sym = lit.decode(&mut r)? as usize;
+                    if sym < 256 {
+                        out.push(sym as u8);
+                        continue;
+                    }
+                    if sym == 256 {
+                        break;
+                    }
+                    let i = sym - 257;
+                    if i >= LENGTH_BASE.len() {
+                        return Err(Error::InvalidData("invalid length symbol"));
+                    }
+                    let len = LENGTH_BASE[i] as usize + r.bits(LENGTH_EXTRA[i] as u32)? as usize;
+                    let j = dist.decode(&mut r)? as usize;
+                    if j >= DIST_BASE.len() {
+                        return Err(Error::InvalidData("invalid distance symbol"));
+                    }
+                    let d = DIST_BASE[j] as usize + r.bits(DIST_EXTRA[j] as u32)? as usize;
+                    if d > out.len() {
+                        return Err(Error::InvalidData("distance too far back"));
+                    }
+                    let start = out.len() - d;
+                    for k in 0..len {
+                        out.push(out[start + k]);
+                    }
+                }
+            }
+            _ => return Err(Error::InvalidData("invalid block type")),
+        }
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+/// Decompress a zlib stream and verify its checksum.
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, Error> {
+    if data.len() < 6 {
+        return Err(Error::UnexpectedEof);
+    }
+    let (cmf, flg) = (data[0], data[1]);
+    if cmf & 0x0f != 8 || !((cmf as u16) << 8 | flg as u16).is_multiple_of(31) || flg & 0x20 != 0 {
+        return Err(Error::InvalidHeader);
+    }
+    let out = inflate(&data[2..])?;
+    let tail = &data[data.len() - 4..];
+    if adler32(&out).to_be_bytes() != tail {
+        return Err(Error::ChecksumMismatch);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::zlib;
+
+    #[test]
+    fn test_checksums() {
+        assert_eq!(zlib::crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(zlib::adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let mut data = Vec::new();
+        for i in 0..100_000u32 {
+            data.push((i % 251) as u8 ^ (i / 1000) as u8);
+        }
+        data.extend(std::iter::repeat_n(7u8, 5000));
+        data.extend(b"abcabcabcabcd");
+        let compressed = zlib::compress(&data);
+        assert!(compressed.len() < data.len() / 4);
+        assert_eq!(zlib::decompress(&compressed).expect("decompress"), data);
+        assert_eq!(zlib::decompress(&zlib::compress(b"")).expect("empty"), b"");
+    }
+
+    #[test]
+    fn test_inflate_stored_and_dynamic() {
+        // produced by Python's zlib with compression level 0
+        let stored = [
+            0x78, 0x01, 0x01, 0x05, 0x00, 0xfa, 0xff, 0x68, 0x65, 0x6c, 0x6c, 0x6f, 0x06, 0x2c,
+            0x02, 0x15,
+        ];
+        assert_eq!(zlib::decompress(&stored).expect("stored"), b"hello");
+        let mut corrupted = stored;
+        corrupted[15] ^= 1;
+        assert_eq!(
+            zlib::decompress(&corrupted),
+            Err(zlib::Error::ChecksumMismatch)
+        );
+        // produced by Python's zlib with compression level 9, using dynamic Huffman codes
+        let dynamic = [
+            0x78, 0xda, 0x25, 0x8a, 0x89, 0x09, 0x00, 0x00, 0x08, 0x02, 0x67, 0x3d, 0x6d, 0xff,
+            0x19, 0xd2, 0x02, 0xc5, 0x17, 0x01, 0x92, 0x5c, 0x39, 0x4e, 0x70, 0x16, 0x1c, 0xc9,
+            0xd0, 0x98, 0x4b, 0x8b, 0xff, 0xa4, 0xf3, 0xc0, 0x02, 0xba, 0x1a, 0x16, 0xe4,
+        ];
+        assert_eq!(
+            zlib::decompress(&dynamic).expect("dynamic"),
+            b"abaaabbbcaaabaaabadbadabaaaaacbaacaadababbbaaacabaaabcaacdaa"
+        );
+    }
+}